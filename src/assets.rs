@@ -0,0 +1,107 @@
+//! Where the game's assets are read from, through the `vfs`.
+//!
+//! Built with the `embed-assets` feature, every file of `assets/` is part of
+//! the binary, which may then be shipped on its own. Files next to the
+//! binary, in `assets.tar` or an `assets` directory, and in the `assets`
+//! directory of where the game is launched from, take precedence in that
+//! order, so that assets may be replaced and levels saved.
+
+use ::phi::vfs::{self, Archive, Directory};
+use ::std::env;
+use ::std::path::PathBuf;
+
+/// Where the paths of assets start, e.g. `assets/spaceship.png`.
+const MOUNT_POINT: &'static str = "assets";
+const ARCHIVE: &'static str = "assets.tar";
+
+#[cfg(feature = "embed-assets")]
+const EMBEDDED: &'static [(&'static str, &'static [u8])] = &[
+    ("asteroid.png", include_bytes!("../assets/asteroid.png")),
+    ("belligerent.ttf", include_bytes!("../assets/belligerent.ttf")),
+    ("boss.png", include_bytes!("../assets/boss.png")),
+    ("bullet.png", include_bytes!("../assets/bullet.png")),
+    ("difficulty.cfg", include_bytes!("../assets/difficulty.cfg")),
+    ("enemies.png", include_bytes!("../assets/enemies.png")),
+    ("enemy_bullet.png", include_bytes!("../assets/enemy_bullet.png")),
+    ("explosion.png", include_bytes!("../assets/explosion.png")),
+    ("levels/01-outer-belt.cfg", include_bytes!("../assets/levels/01-outer-belt.cfg")),
+    ("levels/02-ambush.cfg", include_bytes!("../assets/levels/02-ambush.cfg")),
+    ("powerups.png", include_bytes!("../assets/powerups.png")),
+    ("spaceship.png", include_bytes!("../assets/spaceship.png")),
+    ("starBG.png", include_bytes!("../assets/starBG.png")),
+    ("starFG.png", include_bytes!("../assets/starFG.png")),
+    ("starMG.png", include_bytes!("../assets/starMG.png")),
+];
+
+#[cfg(feature = "embed-assets")]
+fn mount_embedded() {
+    vfs::mount(MOUNT_POINT, vfs::Embedded::new(EMBEDDED));
+}
+
+#[cfg(not(feature = "embed-assets"))]
+fn mount_embedded() {}
+
+/// Mounts every source of assets, the embedded ones first.
+pub fn mount() {
+    mount_embedded();
+
+    let exe_dir: Option<PathBuf> = env::current_exe().ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.to_path_buf()));
+
+    if let Some(dir) = exe_dir {
+        let archive = dir.join(ARCHIVE);
+        if archive.exists() {
+            match Archive::open(&archive) {
+                Ok(archive) => vfs::mount(MOUNT_POINT, archive),
+                Err(err) => warn!("Could not open {}: {}", archive.display(), err),
+            }
+        }
+
+        vfs::mount(MOUNT_POINT, Directory::new(dir.join(MOUNT_POINT)));
+    }
+
+    vfs::mount(MOUNT_POINT, Directory::new(MOUNT_POINT));
+}
+
+#[cfg(all(test, feature = "embed-assets"))]
+mod tests {
+    use super::*;
+    use ::phi::vfs::{Embedded, Mount};
+    use ::std::fs;
+    use ::std::path::Path;
+
+    /// Every file of `dir` and its subdirectories, by path from `dir`.
+    fn files_in(dir: &Path, prefix: &str, files: &mut Vec<String>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let entry = entry.unwrap();
+            let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+
+            if entry.file_type().unwrap().is_dir() {
+                files_in(&entry.path(), &format!("{}/", name), files);
+            } else {
+                files.push(name);
+            }
+        }
+    }
+
+    #[test]
+    fn every_asset_is_embedded() {
+        let mut files = Vec::new();
+        files_in(Path::new(MOUNT_POINT), "", &mut files);
+        files.sort();
+
+        let embedded: Vec<&str> = EMBEDDED.iter().map(|&(name, _)| name).collect();
+        assert_eq!(embedded, files);
+    }
+
+    #[test]
+    fn embedded_files_are_found_by_path() {
+        let mount = Embedded::new(EMBEDDED);
+
+        assert!(mount.contains(Path::new("./levels/01-outer-belt.cfg")));
+        assert!(!mount.contains(Path::new("levels")));
+        assert_eq!(mount.list(Path::new("levels")), vec!["01-outer-belt.cfg", "02-ambush.cfg"]);
+        assert_eq!(mount.read(Path::new("difficulty.cfg")).unwrap().unwrap(),
+                   fs::read("assets/difficulty.cfg").unwrap());
+    }
+}
@@ -1,15 +1,151 @@
+#[macro_use]
+extern crate log;
 extern crate rand;
 extern crate sdl2;
 extern crate sdl2_image;
 extern crate sdl2_ttf;
 
+mod assets;
 mod phi;
 mod views;
 
+use ::phi::{Phi, View, ViewAction};
+use ::phi::console::{self, Command};
+use ::phi::logger::{self, Filter};
+use ::phi::net::Conditions;
+use ::views::error::{ErrorView, or_error};
+use ::views::game::netplay::NetPlayView;
+use ::views::shared::BgSet;
+use ::std::path::PathBuf;
+
 // use ::phi::{Events, Phi, View, ViewAction};
 
+const USAGE: &'static str = "\
+Usage: arcade-shooter [OPTIONS]
+
+    --net-test [LATENCY_MS JITTER_MS LOSS_PERCENT]
+        Play both sides of a networked game over a simulated network, by
+        default with 60ms of latency, 30ms of jitter and 5% packet loss.
+    --net LOCAL_ADDRESS PEER_ADDRESS PLAYER
+        Play a networked game over UDP as player 1 or 2, e.g.
+        --net 0.0.0.0:7000 192.168.1.20:7000 1
+
+Environment:
+
+    ARCADE_SHOOTER_LOG=SPEC
+        Which messages to log, as a default level followed by levels for
+        some modules, e.g. warn,phi::events=trace. Defaults to info.
+    ARCADE_SHOOTER_LOG_FILE=PATH
+        Also log to a file, rotated once it reaches 1 MiB.";
+
+/// Starts logging as asked for by the environment.
+fn init_logging() {
+    let spec = ::std::env::var("ARCADE_SHOOTER_LOG").unwrap_or(String::new());
+    let path = ::std::env::var_os("ARCADE_SHOOTER_LOG_FILE").map(PathBuf::from);
+
+    let (filter, spec_error) = match Filter::parse(&spec) {
+        Ok(filter) => (filter, None),
+        Err(err) => (Filter::new(), Some(err)),
+    };
+
+    if let Err(err) = logger::init(filter, path.as_ref().map(|path| path.as_path())) {
+        eprintln!("Could not start logging: {}", err);
+    }
+    if let Some(err) = spec_error {
+        warn!("Invalid ARCADE_SHOOTER_LOG: {}. Using the default levels.", err);
+    }
+}
+
+/// The first view, as asked for on the command line, or the main menu. If
+/// it cannot be created, e.g. because an asset is missing, an error screen
+/// is shown instead.
+fn first_view(phi: &mut Phi, args: &[String]) -> Result<Box<View>, String> {
+    let backgrounds = match BgSet::new(&mut phi.renderer) {
+        Ok(backgrounds) => backgrounds,
+        Err(err) => return Ok(Box::new(ErrorView::new(&err))),
+    };
+
+    match args.first().map(|arg| &arg[..]) {
+        None =>
+            Ok(Box::new(::views::main_menu::MainMenuView::with_backgrounds(phi, backgrounds))),
+
+        Some("--net-test") => {
+            let numbers: Vec<f64> = try!(args[1..].iter()
+                .map(|arg| arg.parse().map_err(|_| format!("not a number: {}", arg)))
+                .collect());
+
+            let conditions = match numbers.len() {
+                0 => Conditions { latency: 0.06, jitter: 0.03, loss: 0.05 },
+                3 => Conditions {
+                    latency: numbers[0] / 1000.0,
+                    jitter: numbers[1] / 1000.0,
+                    loss: numbers[2] / 100.0,
+                },
+                _ => return Err("--net-test takes either no or three numbers".to_string()),
+            };
+
+            Ok(or_error(NetPlayView::loopback_test(phi, backgrounds, conditions)))
+        },
+
+        Some("--net") if args.len() == 4 => {
+            let local = match &args[3][..] {
+                "1" => 0,
+                "2" => 1,
+                _ => return Err("the player must be 1 or 2".to_string()),
+            };
+
+            Ok(or_error(NetPlayView::udp(phi, backgrounds, local, &args[1], &args[2])))
+        },
+
+        Some(_) =>
+            Err("unknown arguments".to_string()),
+    }
+}
+
+/// Switches to the view called `name`, from the console.
+fn change_view(phi: &mut Phi, name: &str) -> Result<ViewAction, String> {
+    let backgrounds = try!(BgSet::new(&mut phi.renderer).map_err(|err| err.to_string()));
+
+    let view: Box<View> = match name {
+        "menu" => Box::new(::views::main_menu::MainMenuView::with_backgrounds(phi, backgrounds)),
+        "game" => Box::new(try!(::views::game::ShipView::with_backgrounds(phi, backgrounds)
+            .map_err(|err| err.to_string()))),
+        "levels" => Box::new(::views::level_select::LevelSelectView::with_backgrounds(phi, backgrounds)),
+        "options" => Box::new(::views::options::OptionsView::with_backgrounds(phi, backgrounds)),
+        _ => return Err(format!("no view called `{}`", name)),
+    };
+
+    Ok(ViewAction::ChangeView(view))
+}
+
 fn main() {
-    ::phi::spawn("Arcade Shooter", |phi| {
-        Box::new(::views::main_menu::MainMenuView::new(phi))
+    let args: Vec<String> = ::std::env::args().skip(1).collect();
+    init_logging();
+    assets::mount();
+
+    let result = ::phi::spawn("Arcade Shooter", |phi| {
+        phi.debug.set_font(::views::shared::FONT_PATH);
+        phi.profiler.set_font(::views::shared::FONT_PATH);
+        phi.console.set_font(::views::shared::FONT_PATH);
+        phi.console.register(
+            Command { name: "view", usage: "<menu|game|levels|options>", help: "Go to another view" },
+            |phi, args| {
+                let name: String = try!(console::arg(args, 0));
+                change_view(phi, &name)
+            });
+
+        match first_view(phi, &args) {
+            Ok(view) => view,
+            Err(err) => {
+                error!("{}", err);
+                eprintln!("{}", USAGE);
+                or_error(::views::main_menu::MainMenuView::new(phi))
+            },
+        }
     });
+
+    if let Err(err) = result {
+        error!("{}", err);
+        ::std::process::exit(1);
+    }
 }
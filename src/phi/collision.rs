@@ -0,0 +1,269 @@
+use ::phi::data::Rectangle;
+
+/// Alpha values at or above this are considered solid when building a mask.
+pub const ALPHA_THRESHOLD: u8 = 128;
+
+const WORD_BITS: usize = 64;
+
+/// A 1-bit-per-pixel map of which pixels of an image region are solid.
+/// Used for pixel-perfect collision detection between sprites.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CollisionMask {
+    w: usize,
+    h: usize,
+    /// The number of `u64` words used to store a single row.
+    stride: usize,
+    /// Row-major bits, with the least significant bit of each word on the left.
+    bits: Vec<u64>,
+}
+
+impl CollisionMask {
+    /// Creates a mask in which every pixel is empty.
+    pub fn empty(w: usize, h: usize) -> CollisionMask {
+        let stride = (w + WORD_BITS - 1) / WORD_BITS;
+
+        CollisionMask {
+            w: w,
+            h: h,
+            stride: stride,
+            bits: vec![0; stride * h],
+        }
+    }
+
+    /// Creates a mask from a row-major buffer of alpha values, one per pixel.
+    /// Pixels whose alpha is at least `threshold` are solid.
+    pub fn from_alpha(alpha: &[u8], w: usize, h: usize, threshold: u8) -> CollisionMask {
+        assert!(alpha.len() >= w * h);
+
+        let mut mask = CollisionMask::empty(w, h);
+
+        for y in 0..h {
+            for x in 0..w {
+                if alpha[y * w + x] >= threshold {
+                    mask.set(x, y, true);
+                }
+            }
+        }
+
+        mask
+    }
+
+    /// Returns the dimensions of the mask, in pixels.
+    pub fn size(&self) -> (usize, usize) {
+        (self.w, self.h)
+    }
+
+    /// Returns whether the pixel at `(x, y)` is solid. Pixels outside of the
+    /// mask are never solid.
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        if x >= self.w || y >= self.h {
+            return false;
+        }
+
+        let word = self.bits[y * self.stride + x / WORD_BITS];
+        (word >> (x % WORD_BITS)) & 1 == 1
+    }
+
+    /// Marks the pixel at `(x, y)` as solid or empty.
+    pub fn set(&mut self, x: usize, y: usize, solid: bool) {
+        assert!(x < self.w && y < self.h);
+
+        let word = &mut self.bits[y * self.stride + x / WORD_BITS];
+        let bit = 1 << (x % WORD_BITS);
+
+        if solid {
+            *word |= bit;
+        } else {
+            *word &= !bit;
+        }
+    }
+
+    /// Returns the number of solid pixels.
+    pub fn count(&self) -> usize {
+        self.bits.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// Returns a new mask covering the sub-region `rect` of this one.
+    /// Returns `None` if `rect` does not fit inside of the mask.
+    pub fn region(&self, rect: Rectangle) -> Option<CollisionMask> {
+        let bounds = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            w: self.w as f64,
+            h: self.h as f64,
+        };
+
+        if !bounds.contains(rect) {
+            return None;
+        }
+
+        let (left, top) = (rect.x as usize, rect.y as usize);
+        let mut mask = CollisionMask::empty(rect.w as usize, rect.h as usize);
+
+        for y in 0..mask.h {
+            for x in 0..mask.w {
+                if self.get(left + x, top + y) {
+                    mask.set(x, y, true);
+                }
+            }
+        }
+
+        Some(mask)
+    }
+
+    /// Tests whether this mask, drawn over `dest`, has any solid pixel in
+    /// common with `other` drawn over `other_dest`.
+    ///
+    /// Masks are stretched to fit their destination, the same way sprites are
+    /// when they are rendered.
+    pub fn overlaps(&self, dest: Rectangle,
+                    other: &CollisionMask, other_dest: Rectangle) -> bool {
+        // Cheap rejection: the masks cannot touch if their boxes do not.
+        if !dest.overlaps(other_dest) {
+            return false;
+        }
+
+        if dest.w <= 0.0 || dest.h <= 0.0 || other_dest.w <= 0.0 || other_dest.h <= 0.0 {
+            return false;
+        }
+
+        // Only the intersection of both destinations needs to be checked.
+        let left = dest.x.max(other_dest.x).floor();
+        let right = (dest.x + dest.w).min(other_dest.x + other_dest.w).ceil();
+        let top = dest.y.max(other_dest.y).floor();
+        let bottom = (dest.y + dest.h).min(other_dest.y + other_dest.h).ceil();
+
+        // Scale from screen pixels to mask pixels
+        let (sx, sy) = (self.w as f64 / dest.w, self.h as f64 / dest.h);
+        let (ox, oy) = (other.w as f64 / other_dest.w, other.h as f64 / other_dest.h);
+
+        let mut y = top;
+        while y < bottom {
+            // Sample at the centre of the screen pixel
+            let cy = y + 0.5;
+            let (self_y, other_y) = ((cy - dest.y) * sy, (cy - other_dest.y) * oy);
+
+            if self_y >= 0.0 && other_y >= 0.0 {
+                let mut x = left;
+                while x < right {
+                    let cx = x + 0.5;
+                    let (self_x, other_x) = ((cx - dest.x) * sx, (cx - other_dest.x) * ox);
+
+                    if self_x >= 0.0 && other_x >= 0.0 &&
+                        self.get(self_x as usize, self_y as usize) &&
+                        other.get(other_x as usize, other_y as usize) {
+                        return true;
+                    }
+
+                    x += 1.0;
+                }
+            }
+
+            y += 1.0;
+        }
+
+        false
+    }
+}
+
+
+/// Tests whether two objects drawn over `a` and `b` touch. When both of
+/// their masks are known, only their solid pixels count; otherwise their
+/// rectangles are enough.
+pub fn collides(a: Rectangle, a_mask: Option<&CollisionMask>,
+                b: Rectangle, b_mask: Option<&CollisionMask>) -> bool {
+    match (a_mask, b_mask) {
+        (Some(a_mask), Some(b_mask)) => a_mask.overlaps(a, b_mask, b),
+        _ => a.overlaps(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: f64, y: f64, w: f64, h: f64) -> Rectangle {
+        Rectangle { x: x, y: y, w: w, h: h }
+    }
+
+    /// A mask drawn as rows of `#` for solid pixels and `.` for empty ones.
+    fn mask(rows: &[&str]) -> CollisionMask {
+        let alpha: Vec<u8> = rows.iter()
+            .flat_map(|row| row.chars().map(|c| if c == '#' { 255 } else { 0 }))
+            .collect();
+        CollisionMask::from_alpha(&alpha, rows[0].len(), rows.len(), ALPHA_THRESHOLD)
+    }
+
+    #[test]
+    fn solid_pixels_are_those_above_the_threshold() {
+        let mask = CollisionMask::from_alpha(&[0, 127, 128, 255], 2, 2, ALPHA_THRESHOLD);
+
+        assert!(!mask.get(0, 0));
+        assert!(!mask.get(1, 0));
+        assert!(mask.get(0, 1));
+        assert!(mask.get(1, 1));
+        assert!(!mask.get(2, 1));
+        assert_eq!(mask.count(), 2);
+    }
+
+    #[test]
+    fn rows_wider_than_a_word_keep_apart() {
+        let mut mask = CollisionMask::empty(130, 2);
+        mask.set(129, 0, true);
+        mask.set(64, 1, true);
+
+        assert!(mask.get(129, 0));
+        assert!(!mask.get(129, 1));
+        assert!(mask.get(64, 1));
+        assert_eq!(mask.count(), 2);
+
+        mask.set(129, 0, false);
+        assert_eq!(mask.count(), 1);
+    }
+
+    #[test]
+    fn regions_copy_a_part_of_the_mask() {
+        let mask = mask(&["#...",
+                          ".##.",
+                          "...#"]);
+
+        assert_eq!(mask.region(rect(1.0, 1.0, 3.0, 2.0)), Some(self::mask(&["##.", "..#"])));
+        assert_eq!(mask.region(rect(2.0, 0.0, 3.0, 1.0)), None);
+    }
+
+    #[test]
+    fn only_solid_pixels_overlap() {
+        // Two triangles whose boxes overlap, but whose solid corners do not
+        let top_left = mask(&["##",
+                              "#."]);
+        let bottom_right = mask(&[".#",
+                                  "##"]);
+
+        assert!(!top_left.overlaps(rect(0.0, 0.0, 20.0, 20.0), &bottom_right, rect(10.0, 10.0, 20.0, 20.0)));
+        assert!(top_left.overlaps(rect(0.0, 0.0, 20.0, 20.0), &bottom_right, rect(5.0, 5.0, 20.0, 20.0)));
+        assert!(!top_left.overlaps(rect(0.0, 0.0, 20.0, 20.0), &bottom_right, rect(30.0, 0.0, 20.0, 20.0)));
+    }
+
+    #[test]
+    fn masks_stretch_to_their_destination() {
+        let dot = mask(&["#"]);
+        let corner = mask(&["...",
+                            "...",
+                            "..#"]);
+
+        assert!(corner.overlaps(rect(0.0, 0.0, 30.0, 30.0), &dot, rect(25.0, 25.0, 2.0, 2.0)));
+        assert!(!corner.overlaps(rect(0.0, 0.0, 30.0, 30.0), &dot, rect(15.0, 15.0, 2.0, 2.0)));
+        assert!(!corner.overlaps(rect(0.0, 0.0, 0.0, 30.0), &dot, rect(0.0, 0.0, 30.0, 30.0)));
+    }
+
+    #[test]
+    fn rectangles_are_used_without_both_masks() {
+        let empty = CollisionMask::empty(4, 4);
+        let (a, b) = (rect(0.0, 0.0, 10.0, 10.0), rect(5.0, 5.0, 10.0, 10.0));
+
+        assert!(!collides(a, Some(&empty), b, Some(&empty)));
+        assert!(collides(a, Some(&empty), b, None));
+        assert!(collides(a, None, b, None));
+        assert!(!collides(a, None, rect(20.0, 0.0, 5.0, 5.0), None));
+    }
+}
@@ -0,0 +1,497 @@
+use ::phi::data::Rectangle;
+use ::phi::error::PhiError;
+use ::phi::vfs;
+use ::std::env;
+use ::std::fmt;
+use ::std::fs::{self, File};
+use ::std::io::{self, Read, Write};
+use ::std::path::{Path, PathBuf};
+use ::std::str::FromStr;
+
+/// A single `key = value` line of a configuration file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    /// The section the entry appears in, or `""` before the first one.
+    pub section: String,
+    pub key: String,
+    pub value: String,
+    /// The line the entry appears on, starting at 1, or 0 if it was not read
+    /// from a file.
+    pub line: usize,
+}
+
+/// Something wrong with a configuration file, and where it is.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(ParseError),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::Io(ref err) => write!(f, "{}", err),
+            ConfigError::Parse(ref err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(err: io::Error) -> ConfigError {
+        ConfigError::Io(err)
+    }
+}
+
+impl From<ParseError> for ConfigError {
+    fn from(err: ParseError) -> ConfigError {
+        ConfigError::Parse(err)
+    }
+}
+
+/// Why something saved in a configuration file, such as a game in progress,
+/// could not be loaded back.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The file could not be read
+    Config(ConfigError),
+    /// Something the game needs could not be loaded
+    Asset(PhiError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Config(ref err) => write!(f, "{}", err),
+            LoadError::Asset(ref err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<ConfigError> for LoadError {
+    fn from(err: ConfigError) -> LoadError {
+        LoadError::Config(err)
+    }
+}
+
+impl From<ParseError> for LoadError {
+    fn from(err: ParseError) -> LoadError {
+        LoadError::Config(ConfigError::Parse(err))
+    }
+}
+
+impl From<PhiError> for LoadError {
+    fn from(err: PhiError) -> LoadError {
+        LoadError::Asset(err)
+    }
+}
+
+
+/// The contents of a simple configuration file, made of `key = value` lines
+/// grouped in `[sections]`. Empty lines and lines starting with `#` are
+/// ignored.
+///
+/// ```text
+/// version = 1
+///
+/// [video]
+/// mode = windowed
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    entries: Vec<Entry>,
+}
+
+impl Config {
+    pub fn new() -> Config {
+        Config {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Reads a configuration from its text.
+    pub fn parse(text: &str) -> Result<Config, ParseError> {
+        let mut config = Config::new();
+        let mut section = String::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line_number = i + 1;
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if line.starts_with('[') {
+                if !line.ends_with(']') || line.len() < 3 {
+                    return Err(ParseError {
+                        line: line_number,
+                        message: format!("malformed section header `{}`", line),
+                    });
+                }
+
+                section = line[1..line.len() - 1].trim().to_string();
+                continue;
+            }
+
+            let equals = match line.find('=') {
+                Some(equals) => equals,
+                None => return Err(ParseError {
+                    line: line_number,
+                    message: format!("expected `key = value`, found `{}`", line),
+                }),
+            };
+
+            let key = line[..equals].trim();
+            if key.is_empty() {
+                return Err(ParseError {
+                    line: line_number,
+                    message: "missing key before `=`".to_string(),
+                });
+            }
+
+            config.entries.push(Entry {
+                section: section.clone(),
+                key: key.to_string(),
+                value: line[equals + 1..].trim().to_string(),
+                line: line_number,
+            });
+        }
+
+        Ok(config)
+    }
+
+    /// Reads and parses the configuration file at `path`.
+    pub fn load(path: &Path) -> Result<Config, ConfigError> {
+        let mut text = String::new();
+        try!(try!(File::open(path)).read_to_string(&mut text));
+        Ok(try!(Config::parse(&text)))
+    }
+
+    /// Like `load`, for a file shipped with the game, found through the
+    /// `vfs`.
+    pub fn load_asset(path: &Path) -> Result<Config, ConfigError> {
+        let text = try!(String::from_utf8(try!(vfs::read(path)))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)));
+        Ok(try!(Config::parse(&text)))
+    }
+
+    /// Writes the configuration to `path`, creating the parent directories if
+    /// they do not exist.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            try!(fs::create_dir_all(parent));
+        }
+
+        let mut file = try!(File::create(path));
+        file.write_all(self.to_string().as_bytes())
+    }
+
+    /// All of the entries, in the order they were read or added.
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Returns the entry for `key` in `section`. If it appears several times,
+    /// the last one wins.
+    pub fn entry(&self, section: &str, key: &str) -> Option<&Entry> {
+        self.entries.iter().rev()
+            .find(|entry| entry.section == section && entry.key == key)
+    }
+
+    /// Returns the value of `key` in `section`, if it is present.
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.entry(section, key).map(|entry| &entry.value[..])
+    }
+
+    /// Returns the value of `key` in `section` converted to `T`, `Ok(None)` if
+    /// it is missing, or an error pointing at its line if it is invalid.
+    pub fn parsed<T: FromStr>(&self, section: &str, key: &str) -> Result<Option<T>, ParseError> {
+        match self.entry(section, key) {
+            Some(entry) => entry.value.parse().map(Some).map_err(|_| ParseError {
+                line: entry.line,
+                message: format!("invalid value `{}` for `{}`", entry.value, key),
+            }),
+            None => Ok(None),
+        }
+    }
+
+    /// Sets the value of `key` in `section`, replacing any previous value.
+    pub fn set<T: ToString>(&mut self, section: &str, key: &str, value: T) {
+        self.entries.retain(|entry| !(entry.section == section && entry.key == key));
+        self.entries.push(Entry {
+            section: section.to_string(),
+            key: key.to_string(),
+            value: value.to_string(),
+            line: 0,
+        });
+    }
+
+    /// Appends an entry for `key` in `section`, keeping any previous value,
+    /// for files where a key may appear several times.
+    pub fn add<T: ToString>(&mut self, section: &str, key: &str, value: T) {
+        self.entries.push(Entry {
+            section: section.to_string(),
+            key: key.to_string(),
+            value: value.to_string(),
+            line: 0,
+        });
+    }
+}
+
+impl fmt::Display for Config {
+    /// Writes the configuration back in the format read by `parse`, grouping
+    /// entries by section. Those of no section come first, since they would
+    /// be read back into the section before them.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut sections: Vec<&str> = vec![""];
+        for entry in &self.entries {
+            if !sections.contains(&&entry.section[..]) {
+                sections.push(&entry.section);
+            }
+        }
+
+        let has_root = self.entries.iter().any(|entry| entry.section.is_empty());
+
+        for (i, section) in sections.iter().enumerate() {
+            if !section.is_empty() {
+                if i > 1 || has_root {
+                    try!(writeln!(f, ""));
+                }
+                try!(writeln!(f, "[{}]", section));
+            }
+
+            for entry in self.entries.iter().filter(|entry| entry.section == *section) {
+                try!(writeln!(f, "{} = {}", entry.key, entry.value));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+
+/// Returns the directory where the application called `app` should store its
+/// configuration files, following the conventions of the platform.
+pub fn config_dir(app: &str) -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::home_dir().map(|home| home.join("Library").join("Application Support"))
+    } else {
+        env::var_os("XDG_CONFIG_HOME").map(PathBuf::from)
+            .or_else(|| env::home_dir().map(|home| home.join(".config")))
+    };
+
+    base.map(|base| base.join(app))
+}
+
+/// The state of an object, written as numbers separated by spaces.
+pub struct Line {
+    text: String,
+}
+
+impl Line {
+    pub fn new() -> Line {
+        Line { text: String::new() }
+    }
+
+    pub fn word<T: ToString>(mut self, value: T) -> Line {
+        if !self.text.is_empty() {
+            self.text.push(' ');
+        }
+        self.text.push_str(&value.to_string());
+        self
+    }
+
+    pub fn flag(self, value: bool) -> Line {
+        self.word(if value { 1 } else { 0 })
+    }
+
+    pub fn pair(self, (x, y): (f64, f64)) -> Line {
+        self.word(x).word(y)
+    }
+
+    pub fn rect(self, rect: Rectangle) -> Line {
+        self.word(rect.x).word(rect.y).word(rect.w).word(rect.h)
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// Reads back the words of a `Line`, in the order they were written.
+pub struct Fields<'a> {
+    entry: &'a Entry,
+    words: Vec<&'a str>,
+    next: usize,
+}
+
+impl<'a> Fields<'a> {
+    pub fn new(entry: &'a Entry) -> Fields<'a> {
+        Fields {
+            entry: entry,
+            words: entry.value.split_whitespace().collect(),
+            next: 0,
+        }
+    }
+
+    /// An error pointing at the line being read.
+    pub fn error(&self, message: &str) -> ParseError {
+        ParseError {
+            line: self.entry.line,
+            message: format!("`{}`: {}", self.entry.key, message),
+        }
+    }
+
+    pub fn word<T: FromStr>(&mut self) -> Result<T, ParseError> {
+        let word = match self.words.get(self.next) {
+            Some(word) => *word,
+            None => return Err(self.error("missing values")),
+        };
+        self.next += 1;
+
+        word.parse().map_err(|_| self.error(&format!("invalid value `{}`", word)))
+    }
+
+    pub fn flag(&mut self) -> Result<bool, ParseError> {
+        Ok(try!(self.word::<u32>()) != 0)
+    }
+
+    pub fn pair(&mut self) -> Result<(f64, f64), ParseError> {
+        Ok((try!(self.word()), try!(self.word())))
+    }
+
+    pub fn rect(&mut self) -> Result<Rectangle, ParseError> {
+        Ok(Rectangle {
+            x: try!(self.word()),
+            y: try!(self.word()),
+            w: try!(self.word()),
+            h: try!(self.word()),
+        })
+    }
+
+    /// Every word not read yet.
+    pub fn rest(&mut self) -> Vec<&'a str> {
+        let rest = self.words[self.next..].to_vec();
+        self.next = self.words.len();
+        rest
+    }
+
+    /// Checks that every word was read.
+    pub fn end(&self) -> Result<(), ParseError> {
+        match self.words.get(self.next) {
+            Some(word) => Err(self.error(&format!("unexpected `{}`", word))),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The entries without the lines they were read from.
+    fn contents(config: &Config) -> Vec<(String, String, String)> {
+        config.entries().iter()
+            .map(|entry| (entry.section.clone(), entry.key.clone(), entry.value.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_sections_and_comments() {
+        let config = Config::parse("version = 1\n# a comment\n\n[video]\n  mode = windowed  \n").unwrap();
+
+        assert_eq!(config.get("", "version"), Some("1"));
+        assert_eq!(config.get("video", "mode"), Some("windowed"));
+        assert_eq!(config.entry("video", "mode").unwrap().line, 5);
+        assert_eq!(config.get("video", "version"), None);
+    }
+
+    #[test]
+    fn reports_the_line_of_errors() {
+        assert_eq!(Config::parse("a = 1\n[video\n").unwrap_err().line, 2);
+        assert_eq!(Config::parse("a = 1\n\nnot an entry\n").unwrap_err().line, 3);
+        assert_eq!(Config::parse("= 1\n").unwrap_err().line, 1);
+    }
+
+    #[test]
+    fn parsed_points_at_invalid_values() {
+        let config = Config::parse("[video]\nwidth = wide\n").unwrap();
+
+        assert_eq!(config.parsed::<u32>("video", "height"), Ok(None));
+        assert_eq!(config.parsed::<u32>("video", "width").unwrap_err().line, 2);
+    }
+
+    #[test]
+    fn last_value_wins() {
+        let mut config = Config::parse("a = 1\na = 2\n").unwrap();
+        assert_eq!(config.get("", "a"), Some("2"));
+
+        config.set("", "a", 3);
+        assert_eq!(config.entries().len(), 1);
+        assert_eq!(config.get("", "a"), Some("3"));
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        let config = Config::parse("version = 1\n[video]\nmode = windowed\n[events]\n2 = boss\n2 = end\n").unwrap();
+        let again = Config::parse(&config.to_string()).unwrap();
+
+        assert_eq!(contents(&again), contents(&config));
+    }
+
+    #[test]
+    fn writes_the_root_section_first() {
+        let mut config = Config::new();
+        config.set("video", "mode", "windowed");
+        config.set("", "version", 1);
+
+        let again = Config::parse(&config.to_string()).unwrap();
+        assert_eq!(again.get("", "version"), Some("1"));
+        assert_eq!(again.get("video", "version"), None);
+        assert_eq!(again.get("video", "mode"), Some("windowed"));
+    }
+
+    #[test]
+    fn lines_read_back_as_written() {
+        let rect = Rectangle { x: 0.5, y: -2.0, w: 16.0, h: 8.25 };
+        let mut config = Config::new();
+        config.set("player 1", "ship", Line::new().rect(rect).flag(true).pair((1.0 / 3.0, 7.0)).word(3));
+
+        let config = Config::parse(&config.to_string()).unwrap();
+        let entry = config.entry("player 1", "ship").unwrap();
+        let mut fields = Fields::new(entry);
+
+        assert_eq!(fields.rect(), Ok(rect));
+        assert_eq!(fields.flag(), Ok(true));
+        assert_eq!(fields.pair(), Ok((1.0 / 3.0, 7.0)));
+        assert_eq!(fields.word::<u32>(), Ok(3));
+        assert_eq!(fields.end(), Ok(()));
+    }
+
+    #[test]
+    fn fields_point_at_their_line() {
+        let config = Config::parse("[asteroids]\nasteroid = 1 two\n").unwrap();
+        let mut fields = Fields::new(config.entry("asteroids", "asteroid").unwrap());
+
+        assert_eq!(fields.word::<f64>(), Ok(1.0));
+        assert_eq!(fields.word::<f64>().unwrap_err().line, 2);
+        assert_eq!(fields.end(), Ok(()));
+        assert_eq!(fields.word::<f64>().unwrap_err().message, "`asteroid`: missing values");
+    }
+}
@@ -0,0 +1,464 @@
+//! A drop-down console, opened with `key_console` over any view, to run
+//! commands while developing the game.
+//!
+//! Commands come from two places. Those which do not depend on the view are
+//! registered with `Console::register`, e.g. by `phi` itself or by the game
+//! when it starts. The view being shown adds its own through
+//! `View::commands`, and runs them with `View::run_command`.
+
+use ::phi::{Phi, View, ViewAction};
+use ::phi::data::Rectangle;
+use ::phi::gfx::{CopySprite, DrawRectangle};
+use ::sdl2::keyboard::Keycode;
+use ::sdl2::pixels::Color;
+use ::sdl2::render::BlendMode;
+use ::std::path::PathBuf;
+use ::std::rc::Rc;
+
+/// Lines of output kept.
+const SCROLLBACK: usize = 200;
+/// Proportion of the screen's height covered by the open console.
+const HEIGHT: f64 = 0.45;
+/// Seconds taken to open or close.
+const SLIDE_TIME: f64 = 0.15;
+const TEXT_SIZE: i32 = 18;
+const LINE_HEIGHT: f64 = 22.0;
+const MARGIN: f64 = 8.0;
+/// Where traces are written by default, next to the settings.
+const TRACE_FILE: &'static str = "trace.json";
+
+/// What a command is called, and how it is used.
+#[derive(Clone, Copy, Debug)]
+pub struct Command {
+    pub name: &'static str,
+    /// Its arguments, e.g. `<factor>`
+    pub usage: &'static str,
+    pub help: &'static str,
+}
+
+/// Runs a command registered with the console, given its arguments. It may
+/// print with `phi.console.print`, and ask the game loop for something.
+pub type Handler = Rc<Fn(&mut Phi, &[&str]) -> Result<ViewAction, String>>;
+
+pub struct Console {
+    open: bool,
+    /// How far the console is pulled down, in `[0, 1]`
+    shown: f64,
+    /// The font of the text, without which the console cannot be shown
+    font: Option<&'static str>,
+    input: String,
+    /// Lines printed, oldest first, and whether they are errors
+    output: Vec<(String, bool)>,
+    /// Lines entered, oldest first
+    history: Vec<String>,
+    /// The entry of `history` shown in the input, while browsing it
+    browsing: Option<usize>,
+    commands: Vec<(Command, Handler)>,
+}
+
+impl Console {
+    /// A closed console, which only knows the commands of `phi`.
+    pub fn new() -> Console {
+        let mut console = Console {
+            open: false,
+            shown: 0.0,
+            font: None,
+            input: String::new(),
+            output: Vec::new(),
+            history: Vec::new(),
+            browsing: None,
+            commands: Vec::new(),
+        };
+
+        console.register(Command { name: "timescale", usage: "<factor>",
+                                   help: "Run the game slower or faster" },
+                         |phi, args| {
+            let scale = try!(arg::<f64>(args, 0));
+            if scale < 0.0 {
+                return Err("the time scale cannot be negative".to_string());
+            }
+            phi.time_scale = scale;
+            Ok(ViewAction::None)
+        });
+
+        console.register(Command { name: "debug", usage: "",
+                                   help: "Show or hide the debug overlay" },
+                         |phi, _| {
+            phi.debug.toggle();
+            Ok(ViewAction::None)
+        });
+
+        console.register(Command { name: "profiler", usage: "",
+                                   help: "Show or hide the frame time profiler" },
+                         |phi, _| {
+            phi.profiler.toggle();
+            Ok(ViewAction::None)
+        });
+
+        console.register(Command { name: "trace", usage: "[file]",
+                                   help: "Write the last frames' zones as a Chrome trace" },
+                         |phi, args| {
+            let path = match args.first() {
+                Some(file) => PathBuf::from(*file),
+                None => phi.config_path(TRACE_FILE).unwrap_or_else(|| PathBuf::from(TRACE_FILE)),
+            };
+
+            try!(phi.profiler.write_trace(&path)
+                .map_err(|err| format!("cannot write {}: {}", path.display(), err)));
+            phi.console.print(&format!("trace written to {}", path.display()));
+            Ok(ViewAction::None)
+        });
+
+        console.register(Command { name: "quit", usage: "", help: "Quit the game" },
+                         |_, _| Ok(ViewAction::Quit));
+
+        console
+    }
+
+    /// Sets the font of the console's text.
+    pub fn set_font(&mut self, font: &'static str) {
+        self.font = Some(font);
+    }
+
+    /// Makes a command available whatever the view, replacing any of the same
+    /// name.
+    pub fn register<F>(&mut self, command: Command, handler: F)
+    where F: Fn(&mut Phi, &[&str]) -> Result<ViewAction, String> + 'static {
+        self.commands.retain(|&(ref c, _)| c.name != command.name);
+        self.commands.push((command, Rc::new(handler)));
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Adds a line to the output.
+    pub fn print(&mut self, line: &str) {
+        self.push(line.to_string(), false);
+    }
+
+    fn error(&mut self, line: &str) {
+        self.push(line.to_string(), true);
+    }
+
+    fn push(&mut self, line: String, error: bool) {
+        if self.output.len() == SCROLLBACK {
+            self.output.remove(0);
+        }
+        self.output.push((line, error));
+    }
+
+    /// Adds a line entered to the history, unless it repeats the last one.
+    fn remember(&mut self, line: &str) {
+        if self.history.last().map_or(true, |last| last != line) {
+            self.history.push(line.to_string());
+        }
+        self.browsing = None;
+    }
+
+    /// Completes the input as far as it is the same for every one of `names`
+    /// it may be, and lists them if there are several.
+    fn complete_among(&mut self, names: &[&str]) {
+        if self.input.contains(' ') {
+            return;
+        }
+
+        let matches: Vec<&str> = names.iter().cloned()
+            .filter(|name| name.starts_with(&self.input[..]))
+            .collect();
+
+        match matches.len() {
+            0 => {},
+            1 => self.input = format!("{} ", matches[0]),
+            _ => {
+                let mut prefix = matches[0].to_string();
+                for name in &matches[1..] {
+                    while !name.starts_with(&prefix[..]) {
+                        prefix.pop();
+                    }
+                }
+
+                self.print(&matches.join("  "));
+                self.input = prefix;
+            },
+        }
+    }
+}
+
+/// Reads the `index`th argument of a command as a `T`.
+pub fn arg<T: ::std::str::FromStr>(args: &[&str], index: usize) -> Result<T, String> {
+    match args.get(index) {
+        Some(word) => word.parse().map_err(|_| format!("invalid argument `{}`", word)),
+        None => Err(format!("missing argument {}", index + 1)),
+    }
+}
+
+/// Every command available with `view` shown, sorted by name.
+fn all_commands(phi: &Phi, view: &View) -> Vec<Command> {
+    let mut commands: Vec<Command> = phi.console.commands.iter().map(|&(command, _)| command)
+        .chain(view.commands())
+        .chain(vec![
+            Command { name: "help", usage: "", help: "List the commands" },
+            Command { name: "clear", usage: "", help: "Clear the console" },
+        ])
+        .collect();
+
+    commands.sort_by(|a, b| a.name.cmp(b.name));
+    commands
+}
+
+/// Opens or closes the console, and while it is open, takes the keyboard
+/// away from `view` to edit and run commands. Returns what the commands ask
+/// of the game loop.
+pub fn update(phi: &mut Phi, view: &mut View) -> ViewAction {
+    if phi.console.font.is_none() {
+        return ViewAction::None;
+    }
+
+    if phi.events.now.key_console == Some(true) {
+        phi.console.open = !phi.console.open;
+        phi.events.withhold();
+        return ViewAction::None;
+    }
+
+    if !phi.console.open {
+        return ViewAction::None;
+    }
+
+    let text: String = phi.events.now.text.chars().filter(|&c| c != '`').collect();
+    let keys = phi.events.now.keys.clone();
+    phi.events.withhold();
+
+    phi.console.input.push_str(&text);
+    if !text.is_empty() {
+        phi.console.browsing = None;
+    }
+
+    for key in keys {
+        match key {
+            Keycode::Escape => phi.console.open = false,
+            Keycode::Backspace => {
+                phi.console.input.pop();
+            },
+            Keycode::Up => browse(&mut phi.console, -1),
+            Keycode::Down => browse(&mut phi.console, 1),
+            Keycode::Tab => complete(phi, view),
+            Keycode::Return | Keycode::KpEnter => {
+                let line = ::std::mem::replace(&mut phi.console.input, String::new());
+                let action = run(phi, view, &line);
+
+                if let ViewAction::None = action {
+                    continue;
+                }
+                return action;
+            },
+            _ => {},
+        }
+    }
+
+    ViewAction::None
+}
+
+/// Replaces the input with an older (`step` of -1) or newer (1) line of the
+/// history.
+fn browse(console: &mut Console, step: i32) {
+    if console.history.is_empty() {
+        return;
+    }
+
+    let last = console.history.len() - 1;
+    console.browsing = match (console.browsing, step < 0) {
+        (None, true) => Some(last),
+        (None, false) => None,
+        (Some(0), true) => Some(0),
+        (Some(i), true) => Some(i - 1),
+        (Some(i), false) if i < last => Some(i + 1),
+        (Some(_), false) => None,
+    };
+
+    console.input = match console.browsing {
+        Some(i) => console.history[i].clone(),
+        None => String::new(),
+    };
+}
+
+/// Completes the name of the command being typed as far as it is the same
+/// for every command it may be, and lists them if there are several.
+fn complete(phi: &mut Phi, view: &View) {
+    let names: Vec<&'static str> = all_commands(phi, view).iter()
+        .map(|command| command.name)
+        .collect();
+
+    phi.console.complete_among(&names);
+}
+
+/// Runs a line entered in the console.
+fn run(phi: &mut Phi, view: &mut View, line: &str) -> ViewAction {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    if words.is_empty() {
+        return ViewAction::None;
+    }
+
+    phi.console.print(&format!("> {}", line));
+    phi.console.remember(line);
+
+    let (name, args) = (words[0], &words[1..]);
+
+    let result = if name == "help" {
+        for command in all_commands(phi, view) {
+            let line = format!("{} {} - {}", command.name, command.usage, command.help);
+            phi.console.print(&line);
+        }
+        Ok(ViewAction::None)
+    } else if name == "clear" {
+        phi.console.output.clear();
+        Ok(ViewAction::None)
+    } else if view.commands().iter().any(|command| command.name == name) {
+        view.run_command(phi, name, args)
+    } else {
+        let handler = phi.console.commands.iter()
+            .find(|&&(ref command, _)| command.name == name)
+            .map(|&(_, ref handler)| handler.clone());
+
+        match handler {
+            Some(handler) => (*handler)(phi, args),
+            None => Err(format!("unknown command `{}`, see `help`", name)),
+        }
+    };
+
+    match result {
+        Ok(action) => action,
+        Err(message) => {
+            phi.console.error(&message);
+            ViewAction::None
+        },
+    }
+}
+
+/// Draws the console over the view, sliding it in or out as `elapsed`
+/// seconds go by.
+pub fn render(phi: &mut Phi, elapsed: f64) {
+    let font = match phi.console.font {
+        Some(font) => font,
+        None => return,
+    };
+
+    let step = elapsed / SLIDE_TIME;
+    phi.console.shown = if phi.console.open { (phi.console.shown + step).min(1.0) }
+        else { (phi.console.shown - step).max(0.0) };
+
+    if phi.console.shown == 0.0 {
+        return;
+    }
+
+    let (win_w, win_h) = phi.output_size();
+    let height = (win_h * HEIGHT).floor();
+    let bottom = height * phi.console.shown;
+
+    phi.renderer.set_blend_mode(BlendMode::Blend);
+    phi.renderer.set_draw_color(Color::RGBA(10, 10, 24, 220));
+    phi.renderer.fill_rectangle(Rectangle { x: 0.0, y: bottom - height, w: win_w, h: height });
+    phi.renderer.set_draw_color(Color::RGB(90, 90, 140));
+    phi.renderer.fill_rectangle(Rectangle { x: 0.0, y: bottom - 2.0, w: win_w, h: 2.0 });
+    phi.renderer.set_blend_mode(BlendMode::None);
+
+    // The input at the bottom, with a cursor, and the latest output above it
+    let mut lines = vec![(format!("> {}_", phi.console.input), Color::RGB(255, 255, 255))];
+    let visible = (height / LINE_HEIGHT) as usize;
+    for &(ref line, error) in phi.console.output.iter().rev().take(visible.saturating_sub(1)) {
+        let color = if error { Color::RGB(255, 110, 110) } else { Color::RGB(190, 190, 210) };
+        lines.push((line.clone(), color));
+    }
+
+    let mut y = bottom - MARGIN - LINE_HEIGHT;
+    for (line, color) in lines {
+        if !line.is_empty() {
+            if let Some(sprite) = phi.ttf_str_sprite(&line, font, TEXT_SIZE, color) {
+                let (w, h) = sprite.size();
+                phi.renderer.copy_sprite(&sprite, Rectangle { x: MARGIN, y: y, w: w, h: h });
+            }
+        }
+        y -= LINE_HEIGHT;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn console_with_history(lines: &[&str]) -> Console {
+        let mut console = Console::new();
+        for line in lines {
+            console.remember(line);
+        }
+        console
+    }
+
+    /// Browses the history one step at a time, returning the inputs shown.
+    fn browse_steps(console: &mut Console, steps: &[i32]) -> Vec<String> {
+        steps.iter().map(|&step| {
+            browse(console, step);
+            console.input.clone()
+        }).collect()
+    }
+
+    #[test]
+    fn browsing_stops_at_both_ends_of_the_history() {
+        let mut console = console_with_history(&["debug", "timescale 2", "quit"]);
+
+        assert_eq!(browse_steps(&mut console, &[-1, -1, -1, -1]),
+                   vec!["quit", "timescale 2", "debug", "debug"]);
+        assert_eq!(browse_steps(&mut console, &[1, 1, 1, 1]),
+                   vec!["timescale 2", "quit", "", ""]);
+        assert_eq!(console.browsing, None);
+    }
+
+    #[test]
+    fn browsing_an_empty_history_keeps_the_input() {
+        let mut console = Console::new();
+        console.input = "deb".to_string();
+
+        assert_eq!(browse_steps(&mut console, &[-1, 1]), vec!["deb", "deb"]);
+    }
+
+    #[test]
+    fn repeated_lines_are_remembered_once() {
+        let mut console = console_with_history(&["debug", "debug", "quit", "debug"]);
+        assert_eq!(console.history, vec!["debug", "quit", "debug"]);
+
+        browse(&mut console, -1);
+        console.remember("debug");
+        assert_eq!(console.history.len(), 3);
+        assert_eq!(console.browsing, None);
+    }
+
+    const NAMES: [&'static str; 6] = ["debug", "profiler", "quit", "spark", "spawn", "trace"];
+
+    /// Completes `input` among `NAMES`, returning the new input and the lines
+    /// printed.
+    fn completed(input: &str) -> (String, Vec<String>) {
+        let mut console = Console::new();
+        console.input = input.to_string();
+        console.complete_among(&NAMES);
+
+        (console.input, console.output.into_iter().map(|(line, _)| line).collect())
+    }
+
+    #[test]
+    fn completes_a_single_match() {
+        assert_eq!(completed("q"), ("quit ".to_string(), vec![]));
+        assert_eq!(completed("trace"), ("trace ".to_string(), vec![]));
+    }
+
+    #[test]
+    fn completes_the_common_part_of_several_matches() {
+        assert_eq!(completed("s"), ("spa".to_string(), vec!["spark  spawn".to_string()]));
+        assert_eq!(completed("").1, vec![NAMES.join("  ")]);
+    }
+
+    #[test]
+    fn leaves_the_input_without_matches() {
+        assert_eq!(completed("x"), ("x".to_string(), vec![]));
+        assert_eq!(completed("spawn 3"), ("spawn 3".to_string(), vec![]));
+    }
+}
@@ -9,15 +9,18 @@ pub struct Rectangle {
 }
 
 impl Rectangle {
-    /// Generates SDL-compatible Rect equivalent to `self`. Panics if it could
-    /// not be created, for example if a corner coordinate overflows an `i32`.
+    /// Generates SDL-compatible Rect equivalent to `self`. Returns `None` if
+    /// it could not be created, for example if it is empty or if a corner
+    /// coordinate overflows an `i32`.
     pub fn to_sdl(self) -> Option<SDLRect> {
         // Reject negative width and height
-        assert!(self.w >= 0.0 && self.h >= 0.0);
+        if self.w < 0.0 || self.h < 0.0 {
+            return None;
+        }
 
         // SDLRect::new : `(i32, i32, u32, u32) -> Result<Option<SDLRect>>`
         SDLRect::new(self.x as i32, self.y as i32, self.w as u32, self.h as u32)
-            .unwrap()
+            .ok().and_then(|rect| rect)
     }
 
     /// Return a (perhaps moved) rectangle which is contained by a `parent`
@@ -0,0 +1,205 @@
+//! An overlay showing what is normally hidden: hitboxes, velocities, how
+//! long frames take and how many things are alive. It is toggled at runtime
+//! with `key_debug`, and drawn over whatever view is active.
+//!
+//! Views describe what they want shown every frame through `Phi::debug`,
+//! which ignores them while the overlay is hidden.
+
+use ::phi::Phi;
+use ::phi::data::Rectangle;
+use ::phi::gfx::{self, CopySprite, DrawRectangle};
+use ::sdl2::pixels::Color;
+use ::sdl2::rect::Point;
+use ::sdl2::render::BlendMode;
+
+/// Frames shown by the frame time graph.
+const HISTORY: usize = 120;
+/// Pixels of the graph per millisecond a frame took.
+const GRAPH_SCALE: f64 = 2.0;
+/// Milliseconds a frame should take, marked on the graph.
+const TARGET_FRAME_MS: f64 = 1000.0 / 60.0;
+/// Seconds of movement shown by velocity lines.
+const VELOCITY_SECONDS: f64 = 0.25;
+const TEXT_SIZE: i32 = 16;
+const MARGIN: f64 = 8.0;
+
+pub const HITBOX_COLOR: Color = Color::RGB(255, 60, 60);
+pub const REGION_COLOR: Color = Color::RGB(60, 160, 255);
+const VELOCITY_COLOR: Color = Color::RGB(80, 255, 80);
+
+pub struct DebugOverlay {
+    enabled: bool,
+    /// The font of the text, without which only shapes are drawn
+    font: Option<&'static str>,
+    /// How long the last frames took, in seconds, oldest first
+    frame_times: Vec<f64>,
+    rects: Vec<(Rectangle, Color)>,
+    /// Lines from the centre of a rectangle, along its velocity
+    velocities: Vec<(Rectangle, (f64, f64))>,
+    counts: Vec<(&'static str, usize)>,
+}
+
+impl DebugOverlay {
+    pub fn new() -> DebugOverlay {
+        DebugOverlay {
+            enabled: false,
+            font: None,
+            frame_times: Vec::with_capacity(HISTORY),
+            rects: Vec::new(),
+            velocities: Vec::new(),
+            counts: Vec::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+    }
+
+    /// Sets the font of the overlay's text.
+    pub fn set_font(&mut self, font: &'static str) {
+        self.font = Some(font);
+    }
+
+    /// Outlines `rect`, e.g. a hitbox, this frame.
+    pub fn rect(&mut self, rect: Rectangle, color: Color) {
+        if self.enabled {
+            self.rects.push((rect, color));
+        }
+    }
+
+    /// Shows where something occupying `rect` is heading, `vel` being in
+    /// pixels per second.
+    pub fn velocity(&mut self, rect: Rectangle, vel: (f64, f64)) {
+        if self.enabled {
+            self.velocities.push((rect, vel));
+        }
+    }
+
+    /// Shows how many of something called `name` there are this frame.
+    /// Counts of the same name add up.
+    pub fn count(&mut self, name: &'static str, count: usize) {
+        if !self.enabled {
+            return;
+        }
+
+        match self.counts.iter_mut().find(|entry| entry.0 == name) {
+            Some(entry) => entry.1 += count,
+            None => self.counts.push((name, count)),
+        }
+    }
+
+    /// Records that a frame took `elapsed` seconds, even while hidden, so
+    /// that the graph is full once shown.
+    pub fn frame(&mut self, elapsed: f64) {
+        if self.frame_times.len() == HISTORY {
+            self.frame_times.remove(0);
+        }
+        self.frame_times.push(elapsed);
+    }
+
+    fn clear(&mut self) {
+        self.rects.clear();
+        self.velocities.clear();
+        self.counts.clear();
+    }
+}
+
+/// Draws the overlay over the frame rendered by the view, then forgets what
+/// it was given for the next frame.
+pub fn render(phi: &mut Phi) {
+    if !phi.debug.enabled {
+        phi.debug.clear();
+        return;
+    }
+
+    phi.renderer.set_blend_mode(BlendMode::Blend);
+
+    for &(rect, color) in &phi.debug.rects {
+        phi.renderer.set_draw_color(color);
+        if let Some(rect) = rect.to_sdl() {
+            phi.renderer.draw_rect(rect);
+        }
+    }
+
+    phi.renderer.set_draw_color(VELOCITY_COLOR);
+    for &(rect, vel) in &phi.debug.velocities {
+        let (x, y) = (rect.x + rect.w / 2.0, rect.y + rect.h / 2.0);
+        phi.renderer.draw_line(
+            Point::new(x as i32, y as i32),
+            Point::new((x + vel.0 * VELOCITY_SECONDS) as i32, (y + vel.1 * VELOCITY_SECONDS) as i32));
+    }
+
+    render_graph(phi);
+    render_text(phi);
+
+    phi.renderer.set_blend_mode(BlendMode::None);
+    phi.debug.clear();
+}
+
+/// A bar per frame in the bottom left corner, as tall as the frame was long,
+/// with a line where a frame at 60 fps would reach.
+fn render_graph(phi: &mut Phi) {
+    let (_, win_h) = phi.output_size();
+    let bottom = win_h - MARGIN;
+    let width = HISTORY as f64 * 2.0;
+    let height = TARGET_FRAME_MS * GRAPH_SCALE * 3.0;
+
+    phi.renderer.set_draw_color(Color::RGBA(0, 0, 0, 160));
+    phi.renderer.fill_rectangle(Rectangle { x: MARGIN, y: bottom - height, w: width, h: height });
+
+    for (i, &time) in phi.debug.frame_times.iter().enumerate() {
+        let ms = time * 1000.0;
+        let bar = (ms * GRAPH_SCALE).min(height).max(1.0);
+
+        phi.renderer.set_draw_color(
+            if ms > TARGET_FRAME_MS * 1.5 { Color::RGB(255, 80, 80) } else { Color::RGB(120, 220, 120) });
+        phi.renderer.fill_rectangle(Rectangle {
+            x: MARGIN + i as f64 * 2.0,
+            y: bottom - bar,
+            w: 2.0,
+            h: bar,
+        });
+    }
+
+    let target = (bottom - TARGET_FRAME_MS * GRAPH_SCALE) as i32;
+    phi.renderer.set_draw_color(Color::RGB(255, 255, 255));
+    phi.renderer.draw_line(Point::new(MARGIN as i32, target),
+                           Point::new((MARGIN + width) as i32, target));
+}
+
+/// The frame rate, the counts and the texture memory in the top left corner.
+fn render_text(phi: &mut Phi) {
+    let font = match phi.debug.font {
+        Some(font) => font,
+        None => return,
+    };
+
+    let average = phi.debug.frame_times.iter().sum::<f64>() /
+        phi.debug.frame_times.len().max(1) as f64;
+    let worst = phi.debug.frame_times.iter().cloned().fold(0.0, f64::max);
+
+    let mut lines = vec![
+        format!("{:.0} fps, {:.1} ms (worst {:.1} ms)",
+                1.0 / average.max(1e-6), average * 1000.0, worst * 1000.0),
+        format!("textures: {:.1} MiB", gfx::texture_memory() as f64 / (1024.0 * 1024.0)),
+    ];
+    for &(name, count) in &phi.debug.counts {
+        lines.push(format!("{}: {}", name, count));
+    }
+
+    let mut y = MARGIN;
+    for line in lines {
+        if let Some(sprite) = phi.ttf_str_sprite(&line, font, TEXT_SIZE, Color::RGB(255, 255, 255)) {
+            let (w, h) = sprite.size();
+
+            phi.renderer.set_draw_color(Color::RGBA(0, 0, 0, 160));
+            phi.renderer.fill_rectangle(Rectangle { x: MARGIN, y: y, w: w + 8.0, h: h });
+            phi.renderer.copy_sprite(&sprite, Rectangle { x: MARGIN + 4.0, y: y, w: w, h: h });
+            y += h;
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use ::std::error::Error;
+use ::std::fmt;
+use ::std::path::{Path, PathBuf};
+
+/// Why the game could not start, or could not go on.
+#[derive(Debug)]
+pub enum PhiError {
+    /// SDL, or one of the libraries used with it, could not be initialised
+    Init { library: &'static str, cause: String },
+    /// The window, or what renders to it, could not be created
+    Window(String),
+    /// An image could not be loaded, or is too small for what is read of it
+    Asset { path: PathBuf, cause: String },
+    /// A font could not be loaded at some size
+    Font { path: PathBuf, size: i32, cause: String },
+    /// A networked game could not be set up
+    Network(String),
+}
+
+impl PhiError {
+    pub fn init<E: ToString>(library: &'static str, cause: E) -> PhiError {
+        PhiError::Init { library: library, cause: cause.to_string() }
+    }
+
+    pub fn window<E: ToString>(cause: E) -> PhiError {
+        PhiError::Window(cause.to_string())
+    }
+
+    pub fn asset<P: AsRef<Path>, E: ToString>(path: P, cause: E) -> PhiError {
+        PhiError::Asset { path: path.as_ref().to_path_buf(), cause: cause.to_string() }
+    }
+
+    pub fn font<P: AsRef<Path>, E: ToString>(path: P, size: i32, cause: E) -> PhiError {
+        PhiError::Font { path: path.as_ref().to_path_buf(), size: size, cause: cause.to_string() }
+    }
+}
+
+impl fmt::Display for PhiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PhiError::Init { library, ref cause } =>
+                write!(f, "could not initialise {}: {}", library, cause),
+            PhiError::Window(ref cause) =>
+                write!(f, "could not create the window: {}", cause),
+            PhiError::Asset { ref path, ref cause } =>
+                write!(f, "could not load {}: {}", path.display(), cause),
+            PhiError::Font { ref path, size, ref cause } =>
+                write!(f, "could not load the font {} at size {}: {}", path.display(), size, cause),
+            PhiError::Network(ref cause) =>
+                write!(f, "could not set up the network: {}", cause),
+        }
+    }
+}
+
+impl Error for PhiError {
+    fn description(&self) -> &str {
+        match *self {
+            PhiError::Init { .. } => "could not initialise a library",
+            PhiError::Window(_) => "could not create the window",
+            PhiError::Asset { .. } => "could not load an asset",
+            PhiError::Font { .. } => "could not load a font",
+            PhiError::Network(_) => "could not set up the network",
+        }
+    }
+}
+
+pub type PhiResult<T> = Result<T, PhiError>;
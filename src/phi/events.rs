@@ -2,14 +2,79 @@ macro_rules! struct_events {
     (
         keyboard: { $( $key_alias:ident : $key_sdl:ident, )* },
 
+        controller: { $( $pad_alias:ident : $pad_sdl:ident, )* },
+
         else: { $( $exit_alias:ident : $exit_sdl:pat ),* }
     ) => {
         use ::sdl2::EventPump;
+        use ::sdl2::GameControllerSubsystem;
+        use ::sdl2::controller::GameController;
+        use ::sdl2::keyboard::Keycode;
+
+
+        /// The physical key associated to each keyboard alias.
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        pub struct Bindings {
+            $( pub $key_alias : Keycode, )*
+        }
+
+        impl Bindings {
+            /// The default key for every alias.
+            pub fn new() -> Bindings {
+                Bindings {
+                    $( $key_alias : Keycode::$key_sdl, )*
+                }
+            }
+
+            /// The names of the aliases, as used by `get` and `set`.
+            pub fn names() -> Vec<&'static str> {
+                vec![ $( stringify!($key_alias), )* ]
+            }
+
+            /// Returns the key bound to the alias called `name`, if any.
+            pub fn get(&self, name: &str) -> Option<Keycode> {
+                $(
+                    if name == stringify!($key_alias) {
+                        return Some(self.$key_alias);
+                    }
+                )*
+                None
+            }
+
+            /// Binds `keycode` to the alias called `name`. Returns whether such
+            /// an alias exists.
+            pub fn set(&mut self, name: &str, keycode: Keycode) -> bool {
+                $(
+                    if name == stringify!($key_alias) {
+                        self.$key_alias = keycode;
+                        return true;
+                    }
+                )*
+                false
+            }
+        }
 
 
         pub struct ImmediateEvents {
             $( pub $key_alias : Option<bool>, )*
+            $( pub $pad_alias : Option<bool>, )*
             $( pub $exit_alias : bool, )*
+            /// The last key pressed during the frame, whether it is bound or not.
+            pub keycode: Option<Keycode>,
+            /// Every key pressed during the frame, in order, including those
+            /// repeated by holding them down.
+            pub keys: Vec<Keycode>,
+            /// The text typed during the frame.
+            pub text: String,
+            /// Left and right mouse buttons: `Some(true)` when pressed during
+            /// the frame, `Some(false)` when released.
+            pub mouse_left: Option<bool>,
+            pub mouse_right: Option<bool>,
+            /// Notches scrolled with the mouse wheel, positive away from the
+            /// user.
+            pub wheel: i32,
+            /// Whether the window lost the keyboard focus during the frame.
+            pub focus_lost: bool,
             resize: Option<(u32, u32)>,
         }
 
@@ -17,7 +82,15 @@ macro_rules! struct_events {
             pub fn new() -> ImmediateEvents {
                 ImmediateEvents {
                     $( $key_alias : None, )*
+                    $( $pad_alias : None, )*
                     $( $exit_alias : false, )*
+                    keycode: None,
+                    keys: Vec::new(),
+                    text: String::new(),
+                    mouse_left: None,
+                    mouse_right: None,
+                    wheel: 0,
+                    focus_lost: false,
                     resize: None,
                 }
             }
@@ -27,17 +100,37 @@ macro_rules! struct_events {
         pub struct Events {
             pump: EventPump,
             pub now: ImmediateEvents,
+            pub bindings: Bindings,
 
             $( pub $key_alias: bool, )*
+            $( pub $pad_alias: bool, )*
+
+            /// The position of the mouse cursor in the window, in pixels.
+            pub mouse: (f64, f64),
+            pub mouse_left: bool,
+            pub mouse_right: bool,
+
+            controller_subsystem: GameControllerSubsystem,
+            // Controllers stop sending events when dropped, so keep them open.
+            controllers: Vec<GameController>,
         }
 
         impl Events {
-            pub fn new(pump: EventPump) -> Events {
+            pub fn new(pump: EventPump, controller_subsystem: GameControllerSubsystem) -> Events {
                 Events {
                     pump: pump,
                     now: ImmediateEvents::new(),
+                    bindings: Bindings::new(),
 
                     $( $key_alias: false, )*
+                    $( $pad_alias: false, )*
+
+                    mouse: (0.0, 0.0),
+                    mouse_left: false,
+                    mouse_right: false,
+
+                    controller_subsystem: controller_subsystem,
+                    controllers: Vec::new(),
                 }
             }
 
@@ -46,37 +139,119 @@ macro_rules! struct_events {
 
                 for event in self.pump.poll_iter() {
                     use ::sdl2::event::Event::*;
-                    use ::sdl2::event::WindowEventId::Resized;
-                    use ::sdl2::keyboard::Keycode::*;
+                    use ::sdl2::event::WindowEventId::{FocusLost, Resized};
 
                     match event {
                         Window { win_event_id: Resized, .. } => {
                             self.now.resize = Some(renderer.output_size().unwrap());
                         },
 
-                        KeyDown { keycode, .. } => match keycode {
+                        Window { win_event_id: FocusLost, .. } => {
+                            self.now.focus_lost = true;
+                        },
+
+                        KeyDown { keycode: Some(keycode), .. } => {
+                            self.now.keycode = Some(keycode);
+                            self.now.keys.push(keycode);
+
+                            // Several aliases may share the same key
                             $(
-                                Some($key_sdl) => {
+                                if keycode == self.bindings.$key_alias {
                                     if !self.$key_alias {
                                         // Key pressed, wasn't before
                                         self.now.$key_alias = Some(true);
                                         
-                                        println!("Key down: {}", $key_sdl);
+                                        trace!("Key down: {}", keycode);
                                     }
 
                                     self.$key_alias = true;
-                                },
+                                }
                             )*
-
-                            _ => {}
                         },
 
-                        KeyUp { keycode, .. } => match keycode {
+                        KeyUp { keycode: Some(keycode), .. } => {
                             $(
-                                Some($key_sdl) => {
+                                if keycode == self.bindings.$key_alias {
                                     // key released
                                     self.now.$key_alias = Some(false);
                                     self.$key_alias = false;
+                                }
+                            )*
+                        },
+
+                        TextInput { text, .. } => {
+                            self.now.text.push_str(&text);
+                        },
+
+                        MouseMotion { x, y, .. } => {
+                            self.mouse = (x as f64, y as f64);
+                        },
+
+                        MouseButtonDown { mouse_btn, x, y, .. } => {
+                            self.mouse = (x as f64, y as f64);
+
+                            match mouse_btn {
+                                ::sdl2::mouse::Mouse::Left => {
+                                    self.now.mouse_left = Some(true);
+                                    self.mouse_left = true;
+                                },
+                                ::sdl2::mouse::Mouse::Right => {
+                                    self.now.mouse_right = Some(true);
+                                    self.mouse_right = true;
+                                },
+                                _ => {},
+                            }
+                        },
+
+                        MouseButtonUp { mouse_btn, x, y, .. } => {
+                            self.mouse = (x as f64, y as f64);
+
+                            match mouse_btn {
+                                ::sdl2::mouse::Mouse::Left => {
+                                    self.now.mouse_left = Some(false);
+                                    self.mouse_left = false;
+                                },
+                                ::sdl2::mouse::Mouse::Right => {
+                                    self.now.mouse_right = Some(false);
+                                    self.mouse_right = false;
+                                },
+                                _ => {},
+                            }
+                        },
+
+                        MouseWheel { y, .. } => {
+                            self.now.wheel += y;
+                        },
+
+                        ControllerDeviceAdded { which, .. } => {
+                            if let Ok(controller) = self.controller_subsystem.open(which as u32) {
+                                self.controllers.push(controller);
+                            }
+                        },
+
+                        ControllerDeviceRemoved { which, .. } => {
+                            self.controllers.retain(|c| c.instance_id() != which);
+                        },
+
+                        ControllerButtonDown { button, .. } => match button {
+                            $(
+                                ::sdl2::controller::Button::$pad_sdl => {
+                                    if !self.$pad_alias {
+                                        self.now.$pad_alias = Some(true);
+                                    }
+
+                                    self.$pad_alias = true;
+                                },
+                            )*
+
+                            _ => {}
+                        },
+
+                        ControllerButtonUp { button, .. } => match button {
+                            $(
+                                ::sdl2::controller::Button::$pad_sdl => {
+                                    self.now.$pad_alias = Some(false);
+                                    self.$pad_alias = false;
                                 },
                             )*
 
@@ -93,6 +268,23 @@ macro_rules! struct_events {
                     }
                 }
             }
+
+            /// Hides the keys and buttons of the frame from whoever reads the
+            /// events next, as if they were all released, e.g. while the
+            /// keyboard is used by the console. Exits and window events are
+            /// kept.
+            pub fn withhold(&mut self) {
+                let mut now = ImmediateEvents::new();
+                $( now.$exit_alias = self.now.$exit_alias; )*
+                now.focus_lost = self.now.focus_lost;
+                now.resize = self.now.resize;
+                self.now = now;
+
+                $( self.$key_alias = false; )*
+                $( self.$pad_alias = false; )*
+                self.mouse_left = false;
+                self.mouse_right = false;
+            }
         }
     }
 }
@@ -1,14 +1,57 @@
+use ::phi::collision::{ALPHA_THRESHOLD, CollisionMask};
 use ::phi::data::Rectangle;
+use ::phi::error::{PhiError, PhiResult};
+use ::phi::vfs;
+use ::sdl2::pixels::{Color, PixelFormatEnum};
 use ::sdl2::render::{Renderer, Texture};
-use ::sdl2_image::LoadTexture;
-use ::std::cell::RefCell;
-use ::std::path::Path;
+use ::sdl2::rwops::RWops;
+use ::sdl2::surface::Surface;
+use ::sdl2_image::ImageRWops;
+use ::std::cell::{Cell, RefCell};
 use ::std::rc::Rc;
 
+/// The side of the texture shown in place of a missing image, in pixels.
+const MISSING_SIZE: u32 = 64;
+/// The side of the squares of that texture.
+const MISSING_SQUARE: u32 = 8;
+const MISSING_COLORS: [Color; 2] = [Color::RGB(255, 0, 255), Color::RGB(0, 0, 0)];
+
+thread_local!(static TEXTURE_MEMORY: Cell<usize> = Cell::new(0));
+
+/// Bytes taken by every texture still in use, as far as it can be told from
+/// their size.
+pub fn texture_memory() -> usize {
+    TEXTURE_MEMORY.with(|memory| memory.get())
+}
+
+/// The share of `texture_memory` of a texture, given back once every sprite
+/// using it is gone.
+struct TextureMemory(usize);
+
+impl TextureMemory {
+    fn new(bytes: usize) -> TextureMemory {
+        TEXTURE_MEMORY.with(|memory| memory.set(memory.get() + bytes));
+        TextureMemory(bytes)
+    }
+}
+
+impl Drop for TextureMemory {
+    fn drop(&mut self) {
+        let bytes = self.0;
+        TEXTURE_MEMORY.with(|memory| memory.set(memory.get().saturating_sub(bytes)));
+    }
+}
+
 #[derive(Clone)]
 pub struct Sprite {
     tex: Rc<RefCell<Texture>>,
+    memory: Rc<TextureMemory>,
     src: Rectangle,
+    /// The solid pixels of `src`, if the sprite was loaded from an image.
+    mask: Option<Rc<CollisionMask>>,
+    /// Whether the image could not be loaded, and a checkerboard is shown
+    /// in its place.
+    missing: bool,
 }
 
 /// Common interface for rendering graphical components to a given window region
@@ -20,22 +63,75 @@ impl Sprite {
     /// Creates a new sprite by wrapping a `Texture`.
     pub fn new(texture: Texture) -> Sprite {
         let tex_query = texture.query();
+        // Every texture is assumed to take 4 bytes per pixel.
+        let bytes = tex_query.width as usize * tex_query.height as usize * 4;
 
         Sprite {
             tex: Rc::new(RefCell::new(texture)),
+            memory: Rc::new(TextureMemory::new(bytes)),
             src: Rectangle {
                 w: tex_query.width as f64,
                 h: tex_query.height as f64,
                 x: 0.0,
                 y: 0.0,
+            },
+            mask: None,
+            missing: false,
+        }
+    }
+
+    /// Creates a new sprite from an image file located at the given path in
+    /// the `vfs`, generating its collision mask from the image's alpha
+    /// channel.
+    ///
+    /// If the image cannot be read, this is logged and a checkerboard is
+    /// shown instead, so that the game goes on with the problem in sight.
+    pub fn load(renderer: &Renderer, path: &str) -> PhiResult<Sprite> {
+        let surface = match load_surface(path) {
+            Ok(surface) => surface,
+            Err(err) => {
+                warn!("{}, showing a placeholder", PhiError::asset(path, err));
+                return Sprite::missing(renderer)
+                    .map_err(|err| PhiError::asset(path, err));
+            },
+        };
+
+        let mask = alpha_mask(&surface);
+
+        let texture = try!(renderer.create_texture_from_surface(&surface)
+            .map_err(|err| PhiError::asset(path, err)));
+
+        Ok(Sprite { mask: mask.map(Rc::new), ..Sprite::new(texture) })
+    }
+
+    /// A checkerboard standing for an image which could not be loaded. Any
+    /// region may be taken from it, and is shown as a whole checkerboard.
+    pub fn missing(renderer: &Renderer) -> Result<Sprite, String> {
+        let mut surface = try!(Surface::new(MISSING_SIZE, MISSING_SIZE, PixelFormatEnum::RGB888));
+        let squares = MISSING_SIZE / MISSING_SQUARE;
+
+        for y in 0..squares {
+            for x in 0..squares {
+                let square = Rectangle {
+                    x: (x * MISSING_SQUARE) as f64,
+                    y: (y * MISSING_SQUARE) as f64,
+                    w: MISSING_SQUARE as f64,
+                    h: MISSING_SQUARE as f64,
+                };
+                try!(surface.fill_rect(square.to_sdl(), MISSING_COLORS[((x + y) % 2) as usize]));
             }
         }
+
+        let texture = try!(renderer.create_texture_from_surface(&surface).map_err(|err| err.to_string()));
+        Ok(Sprite { missing: true, ..Sprite::new(texture) })
     }
 
-    /// Creates a new sprite from an image file located at the given path.
-    /// Returns `Some` if the file could be read, and `None` otherwise.
-    pub fn load(renderer: &Renderer, path: &str) -> Option<Sprite> {
-        renderer.load_texture(Path::new(path)).ok().map(Sprite::new)
+    /// Like `region`, for a sprite loaded from the image at `path`, which is
+    /// then too small if the `rect` is not inside of it.
+    pub fn region_of(&self, path: &str, rect: Rectangle) -> PhiResult<Sprite> {
+        self.region(rect).ok_or_else(|| PhiError::asset(path, format!(
+            "the image is too small to hold a sprite at ({}, {}) of size {}x{}",
+            rect.x, rect.y, rect.w, rect.h)))
     }
 
     /// Returns a new `Sprite` representing a sub-region of the current one.
@@ -43,6 +139,15 @@ impl Sprite {
     /// Returns `Some` if the `rect` is valid, i.e. included in the current
     /// regiion, and `None` otherwise.
     pub fn region(&self, rect: Rectangle) -> Option<Sprite> {
+        // Nothing is known of the missing image, so that every region of it
+        // is valid and as missing.
+        if self.missing {
+            return Some(Sprite {
+                src: Rectangle { x: 0.0, y: 0.0, ..rect },
+                ..self.clone()
+            });
+        }
+
         let new_src = Rectangle {
             x: rect.x + self.src.x,
             y: rect.y + self.src.y,
@@ -53,7 +158,14 @@ impl Sprite {
         if self.src.contains(new_src) {
             Some(Sprite {
                 tex: self.tex.clone(),
+                memory: self.memory.clone(),
                 src: new_src,
+                // Each region gets its own mask up front, so that collision
+                // checks never have to slice the whole sheet's.
+                mask: self.mask.as_ref()
+                    .and_then(|mask| mask.region(rect))
+                    .map(Rc::new),
+                missing: false,
             })
         } else {
             None
@@ -64,11 +176,65 @@ impl Sprite {
     pub fn size(&self) -> (f64, f64) {
         (self.src.w, self.src.h)
     }
+
+    /// Returns the collision mask of the region, if it has one.
+    pub fn mask(&self) -> Option<&CollisionMask> {
+        self.mask.as_ref().map(|mask| &**mask)
+    }
+
+    /// Multiplies the colors of the texture by `color` when rendering. This
+    /// affects every region of the same texture.
+    pub fn tint(&self, color: Color) {
+        let (r, g, b) = color.rgb();
+        self.tex.borrow_mut().set_color_mod(r, g, b);
+    }
+}
+
+/// Decodes the image at `path` in the `vfs`.
+fn load_surface(path: &str) -> Result<Surface<'static>, String> {
+    let data = try!(vfs::read(path).map_err(|err| err.to_string()));
+    let rwops = try!(RWops::from_bytes(&data));
+    let surface = try!(rwops.load());
+    Ok(surface)
+}
+
+/// Extracts the solid pixels of a surface into a collision mask.
+/// Returns `None` if the surface could not be read.
+fn alpha_mask(surface: &Surface) -> Option<CollisionMask> {
+    // Normalise the pixel layout so that alpha is always in the same place.
+    let rgba = match surface.convert_format(PixelFormatEnum::RGBA8888) {
+        Ok(rgba) => rgba,
+        Err(_) => return None,
+    };
+
+    let (w, h) = (rgba.width() as usize, rgba.height() as usize);
+    let pitch = rgba.pitch() as usize;
+
+    // RGBA8888 is a packed format, so the byte holding alpha depends on
+    // the endianness of the machine.
+    let alpha_offset = if cfg!(target_endian = "little") { 0 } else { 3 };
+
+    let alpha = rgba.with_lock(|pixels| {
+        let mut alpha = Vec::with_capacity(w * h);
+        for y in 0..h {
+            for x in 0..w {
+                alpha.push(pixels[y * pitch + x * 4 + alpha_offset]);
+            }
+        }
+        alpha
+    });
+
+    Some(CollisionMask::from_alpha(&alpha, w, h, ALPHA_THRESHOLD))
 }
 
 impl Renderable for Sprite {
     fn render(&self, renderer: &mut Renderer, dest: Rectangle) {
-        renderer.copy(&mut self.tex.borrow_mut(), self.src.to_sdl(), dest.to_sdl())
+        // Without a destination, the sprite would cover the whole window
+        if let Some(dest) = dest.to_sdl() {
+            // A missing image's checkerboard is stretched over the region
+            let src = if self.missing { None } else { self.src.to_sdl() };
+            renderer.copy(&mut self.tex.borrow_mut(), src, Some(dest))
+        }
     }
 }
 
@@ -82,10 +248,40 @@ impl<'window, T:Renderable> CopySprite<T> for Renderer<'window> {
     }
 }
 
+/// The size of what views draw on: the logical size of the renderer if one is
+/// set, which SDL scales to fit the window, or else the size of the window.
+pub fn screen_size(renderer: &Renderer) -> (f64, f64) {
+    let (w, h) = match renderer.logical_size() {
+        (0, 0) => renderer.output_size().unwrap(),
+        size => size,
+    };
+    (w as f64, h as f64)
+}
+
+/// Draws `Rectangle`s, skipping those which SDL cannot, such as empty ones.
+pub trait DrawRectangle {
+    fn fill_rectangle(&mut self, rect: Rectangle);
+    fn outline_rectangle(&mut self, rect: Rectangle);
+}
+
+impl<'window> DrawRectangle for Renderer<'window> {
+    fn fill_rectangle(&mut self, rect: Rectangle) {
+        if let Some(rect) = rect.to_sdl() {
+            self.fill_rect(rect);
+        }
+    }
+
+    fn outline_rectangle(&mut self, rect: Rectangle) {
+        if let Some(rect) = rect.to_sdl() {
+            self.draw_rect(rect);
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct AnimatedSprite {
     /// The frames that will be rendered, in order.
-    sprites: Vec<Sprite>,
+    sprites: Rc<Vec<Sprite>>,
 
     /// The time between frames, in seconds
     frame_delay: f64,
@@ -121,6 +317,11 @@ impl AnimatedSprite {
         self.sprites.len()
     }
 
+    /// The time between frames in seconds.
+    pub fn frame_delay(&self) -> f64 {
+        self.frame_delay
+    }
+
     /// Set the time between frames in seconds.
     /// Negative values animate in reverse.
     pub fn set_frame_delay(&mut self, frame_delay: f64) {
@@ -148,15 +349,34 @@ impl AnimatedSprite {
             self.current_time = (self.frames() - 1) as f64 * self.frame_delay;
         }
     }
+
+    /// The total alive time of the sprite, in seconds.
+    pub fn time(&self) -> f64 {
+        self.current_time
+    }
+
+    /// Jumps to `time` seconds into the animation, e.g. to restore it.
+    pub fn set_time(&mut self, time: f64) {
+        self.current_time = time;
+    }
+
+    /// Returns the frame that should currently be displayed.
+    pub fn current_sprite(&self) -> &Sprite {
+        let current_frame =
+            (self.current_time / self.frame_delay) as usize % self.frames();
+
+        &self.sprites[current_frame]
+    }
+
+    /// Returns the collision mask of the current frame, if it has one.
+    pub fn mask(&self) -> Option<&CollisionMask> {
+        self.current_sprite().mask()
+    }
 }
 
 impl Renderable for AnimatedSprite {
     /// Renders current frame
     fn render(&self, renderer: &mut Renderer, dest: Rectangle) {
-        let current_frame =
-            (self.current_time / self.frame_delay) as usize % self.frames();
-
-        let sprite = &self.sprites[current_frame];
-        sprite.render(renderer, dest);
+        self.current_sprite().render(renderer, dest);
     }
 }
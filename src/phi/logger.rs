@@ -0,0 +1,239 @@
+//! The logger behind the `log` macros used by `phi` and the views.
+//!
+//! Which records are kept is given by a spec of comma separated levels, as
+//! `warn,phi::events=trace`: the first one applies to every module, and the
+//! others to a module (as a path from the crate's root) and those inside it.
+//! Records are written to stderr, and to a file if one is given, which is
+//! rotated once it grows too large.
+
+use ::log::{self, Log, LogLevelFilter, LogMetadata, LogRecord};
+use ::std::fs::{self, File, OpenOptions};
+use ::std::io::{self, Write};
+use ::std::path::{Path, PathBuf};
+use ::std::sync::Mutex;
+use ::std::time::Instant;
+
+/// The level of modules the spec says nothing about.
+const DEFAULT_LEVEL: LogLevelFilter = LogLevelFilter::Info;
+/// Bytes written to the log file before it is rotated.
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+/// Rotated files kept, as `game.log.1` (the latest) to `game.log.3`.
+const KEEP_FILES: u32 = 3;
+
+/// Which records are kept, parsed from a spec.
+#[derive(Clone, Debug)]
+pub struct Filter {
+    default: LogLevelFilter,
+    /// The most specific module coming last
+    modules: Vec<(String, LogLevelFilter)>,
+}
+
+impl Filter {
+    pub fn new() -> Filter {
+        Filter {
+            default: DEFAULT_LEVEL,
+            modules: Vec::new(),
+        }
+    }
+
+    /// Reads a spec such as `warn,phi::events=trace,views=debug`.
+    pub fn parse(spec: &str) -> Result<Filter, String> {
+        let mut filter = Filter::new();
+
+        for part in spec.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            let mut halves = part.splitn(2, '=');
+            let first = halves.next().unwrap().trim();
+
+            match halves.next() {
+                Some(level) => {
+                    let level = try!(parse_level(level.trim()));
+                    filter.modules.push((first.to_string(), level));
+                },
+                None => filter.default = try!(parse_level(first)),
+            }
+        }
+
+        // Shorter paths first, so that inner modules override outer ones
+        filter.modules.sort_by_key(|&(ref module, _)| module.len());
+        Ok(filter)
+    }
+
+    /// The level of records kept from the module at `target`, which starts
+    /// with the crate's name.
+    fn level_for(&self, target: &str) -> LogLevelFilter {
+        let path = target.find("::").map_or("", |i| &target[i + 2..]);
+
+        self.modules.iter().rev()
+            .find(|&&(ref module, _)| {
+                path == module || (path.starts_with(&module[..]) && path[module.len()..].starts_with("::"))
+            })
+            .map_or(self.default, |&(_, level)| level)
+    }
+
+    /// The most verbose level of any module.
+    fn max_level(&self) -> LogLevelFilter {
+        self.modules.iter().map(|&(_, level)| level).fold(self.default, ::std::cmp::max)
+    }
+}
+
+fn parse_level(level: &str) -> Result<LogLevelFilter, String> {
+    level.parse().map_err(|_| format!("unknown log level `{}`", level))
+}
+
+/// A log file, moved aside to start a new one once too large.
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    fn open(path: &Path) -> io::Result<RotatingFile> {
+        let file = try!(OpenOptions::new().create(true).append(true).open(path));
+        let size = try!(file.metadata()).len();
+
+        Ok(RotatingFile {
+            path: path.to_path_buf(),
+            file: file,
+            size: size,
+        })
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.size + line.len() as u64 > MAX_FILE_SIZE && self.size > 0 {
+            try!(self.rotate());
+        }
+
+        try!(self.file.write_all(line.as_bytes()));
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Shifts `game.log` to `game.log.1`, `game.log.1` to `game.log.2`... and
+    /// starts an empty `game.log`.
+    fn rotate(&mut self) -> io::Result<()> {
+        for i in (1..KEEP_FILES).rev() {
+            let from = self.numbered(i);
+            if from.exists() {
+                try!(fs::rename(&from, self.numbered(i + 1)));
+            }
+        }
+        try!(fs::rename(&self.path, self.numbered(1)));
+
+        self.file = try!(File::create(&self.path));
+        self.size = 0;
+        Ok(())
+    }
+
+    fn numbered(&self, i: u32) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", i));
+        PathBuf::from(name)
+    }
+}
+
+struct Logger {
+    filter: Filter,
+    start: Instant,
+    file: Option<Mutex<RotatingFile>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &LogMetadata) -> bool {
+        metadata.level() <= self.filter.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &LogRecord) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let time = self.start.elapsed();
+        let target = record.target();
+        let line = format!("[{:>4}.{:03} {:<5} {}] {}\n",
+                           time.as_secs(), time.subsec_nanos() / 1_000_000, record.level(),
+                           target.find("::").map_or(target, |i| &target[i + 2..]),
+                           record.args());
+
+        let _ = io::stderr().write_all(line.as_bytes());
+
+        if let Some(ref file) = self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = file.write_line(&line);
+            }
+        }
+    }
+}
+
+/// Starts logging what `filter` keeps, to stderr and to the file at `path`
+/// if there is one. Records logged before are lost.
+pub fn init(filter: Filter, path: Option<&Path>) -> Result<(), String> {
+    let file = match path {
+        Some(path) => Some(Mutex::new(try!(RotatingFile::open(path)
+            .map_err(|err| format!("cannot open {}: {}", path.display(), err))))),
+        None => None,
+    };
+
+    log::set_logger(|max_level| {
+        max_level.set(filter.max_level());
+        Box::new(Logger {
+            filter: filter,
+            start: Instant::now(),
+            file: file,
+        })
+    }).map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::std::env;
+
+    #[test]
+    fn the_first_level_applies_to_every_module() {
+        let filter = Filter::parse("warn").unwrap();
+
+        assert_eq!(filter.level_for("arcade_shooter::views::game"), LogLevelFilter::Warn);
+        assert_eq!(filter.level_for("arcade_shooter"), LogLevelFilter::Warn);
+        assert_eq!(Filter::new().level_for("arcade_shooter::phi"), DEFAULT_LEVEL);
+    }
+
+    #[test]
+    fn inner_modules_override_outer_ones() {
+        let filter = Filter::parse(" phi::events=trace , error, phi=debug").unwrap();
+
+        assert_eq!(filter.level_for("arcade_shooter::phi::events"), LogLevelFilter::Trace);
+        assert_eq!(filter.level_for("arcade_shooter::phi::events::keys"), LogLevelFilter::Trace);
+        assert_eq!(filter.level_for("arcade_shooter::phi::gfx"), LogLevelFilter::Debug);
+        assert_eq!(filter.level_for("arcade_shooter::phi_other"), LogLevelFilter::Error);
+        assert_eq!(filter.level_for("arcade_shooter::views"), LogLevelFilter::Error);
+        assert_eq!(filter.max_level(), LogLevelFilter::Trace);
+    }
+
+    #[test]
+    fn rejects_unknown_levels() {
+        assert!(Filter::parse("loud").is_err());
+        assert!(Filter::parse("warn,phi=loud").is_err());
+        assert!(Filter::parse("").is_ok());
+    }
+
+    #[test]
+    fn rotates_files_which_grow_too_large() {
+        let dir = env::temp_dir().join(format!("arcade_shooter_logger_{}", ::std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("game.log");
+
+        let mut file = RotatingFile::open(&path).unwrap();
+        let line = format!("{}\n", "x".repeat(MAX_FILE_SIZE as usize / 2));
+        for _ in 0..2 + KEEP_FILES * 2 {
+            file.write_line(&line).unwrap();
+        }
+
+        assert!(file.numbered(KEEP_FILES).exists());
+        assert!(!file.numbered(KEEP_FILES + 1).exists());
+        assert!(fs::metadata(&path).unwrap().len() <= MAX_FILE_SIZE);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,13 +1,35 @@
 #[macro_use]
 mod events;
+pub mod collision;
+pub mod config;
+pub mod console;
 pub mod data;
+pub mod debug;
+pub mod error;
+pub mod fallback_font;
 pub mod gfx;
+pub mod logger;
+pub mod net;
+pub mod profiler;
+pub mod rng;
+pub mod rollback;
+pub mod settings;
+pub mod tween;
+pub mod ui;
+pub mod vfs;
 
+use self::console::{Command, Console};
+use self::debug::DebugOverlay;
+use self::error::{PhiError, PhiResult};
+use self::profiler::Profiler;
 use self::gfx::Sprite;
+use self::settings::Settings;
 use ::sdl2::render::Renderer;
 use ::sdl2::pixels::Color;
-use ::std::collections::HashMap;
-use ::std::path::Path;
+use ::sdl2::rwops::RWops;
+use ::sdl2_ttf::LoaderRWops;
+use ::std::collections::{HashMap, HashSet};
+use ::std::path::PathBuf;
 
 struct_events! {
     keyboard: {
@@ -18,6 +40,24 @@ struct_events! {
         key_right: Right,
         key_space: Space,
         key_return: Return,
+        key_fire: Space,
+        key_p2_up: W,
+        key_p2_down: S,
+        key_p2_left: A,
+        key_p2_right: D,
+        key_p2_fire: LShift,
+        key_debug: F3,
+        key_console: Backquote,
+        key_profiler: F4,
+    },
+    controller: {
+        pad_up: DPadUp,
+        pad_down: DPadDown,
+        pad_left: DPadLeft,
+        pad_right: DPadRight,
+        pad_a: A,
+        pad_b: B,
+        pad_start: Start,
     },
     else: {
         quit: Quit { .. }
@@ -25,58 +65,160 @@ struct_events! {
 }
 
 
+/// Strings rendered with the fallback font kept at once, after which they are
+/// all forgotten, e.g. as a score keeps changing.
+const FALLBACK_CACHE_SIZE: usize = 256;
+
 /// Bundles the Phi abstractions in a single structure which can be passed
 /// easily between functions.
 pub struct Phi<'window> {
     pub events: Events,
     pub renderer: Renderer<'window>,
+    pub settings: Settings,
+    pub debug: DebugOverlay,
+    pub profiler: Profiler,
+    pub console: Console,
+    /// How much faster than real time the views are told time goes by
+    pub time_scale: f64,
 
-    cached_fonts: HashMap<(&'static str, i32), ::sdl2_ttf::Font>,
+    settings_path: Option<PathBuf>,
+    /// Fonts are read from memory, which SDL reads from until they are closed
+    cached_fonts: HashMap<(&'static str, i32), (::sdl2_ttf::Font, RWops<'static>)>,
+    /// The fonts which could not be loaded, at each size
+    failed_fonts: HashSet<(&'static str, i32)>,
+    /// Strings already rendered with the fallback font, by size and color,
+    /// as drawing it dot by dot is too slow to do every frame
+    fallback_sprites: HashMap<(String, i32, (u8, u8, u8, u8)), Sprite>,
 }
 
 impl<'window> Phi<'window> {
-    fn new(events: Events, renderer: Renderer<'window>) -> Phi<'window> {
+    fn new(events: Events, renderer: Renderer<'window>,
+           settings: Settings, settings_path: Option<PathBuf>) -> Phi<'window> {
         Phi {
             events: events,
             renderer: renderer,
+            settings: settings,
+            debug: DebugOverlay::new(),
+            profiler: Profiler::new(),
+            console: Console::new(),
+            time_scale: 1.0,
+            settings_path: settings_path,
             cached_fonts: HashMap::new(),
+            failed_fonts: HashSet::new(),
+            fallback_sprites: HashMap::new(),
+        }
+    }
+
+    /// Makes the window and the key bindings match `self.settings`.
+    pub fn apply_settings(&mut self) {
+        self.events.bindings = self.settings.bindings;
+
+        let mode = self.settings.window_mode;
+        let (w, h) = self.settings.resolution;
+
+        if let Some(window) = self.renderer.window_mut() {
+            // Leave fullscreen first, so that the size applies to the window.
+            if let Err(err) = window.set_fullscreen(mode.fullscreen_type()) {
+                warn!("Could not set window mode to {}: {}", mode.name(), err);
+            }
+            let _ = window.set_size(w, h);
+        }
+    }
+
+    /// Writes `self.settings` to the settings file, so that they are used the
+    /// next time the game starts.
+    pub fn save_settings(&self) {
+        if let Some(ref path) = self.settings_path {
+            if let Err(err) = self.settings.save(path) {
+                warn!("Could not save settings to {}: {}", path.display(), err);
+            }
         }
     }
 
+    /// Returns where the file called `file` should be stored, next to the
+    /// settings file, if there is a place for it.
+    pub fn config_path(&self, file: &str) -> Option<PathBuf> {
+        self.settings_path.as_ref()
+            .and_then(|path| path.parent())
+            .map(|dir| dir.join(file))
+    }
+
+    /// The size of the screen, or of the playfield the renderer is set to
+    /// scale to it, see `gfx::screen_size`.
     pub fn output_size(&self) -> (f64, f64) {
-        let (w, h) = self.renderer.output_size().unwrap();
-        (w as f64, h as f64)
+        ::phi::gfx::screen_size(&self.renderer)
     }
 
     /// Renders a string of text as a sprite using provided parameters.
+    /// If the font could not be loaded, which is logged once, the text is
+    /// rendered with the built-in `fallback_font` instead.
     pub fn ttf_str_sprite(&mut self, text: &str, font_path: &'static str,
                           size: i32, color: Color) -> Option<Sprite> {
        // If cached, used that to render text
-       if let Some(font) = self.cached_fonts.get(&(font_path, size)) {
+       if let Some(&(ref font, _)) = self.cached_fonts.get(&(font_path, size)) {
            return font.render(text, ::sdl2_ttf::blended(color)).ok()
                 .and_then(|surface| self.renderer
                     .create_texture_from_surface(&surface).ok())
                 .map(Sprite::new)
         }
 
+       // Text is rendered every frame, so a font is only tried once.
+       if self.failed_fonts.contains(&(font_path, size)) {
+           let key = (text.to_string(), size, color.rgba());
+           if let Some(sprite) = self.fallback_sprites.get(&key) {
+               return Some(sprite.clone());
+           }
+
+           let sprite = fallback_font::render(text, size, color).ok()
+                .and_then(|surface| self.renderer
+                    .create_texture_from_surface(&surface).ok())
+                .map(Sprite::new);
+
+           if let Some(ref sprite) = sprite {
+               if self.fallback_sprites.len() >= FALLBACK_CACHE_SIZE {
+                   self.fallback_sprites.clear();
+               }
+               self.fallback_sprites.insert(key, sprite.clone());
+           }
+           return sprite;
+       }
+
        // Otherwise, try to load requested font, then render.
-       ::sdl2_ttf::Font::from_file(Path::new(font_path), size).ok() 
-           // Closure ensure lifetime of `font` lasts to until texture created
-           .and_then(|font| {
+       match load_font(font_path, size) {
+           Ok(font) => {
                self.cached_fonts.insert((font_path, size), font);
                // Recursively call method so that sprite is taken from cache.
                self.ttf_str_sprite(text, font_path, size, color)
-           })
+           },
+           Err(err) => {
+               warn!("{}, using the built-in font", PhiError::font(font_path, size, err));
+               self.failed_fonts.insert((font_path, size));
+               self.ttf_str_sprite(text, font_path, size, color)
+           },
+       }
     }
 }
 
+/// Opens the font at `path` in the `vfs`, along with what it is read from.
+fn load_font(path: &str, size: i32) -> Result<(::sdl2_ttf::Font, RWops<'static>), String> {
+    let data = try!(vfs::read_kept(path).map_err(|err| err.to_string()));
+    let rwops = try!(RWops::from_bytes(data));
+    let font = try!(rwops.load_font(size));
+    Ok((font, rwops))
+}
+
 /// A `ViewAction` is a way for the currently executed view to communicate
 /// with the game loop. It specifies which action should be executed
 /// before the next rendering.
 pub enum ViewAction {
     None,
     Quit,
+    /// Replaces the current view, and forgets those it was shown over.
     ChangeView(Box<View>),
+    /// Shows a view over the current one, which is resumed once it pops.
+    PushView(Box<View>),
+    /// Goes back to the view below, or quits if there is none.
+    PopView,
 }
 
 
@@ -85,11 +227,24 @@ pub trait View {
     ///
     /// `elapsed` is expressed in seconds.
     fn render(&mut self, context: &mut Phi, elapsed: f64) -> ViewAction;
+
+    /// The console commands which only make sense while this view is shown.
+    fn commands(&self) -> Vec<Command> {
+        Vec::new()
+    }
+
+    /// Runs one of the commands returned by `commands()`, given its
+    /// arguments, or explains why it could not.
+    fn run_command(&mut self, _context: &mut Phi, name: &str, _args: &[&str])
+                   -> Result<ViewAction, String> {
+        Err(format!("`{}` is not available here", name))
+    }
 }
 
 
 /// Create a window with name `title`, initialize the underlying libraries and
-/// start the game with the `View` returned by `init()`.
+/// start the game with the `View` returned by `init()`. Fails if SDL or the
+/// window could not be set up.
 ///
 /// # Examples
 ///
@@ -115,30 +270,47 @@ pub trait View {
 ///     Box::new(MyView)
 /// });
 /// ```
-pub fn spawn<F>(title: &str, init: F)
+pub fn spawn<F>(title: &str, init: F) -> PhiResult<()>
 where F: Fn(&mut Phi) -> Box<View> {
  // init SDL2
-    let sdl_context = ::sdl2::init().unwrap();
-    let video = sdl_context.video().unwrap();
-    let mut timer = sdl_context.timer().unwrap();
-    let _image_context = ::sdl2_image::init(::sdl2_image::INIT_PNG).unwrap();
-    let _ttf_context = ::sdl2_ttf::init().unwrap();
+    let sdl_context = try!(::sdl2::init().map_err(|err| PhiError::init("SDL", err)));
+    let video = try!(sdl_context.video().map_err(|err| PhiError::init("the video subsystem", err)));
+    let controllers = try!(sdl_context.game_controller()
+        .map_err(|err| PhiError::init("the game controller subsystem", err)));
+    let mut timer = try!(sdl_context.timer().map_err(|err| PhiError::init("the timer subsystem", err)));
+    let _image_context = try!(::sdl2_image::init(::sdl2_image::INIT_PNG)
+        .map_err(|err| PhiError::init("SDL_image", err)));
+    let _ttf_context = try!(::sdl2_ttf::init().map_err(|err| PhiError::init("SDL_ttf", err)));
+
+    // load settings saved by a previous run
+    let app_name = title.to_lowercase().replace(' ', "-");
+    let settings_path = Settings::path(&app_name);
+    let settings = settings_path.as_ref()
+        .map_or_else(Settings::new, |path| Settings::load(path));
 
     // create window
-    let window = video.window (title, 800, 600)
+    let (width, height) = settings.resolution;
+    let window = try!(video.window (title, width, height)
         .position_centered().opengl().resizable()
-        .build().unwrap();
+        .build().map_err(PhiError::window));
+    let renderer = try!(window.renderer()
+        .accelerated()
+        .build().map_err(PhiError::window));
+    let event_pump = try!(sdl_context.event_pump().map_err(|err| PhiError::init("the event pump", err)));
 
     // create context
     let mut context = Phi::new(
-        Events::new(sdl_context.event_pump().unwrap()),
-        window.renderer()
-            .accelerated()
-            .build().unwrap());
+        Events::new(event_pump, controllers),
+        renderer,
+        settings,
+        settings_path);
 
-    // create default view and set as current
+    context.apply_settings();
+
+    // create default view and set as current, views pushed over it being
+    // kept below
     let mut current_view = init(&mut context);
-        //Box<View> = Box::new(::views::DefaultView);
+    let mut stack: Vec<Box<View>> = Vec::new();
 
     // frame timing
     let interval = 1_000 / 60;
@@ -162,24 +334,69 @@ where F: Fn(&mut Phi) -> Box<View> {
         fps += 1;
 
         if now - last_second > 1_000 {
-            println!("fps: {}", fps);
+            debug!("fps: {}", fps);
             last_second = now;
             fps = 0;
         }
 
         // Logic and Rendering
 
-        context.events.pump(&mut context.renderer);
+        context.profiler.frame();
+
+        {
+            let _zone = context.profiler.zone("events");
+            context.events.pump(&mut context.renderer);
+        }
+        context.debug.frame(elapsed);
+
+        if context.events.now.key_debug == Some(true) {
+            context.debug.toggle();
+        }
+        if context.events.now.key_profiler == Some(true) {
+            context.profiler.toggle();
+        }
+
+        // The console takes the keyboard away from the view while it is open
+        let action = match console::update(&mut context, &mut *current_view) {
+            ViewAction::None => {
+                let _zone = context.profiler.zone("view");
+                let scaled = elapsed * context.time_scale;
+                current_view.render(&mut context, scaled)
+            },
+            action => action,
+        };
 
-        match current_view.render(&mut context, elapsed) {
-            ViewAction::None =>
-                context.renderer.present(),
+        match action {
+            ViewAction::None => {
+                {
+                    let _zone = context.profiler.zone("overlays");
+                    debug::render(&mut context);
+                    profiler::render(&mut context);
+                    console::render(&mut context, elapsed);
+                }
+
+                let _zone = context.profiler.zone("present");
+                context.renderer.present();
+            },
 
             ViewAction::Quit =>
                 break,
 
-            ViewAction::ChangeView(new_view) =>
-                current_view = new_view,
+            // The views below are left for good, rather than popped back to
+            ViewAction::ChangeView(new_view) => {
+                stack.clear();
+                current_view = new_view;
+            },
+
+            ViewAction::PushView(new_view) =>
+                stack.push(::std::mem::replace(&mut current_view, new_view)),
+
+            ViewAction::PopView => match stack.pop() {
+                Some(view) => current_view = view,
+                None => break,
+            },
         }
     }
+
+    Ok(())
 }
@@ -0,0 +1,130 @@
+use ::phi::rng::Rng;
+use ::std::cell::RefCell;
+use ::std::io;
+use ::std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use ::std::rc::Rc;
+use ::std::time::{Duration, Instant};
+
+/// Largest packet which may be received.
+const MAX_PACKET_SIZE: usize = 1024;
+
+/// A way to exchange packets with another peer, which may lose, delay or
+/// reorder them.
+pub trait Link {
+    fn send(&mut self, packet: &[u8]);
+    /// The next packet received, if any, without waiting for one.
+    fn receive(&mut self) -> Option<Vec<u8>>;
+}
+
+/// Exchanges packets with a peer over UDP.
+pub struct UdpLink {
+    socket: UdpSocket,
+    peer: SocketAddr,
+}
+
+impl UdpLink {
+    /// Listens on the `local` address, e.g. `0.0.0.0:7000`, for packets from
+    /// the `peer` one, e.g. `192.168.1.20:7000`.
+    pub fn bind(local: &str, peer: &str) -> io::Result<UdpLink> {
+        let peer = match try!(peer.to_socket_addrs()).next() {
+            Some(peer) => peer,
+            None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "no address for peer")),
+        };
+
+        let socket = try!(UdpSocket::bind(local));
+        try!(socket.set_nonblocking(true));
+
+        Ok(UdpLink {
+            socket: socket,
+            peer: peer,
+        })
+    }
+}
+
+impl Link for UdpLink {
+    fn send(&mut self, packet: &[u8]) {
+        // Packets are sent again until acknowledged, so losing one is fine.
+        let _ = self.socket.send_to(packet, self.peer);
+    }
+
+    fn receive(&mut self) -> Option<Vec<u8>> {
+        let mut buffer = [0; MAX_PACKET_SIZE];
+
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((len, from)) if from == self.peer => return Some(buffer[..len].to_vec()),
+                // Ignore strangers
+                Ok(_) => continue,
+                Err(_) => return None,
+            }
+        }
+    }
+}
+
+/// How bad a simulated network is.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Conditions {
+    /// Seconds every packet takes to arrive
+    pub latency: f64,
+    /// Seconds up to which a packet may take longer, which reorders them
+    pub jitter: f64,
+    /// Proportion of the packets lost, in `[0, 1]`
+    pub loss: f64,
+}
+
+/// Packets on their way, with the time at which they arrive.
+type Queue = Rc<RefCell<Vec<(Instant, Vec<u8>)>>>;
+
+/// One end of a simulated network inside of the process, to try out
+/// networked games without a second machine.
+pub struct LossyLink {
+    conditions: Conditions,
+    rng: Rng,
+    outgoing: Queue,
+    incoming: Queue,
+}
+
+impl LossyLink {
+    /// Both ends of a simulated network, whose randomness comes from `seed`.
+    pub fn pair(conditions: Conditions, seed: u64) -> (LossyLink, LossyLink) {
+        let there: Queue = Rc::new(RefCell::new(Vec::new()));
+        let back: Queue = Rc::new(RefCell::new(Vec::new()));
+
+        (LossyLink {
+            conditions: conditions,
+            rng: Rng::new(seed),
+            outgoing: there.clone(),
+            incoming: back.clone(),
+        },
+         LossyLink {
+            conditions: conditions,
+            rng: Rng::new(seed.wrapping_add(1)),
+            outgoing: back,
+            incoming: there,
+        })
+    }
+}
+
+impl Link for LossyLink {
+    fn send(&mut self, packet: &[u8]) {
+        if self.rng.next_f64() < self.conditions.loss {
+            return;
+        }
+
+        let delay = self.conditions.latency + self.conditions.jitter * self.rng.next_f64();
+        let arrival = Instant::now() + Duration::from_millis((delay * 1000.0) as u64);
+        self.outgoing.borrow_mut().push((arrival, packet.to_vec()));
+    }
+
+    fn receive(&mut self) -> Option<Vec<u8>> {
+        let now = Instant::now();
+        let mut incoming = self.incoming.borrow_mut();
+
+        let first = incoming.iter().enumerate()
+            .filter(|&(_, &(arrival, _))| arrival <= now)
+            .min_by_key(|&(_, &(arrival, _))| arrival)
+            .map(|(i, _)| i);
+
+        first.map(|i| incoming.remove(i).1)
+    }
+}
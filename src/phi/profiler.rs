@@ -0,0 +1,406 @@
+//! Measures where the time of every frame goes, in zones named after what is
+//! being done: handling events, updating the game, rendering a layer...
+//!
+//! A zone lasts as long as the timer returned by `Profiler::zone` is alive,
+//! and may hold others:
+//!
+//! ```
+//! let _zone = phi.profiler.zone("draw");
+//! {
+//!     let _zone = phi.profiler.zone("backgrounds");
+//!     // ...
+//! }
+//! ```
+//!
+//! The zones of the last frames are kept, to be drawn as a graph toggled with
+//! `key_profiler`, or written as a Chrome trace (see `chrome://tracing`).
+
+use ::phi::Phi;
+use ::phi::data::Rectangle;
+use ::phi::gfx::{CopySprite, DrawRectangle};
+use ::sdl2::pixels::Color;
+use ::sdl2::rect::Point;
+use ::sdl2::render::BlendMode;
+use ::std::cell::RefCell;
+use ::std::fs::File;
+use ::std::io::{self, Write};
+use ::std::path::Path;
+use ::std::rc::Rc;
+use ::std::time::{Duration, Instant};
+
+/// Frames kept, and shown by the graph.
+const HISTORY: usize = 240;
+/// Milliseconds a frame should take, marked on the graph.
+const BUDGET_MS: f64 = 1000.0 / 60.0;
+/// Pixels of the graph per millisecond.
+const GRAPH_SCALE: f64 = 4.0;
+const BAR_WIDTH: f64 = 2.0;
+/// Height of a level of the flame graph, and how many levels it shows.
+const FLAME_ROW: f64 = 14.0;
+const FLAME_DEPTH: usize = 5;
+const TEXT_SIZE: i32 = 14;
+const MARGIN: f64 = 8.0;
+
+/// The colours of the zones, given in the order their names are first seen.
+const PALETTE: [Color; 8] = [
+    Color::RGB(230, 90, 90),
+    Color::RGB(90, 200, 110),
+    Color::RGB(90, 140, 240),
+    Color::RGB(240, 200, 70),
+    Color::RGB(200, 110, 230),
+    Color::RGB(80, 210, 210),
+    Color::RGB(240, 150, 60),
+    Color::RGB(170, 170, 170),
+];
+
+/// A zone which ended, its times being in seconds since the profiler started.
+#[derive(Clone, Copy, Debug)]
+struct Zone {
+    name: &'static str,
+    /// How many zones held this one
+    depth: usize,
+    start: f64,
+    duration: f64,
+}
+
+#[derive(Clone, Debug)]
+struct Frame {
+    start: f64,
+    duration: f64,
+    /// In the order they ended, so that inner zones come first
+    zones: Vec<Zone>,
+}
+
+impl Frame {
+    /// How long the outermost zones called `name` took altogether.
+    fn time_in(&self, name: &str) -> f64 {
+        self.zones.iter()
+            .filter(|zone| zone.depth == 0 && zone.name == name)
+            .map(|zone| zone.duration)
+            .sum()
+    }
+}
+
+/// What timers write to.
+struct Recorder {
+    origin: Instant,
+    /// The zones started and not ended yet, outermost first
+    open: Vec<(&'static str, f64)>,
+    current: Frame,
+    /// The last frames, `next` being the oldest once `HISTORY` are kept
+    frames: Vec<Frame>,
+    next: usize,
+    /// Every zone name seen, in order, which gives their colours
+    names: Vec<&'static str>,
+}
+
+impl Recorder {
+    fn now(&self) -> f64 {
+        seconds(self.origin.elapsed())
+    }
+
+    fn frames(&self) -> Vec<&Frame> {
+        self.frames[self.next..].iter().chain(self.frames[..self.next].iter()).collect()
+    }
+
+    fn color(&self, name: &str) -> Color {
+        let index = self.names.iter().position(|&known| known == name).unwrap_or(0);
+        PALETTE[index % PALETTE.len()]
+    }
+}
+
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 * 1e-9
+}
+
+/// Measures a zone until it is dropped.
+pub struct Timer {
+    recorder: Rc<RefCell<Recorder>>,
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        let mut recorder = self.recorder.borrow_mut();
+        let now = recorder.now();
+
+        if let Some((name, start)) = recorder.open.pop() {
+            let depth = recorder.open.len();
+            recorder.current.zones.push(Zone {
+                name: name,
+                depth: depth,
+                start: start,
+                duration: now - start,
+            });
+        }
+    }
+}
+
+pub struct Profiler {
+    recorder: Rc<RefCell<Recorder>>,
+    shown: bool,
+    /// The font of the graph's legend, without which only the graph is shown
+    font: Option<&'static str>,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler {
+            recorder: Rc::new(RefCell::new(Recorder {
+                origin: Instant::now(),
+                open: Vec::new(),
+                current: Frame { start: 0.0, duration: 0.0, zones: Vec::new() },
+                frames: Vec::with_capacity(HISTORY),
+                next: 0,
+                names: Vec::new(),
+            })),
+            shown: false,
+            font: None,
+        }
+    }
+
+    pub fn is_shown(&self) -> bool {
+        self.shown
+    }
+
+    /// Shows or hides the graph. Zones are measured either way.
+    pub fn toggle(&mut self) {
+        self.shown = !self.shown;
+    }
+
+    /// Sets the font of the graph's legend.
+    pub fn set_font(&mut self, font: &'static str) {
+        self.font = Some(font);
+    }
+
+    /// Starts measuring a zone called `name`, which ends when the returned
+    /// timer is dropped.
+    pub fn zone(&self, name: &'static str) -> Timer {
+        let mut recorder = self.recorder.borrow_mut();
+        let now = recorder.now();
+
+        recorder.open.push((name, now));
+        if !recorder.names.contains(&name) {
+            recorder.names.push(name);
+        }
+
+        Timer { recorder: self.recorder.clone() }
+    }
+
+    /// Starts a new frame, keeping the one before it.
+    pub fn frame(&mut self) {
+        let mut recorder = self.recorder.borrow_mut();
+        let now = recorder.now();
+
+        let mut frame = ::std::mem::replace(&mut recorder.current, Frame {
+            start: now,
+            duration: 0.0,
+            zones: Vec::new(),
+        });
+        frame.duration = now - frame.start;
+
+        // Nothing ran before the first frame started
+        if frame.start == 0.0 && frame.zones.is_empty() {
+            return;
+        }
+
+        if recorder.frames.len() < HISTORY {
+            recorder.frames.push(frame);
+        } else {
+            let next = recorder.next;
+            recorder.frames[next] = frame;
+            recorder.next = (next + 1) % HISTORY;
+        }
+    }
+
+    /// Writes the frames kept as a Chrome trace, in the JSON format read by
+    /// `chrome://tracing` and other trace viewers.
+    pub fn write_trace(&self, path: &Path) -> io::Result<()> {
+        let recorder = self.recorder.borrow();
+        let mut events = Vec::new();
+
+        for frame in recorder.frames() {
+            events.push(trace_event("frame", frame.start, frame.duration));
+            for zone in &frame.zones {
+                events.push(trace_event(zone.name, zone.start, zone.duration));
+            }
+        }
+
+        let mut file = try!(File::create(path));
+        try!(write!(file, "{{\"displayTimeUnit\":\"ms\",\"traceEvents\":[\n{}\n]}}\n",
+                    events.join(",\n")));
+        Ok(())
+    }
+}
+
+/// A complete event of the trace format, with times in microseconds.
+fn trace_event(name: &str, start: f64, duration: f64) -> String {
+    format!("{{\"name\":\"{}\",\"ph\":\"X\",\"pid\":1,\"tid\":1,\"ts\":{:.3},\"dur\":{:.3}}}",
+            name.replace('\\', "\\\\").replace('"', "\\\""),
+            start * 1e6, duration * 1e6)
+}
+
+/// Draws the graph in the bottom right corner, if it is shown: a bar per
+/// frame kept, split by outermost zone, and the zones of the slowest of them
+/// as a flame graph above it.
+pub fn render(phi: &mut Phi) {
+    if !phi.profiler.shown {
+        return;
+    }
+
+    let recorder = phi.profiler.recorder.clone();
+    let recorder = recorder.borrow();
+    let frames = recorder.frames();
+    let slowest = match frames.iter().max_by_key(|frame| (frame.duration * 1e9) as u64) {
+        Some(frame) => *frame,
+        None => return,
+    };
+
+    let (win_w, win_h) = phi.output_size();
+    let width = HISTORY as f64 * BAR_WIDTH;
+    let height = BUDGET_MS * GRAPH_SCALE * 2.0;
+    let left = win_w - width - MARGIN;
+    let bottom = win_h - MARGIN;
+
+    phi.renderer.set_blend_mode(BlendMode::Blend);
+    phi.renderer.set_draw_color(Color::RGBA(0, 0, 0, 170));
+    phi.renderer.fill_rectangle(Rectangle {
+        x: left,
+        y: bottom - height - FLAME_ROW * FLAME_DEPTH as f64,
+        w: width,
+        h: height + FLAME_ROW * FLAME_DEPTH as f64,
+    });
+
+    // The bars, outermost zones stacked in the order they ran
+    for (i, frame) in frames.iter().enumerate() {
+        let x = left + i as f64 * BAR_WIDTH;
+        let mut y = bottom;
+
+        for zone in frame.zones.iter().filter(|zone| zone.depth == 0) {
+            let bar = (zone.duration * 1000.0 * GRAPH_SCALE).min(y - (bottom - height));
+            if bar <= 0.0 {
+                continue;
+            }
+
+            phi.renderer.set_draw_color(recorder.color(zone.name));
+            phi.renderer.fill_rectangle(Rectangle { x: x, y: y - bar, w: BAR_WIDTH, h: bar.max(1.0) });
+            y -= bar;
+        }
+    }
+
+    let budget = (bottom - BUDGET_MS * GRAPH_SCALE) as i32;
+    phi.renderer.set_draw_color(Color::RGB(255, 255, 255));
+    phi.renderer.draw_line(Point::new(left as i32, budget), Point::new((left + width) as i32, budget));
+
+    // The flame graph of the slowest frame, as wide as the graph
+    let flame_bottom = bottom - height;
+    let scale = width / slowest.duration.max(1e-6);
+    for zone in slowest.zones.iter().filter(|zone| zone.depth < FLAME_DEPTH) {
+        let rect = Rectangle {
+            x: left + (zone.start - slowest.start) * scale,
+            y: flame_bottom - (zone.depth + 1) as f64 * FLAME_ROW,
+            w: (zone.duration * scale).max(1.0),
+            h: FLAME_ROW - 1.0,
+        };
+
+        phi.renderer.set_draw_color(recorder.color(zone.name));
+        if let Some(rect) = rect.to_sdl() {
+            phi.renderer.fill_rect(rect);
+        }
+    }
+
+    phi.renderer.set_blend_mode(BlendMode::None);
+
+    let font = match phi.profiler.font {
+        Some(font) => font,
+        None => return,
+    };
+
+    // The legend, with the average time of every outermost zone
+    let mut lines = vec![(
+        format!("slowest {:.2} ms, budget {:.2} ms", slowest.duration * 1000.0, BUDGET_MS),
+        Color::RGB(255, 255, 255),
+    )];
+    for &name in &recorder.names {
+        let total: f64 = frames.iter().map(|frame| frame.time_in(name)).sum();
+        if total > 0.0 {
+            let average = total / frames.len() as f64 * 1000.0;
+            lines.push((format!("{}: {:.2} ms", name, average), recorder.color(name)));
+        }
+    }
+
+    let mut y = flame_bottom - FLAME_ROW * FLAME_DEPTH as f64;
+    for (line, color) in lines.into_iter().rev() {
+        if let Some(sprite) = phi.ttf_str_sprite(&line, font, TEXT_SIZE, color) {
+            let (w, h) = sprite.size();
+            y -= h;
+            phi.renderer.copy_sprite(&sprite, Rectangle { x: win_w - w - MARGIN, y: y, w: w, h: h });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::std::env;
+    use ::std::fs;
+
+    /// Records a frame made of three nested zones, the outermost being `name`.
+    fn record_frame(profiler: &mut Profiler, name: &'static str) {
+        profiler.frame();
+        let _outer = profiler.zone(name);
+        let _inner = profiler.zone("inner");
+        let _innermost = profiler.zone("innermost");
+    }
+
+    #[test]
+    fn keeps_the_last_frames_in_order() {
+        let mut profiler = Profiler::new();
+        for _ in 0..10 {
+            record_frame(&mut profiler, "dropped");
+        }
+        for _ in 0..HISTORY - 1 {
+            record_frame(&mut profiler, "kept");
+        }
+        record_frame(&mut profiler, "last");
+        profiler.frame();
+
+        let recorder = profiler.recorder.borrow();
+        let frames = recorder.frames();
+        assert_eq!(frames.len(), HISTORY);
+
+        for (i, frame) in frames.iter().enumerate() {
+            let name = if i == HISTORY - 1 { "last" } else { "kept" };
+            let zones: Vec<(&str, usize)> = frame.zones.iter().map(|zone| (zone.name, zone.depth)).collect();
+            assert_eq!(zones, vec![("innermost", 2), ("inner", 1), (name, 0)]);
+            assert!(frame.zones.iter().all(|zone| zone.start >= frame.start));
+        }
+
+        assert!(frames.windows(2).all(|pair| pair[0].start <= pair[1].start));
+    }
+
+    #[test]
+    fn trace_event_escapes_names() {
+        assert_eq!(trace_event("a \"quoted\\name\"", 0.5, 0.25),
+                   "{\"name\":\"a \\\"quoted\\\\name\\\"\",\"ph\":\"X\",\"pid\":1,\"tid\":1,\
+                    \"ts\":500000.000,\"dur\":250000.000}");
+    }
+
+    #[test]
+    fn writes_every_frame_kept_to_the_trace() {
+        let mut profiler = Profiler::new();
+        record_frame(&mut profiler, "say \"hi\"");
+        record_frame(&mut profiler, "back\\slash");
+        profiler.frame();
+
+        let path = env::temp_dir().join(format!("arcade_shooter_trace_{}.json", ::std::process::id()));
+        profiler.write_trace(&path).unwrap();
+        let trace = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(trace.starts_with("{\"displayTimeUnit\":\"ms\",\"traceEvents\":[\n{\"name\":\"frame\""));
+        assert!(trace.ends_with("}\n]}\n"));
+        assert_eq!(trace.matches("\"ph\":\"X\"").count(), 8);
+        assert!(trace.contains("\"name\":\"say \\\"hi\\\"\""));
+        assert!(trace.contains("\"name\":\"back\\\\slash\""));
+    }
+}
@@ -0,0 +1,117 @@
+use ::std::cell::Cell;
+
+/// A small pseudo-random number generator (xorshift64*) whose whole state is
+/// a single number, so that a game can be saved, replayed or simulated again
+/// on another machine with the same random numbers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// The generator always yielding the same numbers for the same `seed`.
+    pub fn new(seed: u64) -> Rng {
+        // A state of zero would only ever yield zeros.
+        Rng { state: if seed == 0 { 0x2545_f491_4f6c_dd1d } else { seed } }
+    }
+
+    /// A generator seeded differently for every game.
+    pub fn from_entropy() -> Rng {
+        Rng::new(::rand::random::<u64>())
+    }
+
+    /// Everything needed to carry on the sequence with `from_state`.
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn from_state(state: u64) -> Rng {
+        Rng::new(state)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// A number in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        // The 53 high bits fill the mantissa exactly.
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+thread_local!(static CURRENT: Cell<Rng> = Cell::new(Rng::from_entropy()));
+
+/// A number in `[0, 1)` from the thread's current generator.
+pub fn random() -> f64 {
+    CURRENT.with(|current| {
+        let mut rng = current.get();
+        let number = rng.next_f64();
+        current.set(rng);
+        number
+    })
+}
+
+/// Makes `rng` the thread's current generator, and returns the previous one.
+/// A game swaps its own in while it is being simulated, so that several of
+/// them may run side by side, each with its own sequence.
+pub fn swap(rng: Rng) -> Rng {
+    CURRENT.with(|current| {
+        let previous = current.get();
+        current.set(rng);
+        previous
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbers(rng: &mut Rng, count: usize) -> Vec<u64> {
+        (0..count).map(|_| rng.next_u64()).collect()
+    }
+
+    #[test]
+    fn the_same_seed_yields_the_same_numbers() {
+        assert_eq!(numbers(&mut Rng::new(42), 100), numbers(&mut Rng::new(42), 100));
+        assert!(numbers(&mut Rng::new(42), 100) != numbers(&mut Rng::new(43), 100));
+    }
+
+    #[test]
+    fn the_state_carries_on_the_sequence() {
+        let mut rng = Rng::new(7);
+        numbers(&mut rng, 10);
+        let mut resumed = Rng::from_state(rng.state());
+
+        assert_eq!(numbers(&mut rng, 10), numbers(&mut resumed, 10));
+    }
+
+    #[test]
+    fn a_zero_seed_does_not_yield_only_zeros() {
+        assert!(numbers(&mut Rng::new(0), 10).iter().any(|&number| number != 0));
+    }
+
+    #[test]
+    fn floats_are_between_zero_and_one() {
+        let mut rng = Rng::new(3);
+        for _ in 0..1000 {
+            let number = rng.next_f64();
+            assert!(number >= 0.0);
+            assert!(number < 1.0);
+        }
+    }
+
+    #[test]
+    fn swapped_generators_are_used_in_turn() {
+        let mut expected = Rng::new(5);
+        let previous = swap(Rng::new(5));
+
+        assert_eq!(random(), expected.next_f64());
+        assert_eq!(random(), expected.next_f64());
+
+        assert_eq!(swap(previous), expected);
+    }
+}
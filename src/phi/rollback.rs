@@ -0,0 +1,727 @@
+//! Rollback networking for two peers simulating the same game.
+//!
+//! Each peer simulates every frame as soon as its own input is known,
+//! predicting that the other player keeps doing what they last did. When the
+//! actual input of a frame arrives and differs from the prediction, the game
+//! goes back to the state saved before that frame and simulates the following
+//! ones again. Both peers exchange checksums of the frames whose inputs are
+//! all known, which must match if the simulation is deterministic.
+//!
+//! Every packet also carries a number describing how the game is set up,
+//! such as the size of its playfield. Packets from a peer which set it up
+//! another way are ignored, as they cannot be simulated the same way.
+
+use ::std::collections::HashMap;
+
+/// Frames a peer may run ahead of the last input received from the other one,
+/// after which it waits for it.
+pub const MAX_PREDICTION: u32 = 8;
+
+/// The first byte of every packet, to ignore stray ones.
+const PACKET_MAGIC: u8 = 0x52;
+/// Most inputs sent in a single packet, the oldest unacknowledged first.
+const MAX_PACKET_INPUTS: usize = 32;
+/// Checksums kept to be compared with those of the other peer, in frames.
+const CHECKSUM_HISTORY: u32 = 120;
+
+/// The input of a player during a frame, sent to the other peer as a byte.
+pub trait NetInput: Copy + Default + PartialEq {
+    fn to_byte(&self) -> u8;
+    fn from_byte(byte: u8) -> Self;
+}
+
+/// What the game must do to follow the session, in order.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Request<I> {
+    /// Save the state of the game as it is before simulating the frame.
+    Save(u32),
+    /// Go back to the state saved before the frame.
+    Load(u32),
+    /// Simulate the frame with the input of each player, in order.
+    Advance(u32, [I; 2]),
+}
+
+/// The inputs and checksums of a game played by two peers, seen from one of
+/// them.
+pub struct Session<I: NetInput> {
+    /// The index of the local player
+    local: usize,
+    /// How the game is set up, which both peers must agree on
+    setup: u32,
+    /// How the other peer set up the game, if it differs
+    mismatch: Option<u32>,
+    /// Frames between reading the local input and using it, which leaves
+    /// time for it to reach the other peer and avoids most rollbacks
+    delay: u32,
+    /// The next frame to simulate
+    frame: u32,
+    /// The first frame whose inputs are kept below, as older ones can no
+    /// longer be simulated again nor sent
+    base: u32,
+    /// The local input of every frame from `base`
+    local_inputs: Vec<I>,
+    /// The remote input of every frame from `base`, as they are received
+    remote_inputs: Vec<Option<I>>,
+    /// Frames since the start whose remote input was received, without gaps
+    confirmed: u32,
+    /// The remote input each simulated frame from `base` used, predicted or
+    /// not
+    used: Vec<I>,
+    /// The earliest frame to simulate again, if a prediction was wrong
+    rollback_to: Option<u32>,
+    /// Local inputs the other peer acknowledged, from the start
+    acked: u32,
+    /// Checksums of the state after confirmed frames, on each side
+    checksums: HashMap<u32, u64>,
+    remote_checksums: HashMap<u32, u64>,
+    /// The last frame whose checksums matched on both sides
+    verified: Option<u32>,
+    /// The first frame whose checksums differed, if any
+    desync: Option<u32>,
+    rollbacks: u32,
+    started: bool,
+}
+
+impl<I: NetInput> Session<I> {
+    /// A session in which the local player has the index `local`, 0 or 1,
+    /// inputs are used `delay` frames after being read, and the game is set
+    /// up as described by `setup`.
+    pub fn new(local: usize, delay: u32, setup: u32) -> Session<I> {
+        Session {
+            local: local,
+            setup: setup,
+            mismatch: None,
+            delay: delay,
+            frame: 0,
+            base: 0,
+            local_inputs: vec![I::default(); delay as usize],
+            remote_inputs: Vec::new(),
+            confirmed: 0,
+            used: Vec::new(),
+            rollback_to: None,
+            acked: 0,
+            checksums: HashMap::new(),
+            remote_checksums: HashMap::new(),
+            verified: None,
+            desync: None,
+            rollbacks: 0,
+            started: false,
+        }
+    }
+
+    /// The next frame to simulate.
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// Frames whose inputs are all known.
+    pub fn confirmed(&self) -> u32 {
+        self.confirmed.min(self.frame)
+    }
+
+    pub fn rollbacks(&self) -> u32 {
+        self.rollbacks
+    }
+
+    pub fn verified(&self) -> Option<u32> {
+        self.verified
+    }
+
+    pub fn desync(&self) -> Option<u32> {
+        self.desync
+    }
+
+    /// How the other peer set up the game, if it differs from the local
+    /// setup, in which case its packets are ignored.
+    pub fn mismatch(&self) -> Option<u32> {
+        self.mismatch
+    }
+
+    /// Records that the state saved before `frame` could not be loaded, so
+    /// the game no longer matches the other peer's from then on.
+    pub fn lost(&mut self, frame: u32) {
+        if self.desync.map_or(true, |desync| frame < desync) {
+            self.desync = Some(frame);
+        }
+    }
+
+    /// Reads the local input for the frame `delay` frames ahead, unless it
+    /// is already known because the session is waiting for the other peer.
+    pub fn add_local_input(&mut self, input: I) {
+        if self.local_end() <= self.frame + self.delay {
+            self.local_inputs.push(input);
+        }
+    }
+
+    /// The frame after the last one whose local input is known.
+    fn local_end(&self) -> u32 {
+        self.base + self.local_inputs.len() as u32
+    }
+
+    /// What the game must do for this frame: simulate the next one, after
+    /// going back to correct any wrong prediction. Nothing is simulated while
+    /// too far ahead of the other peer.
+    pub fn advance(&mut self) -> Vec<Request<I>> {
+        let mut requests = Vec::new();
+
+        if !self.started {
+            requests.push(Request::Save(0));
+            self.started = true;
+        }
+
+        if let Some(from) = self.rollback_to.take() {
+            requests.push(Request::Load(from));
+            self.rollbacks += 1;
+
+            for frame in from..self.frame {
+                self.simulate(frame, &mut requests);
+            }
+        }
+
+        let waiting = self.frame >= self.confirmed + MAX_PREDICTION ||
+            self.local_end() <= self.frame;
+
+        if !waiting {
+            let frame = self.frame;
+            self.simulate(frame, &mut requests);
+            self.frame += 1;
+        }
+
+        self.forget_old_inputs();
+        requests
+    }
+
+    fn simulate(&mut self, frame: u32, requests: &mut Vec<Request<I>>) {
+        let index = (frame - self.base) as usize;
+        let local = self.local_inputs[index];
+
+        // Predict that the other player keeps the last input received.
+        let remote = match self.remote_inputs.get(index) {
+            Some(&Some(input)) => input,
+            _ if self.confirmed > 0 => self.remote_inputs[(self.confirmed - 1 - self.base) as usize].unwrap(),
+            _ => I::default(),
+        };
+
+        if self.used.len() <= index {
+            self.used.push(remote);
+        } else {
+            self.used[index] = remote;
+        }
+
+        let inputs = if self.local == 0 { [local, remote] } else { [remote, local] };
+        requests.push(Request::Advance(frame, inputs));
+        requests.push(Request::Save(frame + 1));
+    }
+
+    /// Whether `frame` was simulated with every actual input, so that the
+    /// state after it is final.
+    fn is_final(&self, frame: u32) -> bool {
+        frame < self.confirmed && frame < self.frame &&
+            self.rollback_to.map_or(true, |from| frame < from)
+    }
+
+    /// Keeps the checksum of the state after simulating `frame`, to compare it
+    /// with the other peer's once every input of the frame is known.
+    pub fn record_checksum(&mut self, frame: u32, checksum: u64) {
+        self.checksums.insert(frame, checksum);
+        self.compare(frame);
+
+        let oldest = frame.saturating_sub(CHECKSUM_HISTORY);
+        self.checksums.retain(|&f, _| f >= oldest);
+        self.remote_checksums.retain(|&f, _| f >= oldest);
+    }
+
+    fn compare(&mut self, frame: u32) {
+        if !self.is_final(frame) {
+            return;
+        }
+
+        if let (Some(local), Some(remote)) = (self.checksums.get(&frame), self.remote_checksums.get(&frame)) {
+            if local == remote {
+                if self.verified.map_or(true, |verified| verified < frame) {
+                    self.verified = Some(frame);
+                }
+            } else if self.desync.map_or(true, |desync| frame < desync) {
+                self.desync = Some(frame);
+            }
+        }
+    }
+
+    /// Forgets the inputs of the frames which can no longer be simulated
+    /// again, nor have to be sent again, so that they do not pile up for the
+    /// whole game. Every remote input before `confirmed` is known, so a
+    /// rollback never goes back further, and the last of them is still
+    /// needed to predict the following ones.
+    fn forget_old_inputs(&mut self) {
+        let oldest = self.confirmed.saturating_sub(1).min(self.frame).min(self.acked)
+            .min(self.rollback_to.unwrap_or(self.frame));
+        if oldest <= self.base {
+            return;
+        }
+
+        let count = (oldest - self.base) as usize;
+        let local = count.min(self.local_inputs.len());
+        self.local_inputs.drain(..local);
+        let remote = count.min(self.remote_inputs.len());
+        self.remote_inputs.drain(..remote);
+        let used = count.min(self.used.len());
+        self.used.drain(..used);
+        self.base = oldest;
+    }
+
+    fn add_remote_input(&mut self, frame: u32, input: I) {
+        // Every input before `confirmed` is known already, and those too far
+        // ahead cannot have been sent by a peer following the session.
+        if frame < self.confirmed || frame > self.frame + MAX_PREDICTION + MAX_PACKET_INPUTS as u32 {
+            return;
+        }
+
+        let index = (frame - self.base) as usize;
+        while self.remote_inputs.len() <= index {
+            self.remote_inputs.push(None);
+        }
+
+        if self.remote_inputs[index].is_some() {
+            return;
+        }
+        self.remote_inputs[index] = Some(input);
+
+        // Frames already simulated with another input must be simulated again.
+        if frame < self.frame && self.used[index] != input {
+            self.rollback_to = Some(self.rollback_to.map_or(frame, |from| from.min(frame)));
+        }
+
+        let previous = self.confirmed;
+        while ((self.confirmed - self.base) as usize) < self.remote_inputs.len() &&
+            self.remote_inputs[(self.confirmed - self.base) as usize].is_some() {
+            self.confirmed += 1;
+        }
+
+        // Checksums already received may be compared now.
+        for frame in previous..self.confirmed {
+            self.compare(frame);
+        }
+    }
+
+    /// The packet to send to the other peer: the setup of the game, the
+    /// local inputs it has not acknowledged, the remote inputs received, and
+    /// the latest checksum.
+    pub fn packet(&self) -> Vec<u8> {
+        let from = (self.acked - self.base) as usize;
+        let to = self.local_inputs.len().min(from + MAX_PACKET_INPUTS);
+
+        let mut packet = vec![PACKET_MAGIC];
+        write_u32(&mut packet, self.setup);
+        write_u32(&mut packet, self.acked);
+        packet.push((to.saturating_sub(from)) as u8);
+        for input in &self.local_inputs[from.min(to)..to] {
+            packet.push(input.to_byte());
+        }
+
+        write_u32(&mut packet, self.confirmed);
+
+        match self.checksums.keys().filter(|&&frame| self.is_final(frame)).max() {
+            Some(&frame) => {
+                packet.push(1);
+                write_u32(&mut packet, frame);
+                write_u64(&mut packet, self.checksums[&frame]);
+            },
+            None => packet.push(0),
+        }
+
+        packet
+    }
+
+    /// Reads a packet from the other peer. Malformed ones are ignored.
+    pub fn receive(&mut self, packet: &[u8]) {
+        let mut reader = Reader { bytes: packet, pos: 0 };
+        if reader.u8() != Some(PACKET_MAGIC) {
+            return;
+        }
+
+        match reader.u32() {
+            Some(setup) if setup == self.setup => {},
+            Some(setup) => {
+                self.mismatch = Some(setup);
+                return;
+            },
+            None => return,
+        }
+
+        let (from, count) = match (reader.u32(), reader.u8()) {
+            (Some(from), Some(count)) => (from, count),
+            _ => return,
+        };
+
+        let mut inputs = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            match reader.u8() {
+                Some(byte) => inputs.push(I::from_byte(byte)),
+                None => return,
+            }
+        }
+
+        let acked = match reader.u32() {
+            Some(acked) => acked,
+            None => return,
+        };
+
+        let checksum = match reader.u8() {
+            Some(1) => match (reader.u32(), reader.u64()) {
+                (Some(frame), Some(checksum)) => Some((frame, checksum)),
+                _ => return,
+            },
+            _ => None,
+        };
+
+        for (i, input) in inputs.into_iter().enumerate() {
+            self.add_remote_input(from.saturating_add(i as u32), input);
+        }
+
+        self.acked = self.acked.max(acked.min(self.local_end()));
+
+        if let Some((frame, checksum)) = checksum {
+            self.remote_checksums.insert(frame, checksum);
+            self.compare(frame);
+        }
+    }
+}
+
+/// The states saved for the frames which may still be simulated again.
+pub struct Snapshots<S> {
+    states: Vec<Option<(u32, S)>>,
+}
+
+impl<S> Snapshots<S> {
+    pub fn new() -> Snapshots<S> {
+        // Rollbacks never go back further than the predicted frames.
+        let capacity = MAX_PREDICTION as usize + 2;
+        Snapshots { states: (0..capacity).map(|_| None).collect() }
+    }
+
+    pub fn save(&mut self, frame: u32, state: S) {
+        let index = frame as usize % self.states.len();
+        self.states[index] = Some((frame, state));
+    }
+
+    /// The state saved before `frame`, if it was not replaced since.
+    pub fn load(&self, frame: u32) -> Option<&S> {
+        match self.states[frame as usize % self.states.len()] {
+            Some((saved, ref state)) if saved == frame => Some(state),
+            _ => None,
+        }
+    }
+}
+
+/// Sums up the state of a game as a number (FNV-1a), to check that two peers
+/// simulated it the same way.
+pub struct Checksum {
+    hash: u64,
+}
+
+impl Checksum {
+    pub fn new() -> Checksum {
+        Checksum { hash: 0xcbf2_9ce4_8422_2325 }
+    }
+
+    pub fn add_u64(&mut self, value: u64) {
+        for i in 0..8 {
+            self.hash ^= (value >> (i * 8)) & 0xff;
+            self.hash = self.hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub fn add_f64(&mut self, value: f64) {
+        self.add_u64(value.to_bits());
+    }
+
+    pub fn add_str(&mut self, value: &str) {
+        for &byte in value.as_bytes() {
+            self.hash ^= byte as u64;
+            self.hash = self.hash.wrapping_mul(0x0100_0000_01b3);
+        }
+        // So that "ab" then "c" differs from "a" then "bc"
+        self.hash ^= 0xff;
+        self.hash = self.hash.wrapping_mul(0x0100_0000_01b3);
+    }
+
+    pub fn value(&self) -> u64 {
+        self.hash
+    }
+}
+
+fn write_u32(bytes: &mut Vec<u8>, value: u32) {
+    for i in 0..4 {
+        bytes.push((value >> (i * 8)) as u8);
+    }
+}
+
+fn write_u64(bytes: &mut Vec<u8>, value: u64) {
+    for i in 0..8 {
+        bytes.push((value >> (i * 8)) as u8);
+    }
+}
+
+/// Reads little-endian numbers from a packet.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn u8(&mut self) -> Option<u8> {
+        let byte = self.bytes.get(self.pos).cloned();
+        self.pos += 1;
+        byte
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let mut value = 0;
+        for i in 0..4 {
+            match self.u8() {
+                Some(byte) => value |= (byte as u32) << (i * 8),
+                None => return None,
+            }
+        }
+        Some(value)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        let mut value = 0;
+        for i in 0..8 {
+            match self.u8() {
+                Some(byte) => value |= (byte as u64) << (i * 8),
+                None => return None,
+            }
+        }
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::phi::rng::Rng;
+
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    struct Input(u8);
+
+    impl NetInput for Input {
+        fn to_byte(&self) -> u8 {
+            self.0
+        }
+
+        fn from_byte(byte: u8) -> Input {
+            Input(byte)
+        }
+    }
+
+    const SETUP: u32 = 800 << 16 | 600;
+
+    /// One side of a game whose whole state is a number, which depends on
+    /// every input of every frame and on the order they came in.
+    struct Peer {
+        session: Session<Input>,
+        snapshots: Snapshots<u64>,
+        state: u64,
+        /// The state after every frame, as last simulated
+        states: Vec<u64>,
+        /// Where the local inputs come from
+        inputs: Rng,
+        /// Added to the state every frame, to make this peer simulate the
+        /// game differently
+        drift: u64,
+    }
+
+    impl Peer {
+        fn new(local: usize, setup: u32) -> Peer {
+            Peer {
+                session: Session::new(local, 2, setup),
+                snapshots: Snapshots::new(),
+                state: 1,
+                states: Vec::new(),
+                inputs: Rng::new(local as u64 + 1),
+                drift: 0,
+            }
+        }
+
+        fn tick(&mut self) {
+            let input = Input((self.inputs.next_u64() % 16 / 4) as u8);
+            self.session.add_local_input(input);
+
+            for request in self.session.advance() {
+                match request {
+                    Request::Save(frame) => self.snapshots.save(frame, self.state),
+                    Request::Load(frame) => match self.snapshots.load(frame) {
+                        Some(&state) => self.state = state,
+                        None => self.session.lost(frame),
+                    },
+                    Request::Advance(frame, inputs) => {
+                        self.state = self.state.wrapping_mul(31)
+                            .wrapping_add(inputs[0].0 as u64 * 7 + inputs[1].0 as u64 + self.drift);
+
+                        if self.states.len() <= frame as usize {
+                            self.states.push(self.state);
+                        } else {
+                            self.states[frame as usize] = self.state;
+                        }
+                        self.session.record_checksum(frame, self.state);
+                    },
+                }
+            }
+        }
+    }
+
+    /// Carries packets one way, losing some of them and delaying the others
+    /// by a random number of ticks, so that they may arrive out of order.
+    struct Link {
+        rng: Rng,
+        loss: f64,
+        max_delay: u64,
+        /// Packets on their way, with the tick they arrive at
+        queue: Vec<(u64, Vec<u8>)>,
+    }
+
+    impl Link {
+        fn new(seed: u64, loss: f64, max_delay: u64) -> Link {
+            Link { rng: Rng::new(seed), loss: loss, max_delay: max_delay, queue: Vec::new() }
+        }
+
+        fn send(&mut self, now: u64, packet: Vec<u8>) {
+            if self.rng.next_f64() >= self.loss {
+                let delay = 1 + self.rng.next_u64() % self.max_delay;
+                self.queue.push((now + delay, packet));
+            }
+        }
+
+        fn receive(&mut self, now: u64) -> Vec<Vec<u8>> {
+            let (arrived, queued): (Vec<_>, Vec<_>) = self.queue.drain(..).partition(|&(at, _)| at <= now);
+            self.queue = queued;
+            arrived.into_iter().map(|(_, packet)| packet).collect()
+        }
+    }
+
+    /// Plays `ticks` ticks on both peers, then as many again over a perfect
+    /// link so that every input gets through.
+    fn play(peers: &mut [Peer; 2], loss: f64, max_delay: u64, ticks: u64) {
+        let mut links = [Link::new(10, loss, max_delay), Link::new(20, loss, max_delay)];
+
+        for now in 0..ticks * 2 {
+            if now == ticks {
+                for link in &mut links {
+                    link.loss = 0.0;
+                    link.max_delay = 1;
+                }
+            }
+
+            for i in 0..2 {
+                for packet in links[1 - i].receive(now) {
+                    peers[i].session.receive(&packet);
+                }
+                peers[i].tick();
+                let packet = peers[i].session.packet();
+                links[i].send(now, packet);
+            }
+        }
+    }
+
+    fn assert_converged(peers: &[Peer; 2], ticks: u32) {
+        let confirmed = peers[0].session.confirmed().min(peers[1].session.confirmed());
+        assert!(confirmed > ticks, "only {} frames confirmed", confirmed);
+        assert_eq!(&peers[0].states[..confirmed as usize], &peers[1].states[..confirmed as usize]);
+
+        for peer in peers {
+            assert_eq!(peer.session.desync(), None);
+            assert!(peer.session.verified().unwrap() > ticks);
+        }
+    }
+
+    #[test]
+    fn peers_converge_over_a_perfect_link() {
+        let mut peers = [Peer::new(0, SETUP), Peer::new(1, SETUP)];
+        play(&mut peers, 0.0, 1, 300);
+
+        assert_converged(&peers, 300);
+    }
+
+    #[test]
+    fn peers_converge_despite_loss_reordering_and_delay() {
+        let mut peers = [Peer::new(0, SETUP), Peer::new(1, SETUP)];
+        play(&mut peers, 0.25, 6, 600);
+
+        assert_converged(&peers, 600);
+        assert!(peers.iter().any(|peer| peer.session.rollbacks() > 0));
+    }
+
+    #[test]
+    fn old_inputs_are_forgotten() {
+        let mut peers = [Peer::new(0, SETUP), Peer::new(1, SETUP)];
+        play(&mut peers, 0.25, 6, 600);
+
+        for peer in &peers {
+            let session = &peer.session;
+            assert!(session.base > 1000);
+            for &len in &[session.local_inputs.len(), session.remote_inputs.len(), session.used.len()] {
+                assert!(len <= (MAX_PREDICTION + 4) as usize, "{} inputs kept", len);
+            }
+        }
+    }
+
+    #[test]
+    fn inputs_far_ahead_are_ignored() {
+        let mut session = Session::<Input>::new(0, 2, SETUP);
+
+        let mut packet = vec![PACKET_MAGIC];
+        write_u32(&mut packet, SETUP);
+        write_u32(&mut packet, ::std::u32::MAX);
+        packet.extend_from_slice(&[2, 1, 1]);
+        write_u32(&mut packet, 0);
+        packet.push(0);
+        session.receive(&packet);
+
+        assert!(session.remote_inputs.is_empty());
+        assert_eq!(session.confirmed(), 0);
+    }
+
+    #[test]
+    fn different_simulations_are_a_desync() {
+        let mut peers = [Peer::new(0, SETUP), Peer::new(1, SETUP)];
+        peers[1].drift = 1;
+        play(&mut peers, 0.1, 3, 100);
+
+        for peer in &peers {
+            assert!(peer.session.desync().is_some());
+        }
+    }
+
+    #[test]
+    fn lost_snapshots_are_a_desync() {
+        let mut session = Session::<Input>::new(0, 2, SETUP);
+        session.lost(12);
+        session.lost(30);
+
+        assert_eq!(session.desync(), Some(12));
+    }
+
+    #[test]
+    fn packets_of_another_setup_are_ignored() {
+        let mut peers = [Peer::new(0, SETUP), Peer::new(1, 640 << 16 | 480)];
+        play(&mut peers, 0.0, 1, 50);
+
+        assert_eq!(peers[0].session.mismatch(), Some(640 << 16 | 480));
+        assert_eq!(peers[1].session.mismatch(), Some(SETUP));
+        assert_eq!(peers[0].session.confirmed(), 0);
+    }
+
+    #[test]
+    fn snapshots_keep_the_recent_frames() {
+        let mut snapshots = Snapshots::new();
+        for frame in 0..20 {
+            snapshots.save(frame, frame * 10);
+        }
+
+        assert_eq!(snapshots.load(19), Some(&190));
+        assert_eq!(snapshots.load(20 - MAX_PREDICTION), Some(&((20 - MAX_PREDICTION) * 10)));
+        assert_eq!(snapshots.load(0), None);
+    }
+}
@@ -0,0 +1,260 @@
+use ::phi::Bindings;
+use ::phi::config::{self, Config, ParseError};
+use ::sdl2::keyboard::Keycode;
+use ::sdl2::video::FullscreenType;
+use ::std::collections::BTreeMap;
+use ::std::io;
+use ::std::path::{Path, PathBuf};
+
+/// Incremented whenever the meaning of the settings file changes, so that old
+/// files are not misread.
+pub const SETTINGS_VERSION: u32 = 1;
+
+const SETTINGS_FILE: &'static str = "settings.cfg";
+
+/// The window sizes offered to the player.
+pub const RESOLUTIONS: [(u32, u32); 5] = [
+    (800, 600),
+    (1024, 768),
+    (1280, 720),
+    (1600, 900),
+    (1920, 1080),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WindowMode {
+    Windowed,
+    Fullscreen,
+    /// Fullscreen at the resolution of the desktop.
+    Borderless,
+}
+
+impl WindowMode {
+    pub fn all() -> [WindowMode; 3] {
+        [WindowMode::Windowed, WindowMode::Fullscreen, WindowMode::Borderless]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            WindowMode::Windowed => "windowed",
+            WindowMode::Fullscreen => "fullscreen",
+            WindowMode::Borderless => "borderless",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<WindowMode> {
+        WindowMode::all().iter().cloned().find(|mode| mode.name() == name)
+    }
+
+    pub fn fullscreen_type(self) -> FullscreenType {
+        match self {
+            WindowMode::Windowed => FullscreenType::Off,
+            WindowMode::Fullscreen => FullscreenType::True,
+            WindowMode::Borderless => FullscreenType::Desktop,
+        }
+    }
+}
+
+
+/// The preferences of the player, which persist between runs.
+#[derive(Clone, Debug)]
+pub struct Settings {
+    /// Master volume, in `[0, 1]`.
+    pub volume: f64,
+    pub window_mode: WindowMode,
+    pub resolution: (u32, u32),
+    pub bindings: Bindings,
+    /// Switches for gameplay options, which are defined by the game itself.
+    pub gameplay: BTreeMap<String, bool>,
+}
+
+impl Settings {
+    pub fn new() -> Settings {
+        Settings {
+            volume: 0.8,
+            window_mode: WindowMode::Windowed,
+            resolution: RESOLUTIONS[0],
+            bindings: Bindings::new(),
+            gameplay: BTreeMap::new(),
+        }
+    }
+
+    /// Returns where the settings of the application called `app` are stored.
+    pub fn path(app: &str) -> Option<PathBuf> {
+        config::config_dir(app).map(|dir| dir.join(SETTINGS_FILE))
+    }
+
+    /// Loads the settings stored at `path`. If the file does not exist, or is
+    /// invalid or outdated, the default settings are returned instead and a
+    /// warning is printed.
+    pub fn load(path: &Path) -> Settings {
+        if !path.exists() {
+            return Settings::new();
+        }
+
+        let config = match Config::load(path) {
+            Ok(config) => config,
+            Err(err) => {
+                warn!("Could not read {}: {}. Using default settings.", path.display(), err);
+                return Settings::new();
+            },
+        };
+
+        match Settings::from_config(&config) {
+            Ok(settings) => settings,
+            Err(err) => {
+                warn!("Invalid settings in {}: {}. Using default settings.", path.display(), err);
+                Settings::new()
+            },
+        }
+    }
+
+    /// Reads settings from a parsed configuration. Missing values keep their
+    /// default.
+    pub fn from_config(config: &Config) -> Result<Settings, ParseError> {
+        match try!(config.parsed::<u32>("", "version")) {
+            Some(SETTINGS_VERSION) => {},
+            Some(version) => return Err(ParseError {
+                line: config.entry("", "version").map_or(0, |entry| entry.line),
+                message: format!("unsupported version {} (expected {})",
+                                 version, SETTINGS_VERSION),
+            }),
+            None => return Err(ParseError {
+                line: 0,
+                message: "missing version".to_string(),
+            }),
+        }
+
+        let mut settings = Settings::new();
+
+        if let Some(volume) = try!(config.parsed::<f64>("audio", "volume")) {
+            if !volume.is_finite() {
+                return Err(ParseError {
+                    line: config.entry("audio", "volume").map_or(0, |entry| entry.line),
+                    message: format!("invalid volume {}", volume),
+                });
+            }
+            settings.volume = volume.max(0.0).min(1.0);
+        }
+
+        if let Some(entry) = config.entry("video", "mode") {
+            settings.window_mode = try!(WindowMode::from_name(&entry.value).ok_or(ParseError {
+                line: entry.line,
+                message: format!("unknown window mode `{}`", entry.value),
+            }));
+        }
+
+        if let (Some(w), Some(h)) = (try!(config.parsed::<u32>("video", "width")),
+                                     try!(config.parsed::<u32>("video", "height"))) {
+            // A window cannot be built without pixels
+            if w == 0 || h == 0 {
+                return Err(ParseError {
+                    line: config.entry("video", if w == 0 { "width" } else { "height" })
+                        .map_or(0, |entry| entry.line),
+                    message: format!("invalid resolution {}x{}", w, h),
+                });
+            }
+            settings.resolution = (w, h);
+        }
+
+        for entry in config.entries().iter().filter(|entry| entry.section == "bindings") {
+            let keycode = try!(Keycode::from_name(&entry.value).ok_or(ParseError {
+                line: entry.line,
+                message: format!("unknown key `{}`", entry.value),
+            }));
+
+            if !settings.bindings.set(&entry.key, keycode) {
+                return Err(ParseError {
+                    line: entry.line,
+                    message: format!("unknown binding `{}`", entry.key),
+                });
+            }
+        }
+
+        for entry in config.entries().iter().filter(|entry| entry.section == "gameplay") {
+            let enabled = try!(entry.value.parse().map_err(|_| ParseError {
+                line: entry.line,
+                message: format!("expected `true` or `false` for `{}`", entry.key),
+            }));
+            settings.gameplay.insert(entry.key.clone(), enabled);
+        }
+
+        Ok(settings)
+    }
+
+    pub fn to_config(&self) -> Config {
+        let mut config = Config::new();
+
+        config.set("", "version", SETTINGS_VERSION);
+
+        config.set("audio", "volume", self.volume);
+
+        config.set("video", "mode", self.window_mode.name());
+        config.set("video", "width", self.resolution.0);
+        config.set("video", "height", self.resolution.1);
+
+        for name in Bindings::names() {
+            if let Some(keycode) = self.bindings.get(name) {
+                config.set("bindings", name, keycode.name());
+            }
+        }
+
+        for (name, enabled) in &self.gameplay {
+            config.set("gameplay", name, enabled);
+        }
+
+        config
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        self.to_config().save(path)
+    }
+
+    /// Returns whether the gameplay option `name` is enabled, or `default` if
+    /// it was never set.
+    pub fn gameplay(&self, name: &str, default: bool) -> bool {
+        self.gameplay.get(name).cloned().unwrap_or(default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::phi::config::Config;
+
+    #[test]
+    fn rejects_empty_resolutions() {
+        let config = Config::parse("version = 1\n[video]\nwidth = 0\nheight = 600\n").unwrap();
+        assert_eq!(Settings::from_config(&config).unwrap_err().line, 3);
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let config = Config::parse("version = 2\n").unwrap();
+        assert!(Settings::from_config(&config).is_err());
+        assert!(Settings::from_config(&Config::new()).is_err());
+    }
+
+    #[test]
+    fn volume_round_trips_and_is_clamped() {
+        let mut settings = Settings::new();
+        settings.volume = 0.35;
+
+        let config = Config::parse(&settings.to_config().to_string()).unwrap();
+        assert_eq!(config.get("audio", "volume"), Some("0.35"));
+        assert_eq!(Settings::from_config(&config).unwrap().volume, 0.35);
+
+        let config = Config::parse("version = 1\n[audio]\nvolume = 1.5\n").unwrap();
+        assert_eq!(Settings::from_config(&config).unwrap().volume, 1.0);
+        let config = Config::parse("version = 1\n[audio]\nvolume = -2\n").unwrap();
+        assert_eq!(Settings::from_config(&config).unwrap().volume, 0.0);
+        let config = Config::parse("version = 1\n[audio]\nvolume = NaN\n").unwrap();
+        assert_eq!(Settings::from_config(&config).unwrap_err().line, 3);
+    }
+
+    #[test]
+    fn reads_the_resolution() {
+        let config = Config::parse("version = 1\n[video]\nwidth = 1280\nheight = 720\n").unwrap();
+        assert_eq!(Settings::from_config(&config).unwrap().resolution, (1280, 720));
+    }
+}
@@ -0,0 +1,581 @@
+use ::phi::data::Rectangle;
+use ::sdl2::pixels::Color;
+use ::std::cell::Cell;
+use ::std::f64::consts::PI;
+use ::std::rc::Rc;
+
+/// The shape of a transition, mapping linear progress in `[0, 1]` to eased
+/// progress. Curves may leave `[0, 1]` in between (e.g. `BackOut` overshoots)
+/// but always start at 0 and end at 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Ease {
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    ElasticIn,
+    ElasticOut,
+    ElasticInOut,
+    BackIn,
+    BackOut,
+    BackInOut,
+    BounceIn,
+    BounceOut,
+    BounceInOut,
+}
+
+impl Ease {
+    /// Applies the curve to a progress `t`, which is clamped to `[0, 1]`.
+    pub fn apply(self, t: f64) -> f64 {
+        let t = t.max(0.0).min(1.0);
+
+        match self {
+            Ease::Linear => t,
+
+            Ease::QuadIn => t * t,
+            Ease::QuadOut => 1.0 - Ease::QuadIn.apply(1.0 - t),
+            Ease::QuadInOut => in_out(Ease::QuadIn, t),
+
+            Ease::CubicIn => t * t * t,
+            Ease::CubicOut => 1.0 - Ease::CubicIn.apply(1.0 - t),
+            Ease::CubicInOut => in_out(Ease::CubicIn, t),
+
+            Ease::ElasticIn => {
+                if t == 0.0 || t == 1.0 {
+                    return t;
+                }
+                -(2.0_f64).powf(10.0 * (t - 1.0)) * ((t - 1.075) * (2.0 * PI) / 0.3).sin()
+            },
+            Ease::ElasticOut => 1.0 - Ease::ElasticIn.apply(1.0 - t),
+            Ease::ElasticInOut => in_out(Ease::ElasticIn, t),
+
+            Ease::BackIn => {
+                // Amount of overshoot, chosen to dip about 10% below 0
+                let s = 1.70158;
+                t * t * ((s + 1.0) * t - s)
+            },
+            Ease::BackOut => 1.0 - Ease::BackIn.apply(1.0 - t),
+            Ease::BackInOut => in_out(Ease::BackIn, t),
+
+            Ease::BounceIn => 1.0 - Ease::BounceOut.apply(1.0 - t),
+            Ease::BounceOut => {
+                if t < 1.0 / 2.75 {
+                    7.5625 * t * t
+                } else if t < 2.0 / 2.75 {
+                    let t = t - 1.5 / 2.75;
+                    7.5625 * t * t + 0.75
+                } else if t < 2.5 / 2.75 {
+                    let t = t - 2.25 / 2.75;
+                    7.5625 * t * t + 0.9375
+                } else {
+                    let t = t - 2.625 / 2.75;
+                    7.5625 * t * t + 0.984375
+                }
+            },
+            Ease::BounceInOut => in_out(Ease::BounceIn, t),
+        }
+    }
+}
+
+/// Builds the in/out variant of an `*In` curve: the first half of the
+/// transition follows `ease_in`, the second half mirrors it.
+fn in_out(ease_in: Ease, t: f64) -> f64 {
+    if t < 0.5 {
+        ease_in.apply(t * 2.0) / 2.0
+    } else {
+        1.0 - ease_in.apply((1.0 - t) * 2.0) / 2.0
+    }
+}
+
+
+/// A value which can be smoothly interpolated between two states.
+pub trait Tweenable: Copy {
+    /// Returns the state a proportion `t` of the way from `from` to `to`.
+    /// `t` may be outside of `[0, 1]` for curves that overshoot.
+    fn lerp(from: Self, to: Self, t: f64) -> Self;
+}
+
+impl Tweenable for f64 {
+    fn lerp(from: f64, to: f64, t: f64) -> f64 {
+        from + (to - from) * t
+    }
+}
+
+impl Tweenable for Rectangle {
+    fn lerp(from: Rectangle, to: Rectangle, t: f64) -> Rectangle {
+        Rectangle {
+            x: f64::lerp(from.x, to.x, t),
+            y: f64::lerp(from.y, to.y, t),
+            // Overshooting curves must not make the size negative.
+            w: f64::lerp(from.w, to.w, t).max(0.0),
+            h: f64::lerp(from.h, to.h, t).max(0.0),
+        }
+    }
+}
+
+impl Tweenable for Color {
+    fn lerp(from: Color, to: Color, t: f64) -> Color {
+        let (r1, g1, b1, a1) = from.rgba();
+        let (r2, g2, b2, a2) = to.rgba();
+
+        let channel = |c1: u8, c2: u8| {
+            f64::lerp(c1 as f64, c2 as f64, t).round().max(0.0).min(255.0) as u8
+        };
+
+        Color::RGBA(channel(r1, r2), channel(g1, g2), channel(b1, b2), channel(a1, a2))
+    }
+}
+
+
+/// How many times an animation plays.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Repeat {
+    /// Play once, then `n` more times.
+    Times(u32),
+    /// Play until removed.
+    Forever,
+}
+
+/// Anything which describes how a value changes over time.
+pub trait Animation<T> {
+    /// The value of the animation `time` seconds after it started.
+    fn value_at(&self, time: f64) -> T;
+
+    /// The total time the animation takes, in seconds, or `None` if it never
+    /// ends.
+    fn duration(&self) -> Option<f64>;
+}
+
+
+/// A transition between two values, with an optional delay before it starts,
+/// which may be repeated and, if so, played back and forth.
+///
+/// ```
+/// let grow = Tween::new(32.0, 38.0, 0.2).ease(Ease::BackOut);
+/// let pulse = Tween::new(1.0, 1.2, 0.5).repeat(Repeat::Forever).yoyo();
+/// ```
+#[derive(Clone, Debug)]
+pub struct Tween<T: Tweenable> {
+    from: T,
+    to: T,
+    /// Length of a single play, in seconds
+    length: f64,
+    delay: f64,
+    ease: Ease,
+    repeat: Repeat,
+    yoyo: bool,
+}
+
+impl<T: Tweenable> Tween<T> {
+    /// Creates a linear transition from `from` to `to` lasting `length` seconds.
+    pub fn new(from: T, to: T, length: f64) -> Tween<T> {
+        Tween {
+            from: from,
+            to: to,
+            length: length.max(0.0),
+            delay: 0.0,
+            ease: Ease::Linear,
+            repeat: Repeat::Times(0),
+            yoyo: false,
+        }
+    }
+
+    /// Creates a tween which holds `value` for `length` seconds. Mostly
+    /// useful as a pause in a `Sequence`.
+    pub fn hold(value: T, length: f64) -> Tween<T> {
+        Tween::new(value, value, length)
+    }
+
+    /// Sets the easing curve used for every play.
+    pub fn ease(self, ease: Ease) -> Tween<T> {
+        Tween { ease: ease, ..self }
+    }
+
+    /// Waits `delay` seconds, holding the initial value, before starting.
+    pub fn delay(self, delay: f64) -> Tween<T> {
+        Tween { delay: delay.max(0.0), ..self }
+    }
+
+    /// Sets how many times the tween plays.
+    pub fn repeat(self, repeat: Repeat) -> Tween<T> {
+        Tween { repeat: repeat, ..self }
+    }
+
+    /// Makes every other repetition play backwards.
+    pub fn yoyo(self) -> Tween<T> {
+        Tween { yoyo: true, ..self }
+    }
+
+    /// The value of the tween at the given proportion of a single play.
+    fn at_progress(&self, play: u64, progress: f64) -> T {
+        let backwards = self.yoyo && play % 2 == 1;
+        let progress = if backwards { 1.0 - progress } else { progress };
+
+        T::lerp(self.from, self.to, self.ease.apply(progress))
+    }
+}
+
+impl<T: Tweenable> Animation<T> for Tween<T> {
+    fn value_at(&self, time: f64) -> T {
+        let active = time - self.delay;
+
+        if active <= 0.0 {
+            return self.from;
+        }
+
+        if let Some(duration) = self.duration() {
+            if time >= duration {
+                let plays = match self.repeat {
+                    Repeat::Times(n) => n as u64 + 1,
+                    Repeat::Forever => unreachable!(),
+                };
+                return self.at_progress(plays - 1, 1.0);
+            }
+        }
+
+        if self.length == 0.0 {
+            return self.to;
+        }
+
+        let play = (active / self.length).floor();
+        let progress = (active - play * self.length) / self.length;
+        self.at_progress(play as u64, progress)
+    }
+
+    fn duration(&self) -> Option<f64> {
+        match self.repeat {
+            Repeat::Times(n) => Some(self.delay + self.length * (n as f64 + 1.0)),
+            Repeat::Forever => None,
+        }
+    }
+}
+
+
+/// A series of animations played one after the other.
+///
+/// ```
+/// let blink = Sequence::new()
+///     .then(Tween::new(0.0, 255.0, 0.1))
+///     .then(Tween::hold(255.0, 0.3))
+///     .then(Tween::new(255.0, 0.0, 0.1))
+///     .repeat(Repeat::Times(2));
+/// ```
+pub struct Sequence<T: Tweenable> {
+    steps: Vec<Box<Animation<T>>>,
+    repeat: Repeat,
+}
+
+impl<T: Tweenable + 'static> Sequence<T> {
+    pub fn new() -> Sequence<T> {
+        Sequence {
+            steps: Vec::new(),
+            repeat: Repeat::Times(0),
+        }
+    }
+
+    /// Appends an animation to the sequence. Any animation added after one
+    /// which never ends will never be played.
+    pub fn then<A>(mut self, step: A) -> Sequence<T>
+    where A: Animation<T> + 'static {
+        self.steps.push(Box::new(step));
+        self
+    }
+
+    /// Holds the value reached by the previous step for `length` seconds.
+    /// Does nothing if the sequence is still empty, as there is no value to
+    /// hold.
+    pub fn wait(self, length: f64) -> Sequence<T> {
+        let value = match self.steps.last() {
+            Some(last) => last.value_at(last.duration().unwrap_or(0.0)),
+            None => return self,
+        };
+
+        self.then(Tween::hold(value, length))
+    }
+
+    /// Sets how many times the whole sequence plays.
+    pub fn repeat(self, repeat: Repeat) -> Sequence<T> {
+        Sequence { repeat: repeat, ..self }
+    }
+
+    /// The duration of a single play of the sequence.
+    fn length(&self) -> Option<f64> {
+        let mut total = 0.0;
+        for step in &self.steps {
+            total += match step.duration() {
+                Some(duration) => duration,
+                None => return None,
+            };
+        }
+        Some(total)
+    }
+}
+
+impl<T: Tweenable + 'static> Animation<T> for Sequence<T> {
+    fn value_at(&self, time: f64) -> T {
+        assert!(!self.steps.is_empty(), "Tried to play an empty Sequence");
+
+        let mut time = time.max(0.0);
+
+        if let Some(length) = self.length() {
+            if let Some(duration) = self.duration() {
+                if time >= duration {
+                    let last = &self.steps[self.steps.len() - 1];
+                    return last.value_at(last.duration().unwrap_or(0.0));
+                }
+            }
+
+            // Wrap time around for repeated plays
+            if length > 0.0 {
+                time %= length;
+            }
+        }
+
+        for step in &self.steps {
+            match step.duration() {
+                Some(duration) if time >= duration => time -= duration,
+                _ => return step.value_at(time),
+            }
+        }
+
+        // Only reachable through rounding errors at the very end.
+        let last = &self.steps[self.steps.len() - 1];
+        last.value_at(last.duration().unwrap_or(0.0))
+    }
+
+    fn duration(&self) -> Option<f64> {
+        match (self.length(), self.repeat) {
+            (Some(length), Repeat::Times(n)) => Some(length * (n as f64 + 1.0)),
+            _ => None,
+        }
+    }
+}
+
+
+/// A value which can be driven by a `TweenManager`. Clones share the same
+/// underlying value, so the owner of a property can read what the manager
+/// writes.
+#[derive(Clone)]
+pub struct Property<T: Copy> {
+    value: Rc<Cell<T>>,
+    /// Incremented whenever a new animation takes control of the property,
+    /// so that the previous one knows to stop.
+    generation: Rc<Cell<u64>>,
+}
+
+impl<T: Copy> Property<T> {
+    pub fn new(value: T) -> Property<T> {
+        Property {
+            value: Rc::new(Cell::new(value)),
+            generation: Rc::new(Cell::new(0)),
+        }
+    }
+
+    pub fn get(&self) -> T {
+        self.value.get()
+    }
+
+    /// Sets the value directly, stopping any animation driving it.
+    pub fn set(&self, value: T) {
+        self.generation.set(self.generation.get() + 1);
+        self.value.set(value);
+    }
+}
+
+
+/// An animation bound to the property it drives.
+trait Track {
+    /// Advances the track by `dt` seconds. Returns whether it should be kept.
+    fn update(&mut self, dt: f64) -> bool;
+}
+
+struct PropertyTrack<T: Tweenable> {
+    property: Property<T>,
+    generation: u64,
+    animation: Box<Animation<T>>,
+    time: f64,
+}
+
+impl<T: Tweenable> Track for PropertyTrack<T> {
+    fn update(&mut self, dt: f64) -> bool {
+        // Another animation took over, or nobody is reading the value anymore.
+        if self.property.generation.get() != self.generation ||
+            Rc::strong_count(&self.property.value) == 1 {
+            return false;
+        }
+
+        self.time += dt;
+        self.property.value.set(self.animation.value_at(self.time));
+
+        match self.animation.duration() {
+            Some(duration) => self.time < duration,
+            None => true,
+        }
+    }
+}
+
+
+/// Plays animations on properties, advancing all of them each frame.
+///
+/// ```
+/// let size = Property::new(32.0);
+/// tweens.animate(&size, Tween::new(size.get(), 38.0, 0.15).ease(Ease::BackOut));
+///
+/// // Every frame
+/// tweens.update(elapsed);
+/// draw_label(size.get());
+/// ```
+pub struct TweenManager {
+    tracks: Vec<Box<Track>>,
+}
+
+impl TweenManager {
+    pub fn new() -> TweenManager {
+        TweenManager {
+            tracks: Vec::new(),
+        }
+    }
+
+    /// Starts driving `property` with `animation`, replacing whichever
+    /// animation was driving it before. The property is set to the starting
+    /// value of the animation immediately.
+    pub fn animate<T, A>(&mut self, property: &Property<T>, animation: A)
+    where T: Tweenable + 'static, A: Animation<T> + 'static {
+        property.set(animation.value_at(0.0));
+
+        self.tracks.push(Box::new(PropertyTrack {
+            property: property.clone(),
+            generation: property.generation.get(),
+            animation: Box::new(animation),
+            time: 0.0,
+        }));
+    }
+
+    /// Advances every animation by `elapsed` seconds, and forgets about the
+    /// ones which are over.
+    pub fn update(&mut self, elapsed: f64) {
+        let mut i = 0;
+        while i < self.tracks.len() {
+            if self.tracks[i].update(elapsed) {
+                i += 1;
+            } else {
+                self.tracks.swap_remove(i);
+            }
+        }
+    }
+
+    /// The number of animations still playing.
+    pub fn active(&self) -> usize {
+        self.tracks.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASES: [Ease; 16] = [
+        Ease::Linear,
+        Ease::QuadIn, Ease::QuadOut, Ease::QuadInOut,
+        Ease::CubicIn, Ease::CubicOut, Ease::CubicInOut,
+        Ease::ElasticIn, Ease::ElasticOut, Ease::ElasticInOut,
+        Ease::BackIn, Ease::BackOut, Ease::BackInOut,
+        Ease::BounceIn, Ease::BounceOut, Ease::BounceInOut,
+    ];
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn eases_start_at_0_and_end_at_1() {
+        for &ease in EASES.iter() {
+            assert!(ease.apply(0.0).abs() < 1e-9, "{:?} starts at {}", ease, ease.apply(0.0));
+            assert!((ease.apply(1.0) - 1.0).abs() < 1e-9, "{:?} ends at {}", ease, ease.apply(1.0));
+
+            // Progress outside of [0, 1] is clamped.
+            assert_eq!(ease.apply(-1.0), ease.apply(0.0));
+            assert_eq!(ease.apply(2.0), ease.apply(1.0));
+        }
+    }
+
+    #[test]
+    fn tween_waits_for_its_delay() {
+        let tween = Tween::new(0.0, 10.0, 1.0).delay(0.5);
+
+        assert_eq!(tween.duration(), Some(1.5));
+        assert_close(tween.value_at(0.25), 0.0);
+        assert_close(tween.value_at(1.0), 5.0);
+        assert_close(tween.value_at(2.0), 10.0);
+    }
+
+    #[test]
+    fn tween_repeats() {
+        let tween = Tween::new(0.0, 10.0, 1.0).repeat(Repeat::Times(1));
+
+        assert_eq!(tween.duration(), Some(2.0));
+        assert_close(tween.value_at(0.25), 2.5);
+        assert_close(tween.value_at(1.25), 2.5);
+        assert_close(tween.value_at(2.0), 10.0);
+        assert_close(tween.value_at(5.0), 10.0);
+    }
+
+    #[test]
+    fn yoyo_plays_every_other_repetition_backwards() {
+        let tween = Tween::new(0.0, 10.0, 1.0).repeat(Repeat::Times(2)).yoyo();
+
+        assert_close(tween.value_at(0.25), 2.5);
+        assert_close(tween.value_at(1.25), 7.5);
+        assert_close(tween.value_at(2.25), 2.5);
+        assert_close(tween.value_at(3.0), 10.0);
+
+        // An even number of plays ends where it started.
+        let tween = Tween::new(0.0, 10.0, 1.0).repeat(Repeat::Times(1)).yoyo();
+        assert_close(tween.value_at(2.0), 0.0);
+
+        let forever = Tween::new(0.0, 10.0, 1.0).repeat(Repeat::Forever).yoyo();
+        assert_eq!(forever.duration(), None);
+        assert_close(forever.value_at(100.25), 2.5);
+        assert_close(forever.value_at(101.25), 7.5);
+    }
+
+    #[test]
+    fn sequence_steps_from_one_entry_to_the_next() {
+        let sequence = Sequence::new()
+            .then(Tween::new(0.0, 10.0, 1.0))
+            .then(Tween::new(10.0, 20.0, 2.0));
+
+        assert_eq!(sequence.duration(), Some(3.0));
+        assert_close(sequence.value_at(0.5), 5.0);
+        assert_close(sequence.value_at(1.0), 10.0);
+        assert_close(sequence.value_at(1.5), 12.5);
+        assert_close(sequence.value_at(3.0), 20.0);
+        assert_close(sequence.value_at(10.0), 20.0);
+    }
+
+    #[test]
+    fn sequence_repeats_and_waits() {
+        let sequence = Sequence::new()
+            .then(Tween::new(0.0, 10.0, 1.0))
+            .wait(1.0)
+            .then(Tween::new(10.0, 0.0, 1.0))
+            .repeat(Repeat::Times(1));
+
+        assert_eq!(sequence.duration(), Some(6.0));
+        assert_close(sequence.value_at(1.5), 10.0);
+        assert_close(sequence.value_at(2.5), 5.0);
+        assert_close(sequence.value_at(3.5), 5.0);
+        assert_close(sequence.value_at(6.0), 0.0);
+    }
+
+    #[test]
+    fn waiting_first_does_nothing() {
+        let sequence = Sequence::new().wait(1.0).then(Tween::new(0.0, 10.0, 1.0));
+
+        assert_eq!(sequence.duration(), Some(1.0));
+        assert_close(sequence.value_at(0.5), 5.0);
+    }
+}
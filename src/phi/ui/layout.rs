@@ -0,0 +1,318 @@
+use ::phi::data::Rectangle;
+use ::phi::ui::{Frame, Input, Response, Widget};
+use ::sdl2::pixels::Color;
+
+/// Space left around the content of a widget, in pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Insets {
+    pub top: f64,
+    pub right: f64,
+    pub bottom: f64,
+    pub left: f64,
+}
+
+impl Insets {
+    pub fn zero() -> Insets {
+        Insets::all(0.0)
+    }
+
+    /// The same space on every side.
+    pub fn all(space: f64) -> Insets {
+        Insets::symmetric(space, space)
+    }
+
+    /// `vertical` space above and below, `horizontal` space on either side.
+    pub fn symmetric(vertical: f64, horizontal: f64) -> Insets {
+        Insets {
+            top: vertical,
+            right: horizontal,
+            bottom: vertical,
+            left: horizontal,
+        }
+    }
+
+    /// Returns `rect` with the insets removed from its sides.
+    pub fn shrink(&self, rect: Rectangle) -> Rectangle {
+        Rectangle {
+            x: rect.x + self.left,
+            y: rect.y + self.top,
+            w: (rect.w - self.left - self.right).max(0.0),
+            h: (rect.h - self.top - self.bottom).max(0.0),
+        }
+    }
+
+    /// Returns a size with the insets added to it.
+    pub fn grow(&self, (w, h): (f64, f64)) -> (f64, f64) {
+        (w + self.left + self.right, h + self.top + self.bottom)
+    }
+}
+
+
+/// The direction in which a `Stack` places its children.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Orientation {
+    Vertical,
+    Horizontal,
+}
+
+/// Where something is placed in space larger than it needs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Align {
+    Start,
+    Center,
+    End,
+    /// Take all of the available space. Behaves like `Center` for content
+    /// which cannot be stretched, such as text.
+    Stretch,
+}
+
+impl Align {
+    /// Returns the position of something `size` long inside of the span
+    /// starting at `start` and `available` long.
+    pub fn place(self, start: f64, available: f64, size: f64) -> f64 {
+        match self {
+            Align::Start => start,
+            Align::Center | Align::Stretch => start + (available - size) / 2.0,
+            Align::End => start + available - size,
+        }
+    }
+}
+
+
+/// Finds which of `children` holds the `focus`-th focusable element, and the
+/// index of that element inside of the child.
+fn route<M>(children: &[Box<Widget<M>>], focus: usize) -> Option<(usize, usize)> {
+    let mut first = 0;
+
+    for (i, child) in children.iter().enumerate() {
+        let count = child.focusables();
+        if focus < first + count {
+            return Some((i, focus - first));
+        }
+        first += count;
+    }
+
+    None
+}
+
+
+/// Places its children one after the other, either vertically or
+/// horizontally.
+pub struct Stack<M> {
+    children: Vec<Box<Widget<M>>>,
+    orientation: Orientation,
+    align: Align,
+    spacing: f64,
+    padding: Insets,
+}
+
+impl<M> Stack<M> {
+    pub fn new(orientation: Orientation) -> Stack<M> {
+        Stack {
+            children: Vec::new(),
+            orientation: orientation,
+            align: Align::Center,
+            spacing: 0.0,
+            padding: Insets::zero(),
+        }
+    }
+
+    pub fn vertical() -> Stack<M> {
+        Stack::new(Orientation::Vertical)
+    }
+
+    pub fn horizontal() -> Stack<M> {
+        Stack::new(Orientation::Horizontal)
+    }
+
+    /// Appends a child after the previous ones.
+    pub fn with<W>(mut self, child: W) -> Stack<M>
+    where W: Widget<M> + 'static {
+        self.children.push(Box::new(child));
+        self
+    }
+
+    /// Sets how children are placed across the stack, e.g. horizontally for a
+    /// vertical stack.
+    pub fn align(self, align: Align) -> Stack<M> {
+        Stack { align: align, ..self }
+    }
+
+    /// Sets the space left between consecutive children.
+    pub fn spacing(self, spacing: f64) -> Stack<M> {
+        Stack { spacing: spacing, ..self }
+    }
+
+    pub fn padding(self, padding: Insets) -> Stack<M> {
+        Stack { padding: padding, ..self }
+    }
+
+    /// Returns `(along, across)` the stack for a size.
+    fn axes(&self, (w, h): (f64, f64)) -> (f64, f64) {
+        match self.orientation {
+            Orientation::Vertical => (h, w),
+            Orientation::Horizontal => (w, h),
+        }
+    }
+}
+
+impl<M> Widget<M> for Stack<M> {
+    fn measure(&mut self, frame: &mut Frame) -> (f64, f64) {
+        let mut along = 0.0;
+        let mut across: f64 = 0.0;
+
+        for child in &mut self.children {
+            let size = child.measure(frame);
+            let (child_along, child_across) = match self.orientation {
+                Orientation::Vertical => (size.1, size.0),
+                Orientation::Horizontal => (size.0, size.1),
+            };
+            along += child_along;
+            across = across.max(child_across);
+        }
+
+        if !self.children.is_empty() {
+            along += self.spacing * (self.children.len() - 1) as f64;
+        }
+
+        let size = match self.orientation {
+            Orientation::Vertical => (across, along),
+            Orientation::Horizontal => (along, across),
+        };
+
+        self.padding.grow(size)
+    }
+
+    fn render(&mut self, frame: &mut Frame, rect: Rectangle, focus: Option<usize>) {
+        let inner = self.padding.shrink(rect);
+        let focused = focus.and_then(|focus| route(&self.children, focus));
+
+        let (_, available_across) = self.axes((inner.w, inner.h));
+        let mut offset = 0.0;
+
+        for i in 0..self.children.len() {
+            let size = self.children[i].measure(frame);
+            let (along, across) = self.axes(size);
+
+            let across = if self.align == Align::Stretch { available_across } else { across };
+            let across_start = match self.orientation {
+                Orientation::Vertical => self.align.place(inner.x, inner.w, across),
+                Orientation::Horizontal => self.align.place(inner.y, inner.h, across),
+            };
+
+            let child_rect = match self.orientation {
+                Orientation::Vertical => Rectangle {
+                    x: across_start,
+                    y: inner.y + offset,
+                    w: across,
+                    h: along,
+                },
+                Orientation::Horizontal => Rectangle {
+                    x: inner.x + offset,
+                    y: across_start,
+                    w: along,
+                    h: across,
+                },
+            };
+
+            let child_focus = match focused {
+                Some((child, local)) if child == i => Some(local),
+                _ => None,
+            };
+
+            self.children[i].render(frame, child_rect, child_focus);
+            offset += along + self.spacing;
+        }
+    }
+
+    fn focusables(&self) -> usize {
+        self.children.iter().map(|child| child.focusables()).sum()
+    }
+
+    fn handle(&mut self, input: Input, focus: usize) -> Response<M> {
+        match route(&self.children, focus) {
+            Some((child, local)) => self.children[child].handle(input, local),
+            None => Response::Ignored,
+        }
+    }
+}
+
+
+/// A bordered box drawn behind a single child.
+pub struct Panel<M> {
+    child: Box<Widget<M>>,
+    /// Space between the border and the child
+    padding: Insets,
+    /// Space around the border
+    margin: Insets,
+    min_width: f64,
+    min_height: f64,
+    /// Overrides the theme's background color
+    background: Option<Color>,
+}
+
+impl<M> Panel<M> {
+    pub fn new<W>(child: W) -> Panel<M>
+    where W: Widget<M> + 'static {
+        Panel {
+            child: Box::new(child),
+            padding: Insets::zero(),
+            margin: Insets::zero(),
+            min_width: 0.0,
+            min_height: 0.0,
+            background: None,
+        }
+    }
+
+    pub fn padding(self, padding: Insets) -> Panel<M> {
+        Panel { padding: padding, ..self }
+    }
+
+    pub fn margin(self, margin: Insets) -> Panel<M> {
+        Panel { margin: margin, ..self }
+    }
+
+    /// Sets the minimum width of the inside of the box, excluding its border.
+    pub fn min_width(self, min_width: f64) -> Panel<M> {
+        Panel { min_width: min_width, ..self }
+    }
+
+    /// Sets the minimum height of the inside of the box, excluding its border.
+    pub fn min_height(self, min_height: f64) -> Panel<M> {
+        Panel { min_height: min_height, ..self }
+    }
+
+    pub fn background(self, color: Color) -> Panel<M> {
+        Panel { background: Some(color), ..self }
+    }
+}
+
+impl<M> Widget<M> for Panel<M> {
+    fn measure(&mut self, frame: &mut Frame) -> (f64, f64) {
+        let border = Insets::all(frame.theme.border_width);
+        let (w, h) = self.padding.grow(self.child.measure(frame));
+
+        self.margin.grow(border.grow((w.max(self.min_width), h.max(self.min_height))))
+    }
+
+    fn render(&mut self, frame: &mut Frame, rect: Rectangle, focus: Option<usize>) {
+        let border = Insets::all(frame.theme.border_width);
+        let outer = self.margin.shrink(rect);
+        let inner = border.shrink(outer);
+
+        let border_color = frame.theme.border;
+        let background = self.background.unwrap_or(frame.theme.background);
+        frame.fill(outer, border_color);
+        frame.fill(inner, background);
+
+        self.child.render(frame, self.padding.shrink(inner), focus);
+    }
+
+    fn focusables(&self) -> usize {
+        self.child.focusables()
+    }
+
+    fn handle(&mut self, input: Input, focus: usize) -> Response<M> {
+        self.child.handle(input, focus)
+    }
+}
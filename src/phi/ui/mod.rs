@@ -0,0 +1,472 @@
+mod layout;
+mod widgets;
+
+pub use self::layout::{Align, Insets, Orientation, Panel, Stack};
+pub use self::widgets::{Button, Choice, Label, List, Slider, Toggle};
+
+use ::phi::{Events, Phi};
+use ::phi::data::Rectangle;
+use ::phi::gfx::{CopySprite, Sprite};
+use ::phi::tween::{Ease, Property, Tween, TweenManager};
+use ::sdl2::pixels::Color;
+
+/// Colors, font and metrics shared by all the widgets of a `Ui`.
+#[derive(Clone, Debug)]
+pub struct Theme {
+    pub font: &'static str,
+    pub font_size: i32,
+    /// Size of the text of the focused widget.
+    pub focus_font_size: i32,
+
+    pub text: Color,
+    pub focus_text: Color,
+    /// Used for panel backgrounds.
+    pub background: Color,
+    pub border: Color,
+    /// Used for the filled parts of sliders, toggles and lists.
+    pub accent: Color,
+    /// Used for the empty parts of sliders, toggles and lists.
+    pub track: Color,
+
+    pub border_width: f64,
+    /// Height of a single line of widgets.
+    pub item_height: f64,
+    /// Horizontal space between a widget's label and its value.
+    pub spacing: f64,
+    /// Width of the bar drawn by sliders.
+    pub slider_width: f64,
+    /// Seconds taken by a widget to grow or shrink when its focus changes.
+    pub transition_time: f64,
+}
+
+impl Theme {
+    /// Creates the default theme, using the font found at `font`.
+    pub fn with_font(font: &'static str) -> Theme {
+        Theme {
+            font: font,
+            font_size: 32,
+            focus_font_size: 38,
+
+            text: Color::RGB(200, 200, 200),
+            focus_text: Color::RGB(255, 255, 255),
+            background: Color::RGB(80, 80, 200),
+            border: Color::RGB(180, 180, 255),
+            accent: Color::RGB(180, 180, 255),
+            track: Color::RGB(40, 40, 120),
+
+            border_width: 3.0,
+            item_height: 50.0,
+            spacing: 20.0,
+            slider_width: 140.0,
+            transition_time: 0.15,
+        }
+    }
+}
+
+
+/// The actions a user can take to interact with widgets, whatever the device.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Input {
+    Up,
+    Down,
+    Left,
+    Right,
+    Activate,
+    Back,
+}
+
+impl Input {
+    /// Collects the inputs triggered during the current frame.
+    pub fn from_events(events: &Events) -> Vec<Input> {
+        let now = &events.now;
+        let pressed = |key: Option<bool>, pad: Option<bool>| {
+            key == Some(true) || pad == Some(true)
+        };
+
+        let mut inputs = Vec::new();
+
+        if pressed(now.key_up, now.pad_up) { inputs.push(Input::Up); }
+        if pressed(now.key_down, now.pad_down) { inputs.push(Input::Down); }
+        if pressed(now.key_left, now.pad_left) { inputs.push(Input::Left); }
+        if pressed(now.key_right, now.pad_right) { inputs.push(Input::Right); }
+        if pressed(now.key_space, now.pad_a) || now.key_return == Some(true) {
+            inputs.push(Input::Activate);
+        }
+        if pressed(now.key_escape, now.pad_b) { inputs.push(Input::Back); }
+
+        inputs
+    }
+}
+
+/// What a widget did with an `Input`.
+pub enum Response<M> {
+    /// The input is not meaningful to the widget, and may be used to move the
+    /// focus instead.
+    Ignored,
+    /// The widget changed its internal state, but has nothing to report.
+    Consumed,
+    /// The widget wants its owner to act on a message.
+    Message(M),
+}
+
+
+/// Everything a widget needs to measure and draw itself during a frame.
+pub struct Frame<'a, 'window: 'a> {
+    pub phi: &'a mut Phi<'window>,
+    pub theme: &'a Theme,
+    pub tweens: &'a mut TweenManager,
+}
+
+impl<'a, 'window> Frame<'a, 'window> {
+    /// Fills `rect` with `color`, ignoring rectangles too thin to be seen,
+    /// e.g. the empty part of a slider or a bar squeezed by its label.
+    pub fn fill(&mut self, rect: Rectangle, color: Color) {
+        if !(rect.w > 0.0 && rect.h > 0.0) {
+            return;
+        }
+
+        if let Some(rect) = rect.to_sdl() {
+            self.phi.renderer.set_draw_color(color);
+            self.phi.renderer.fill_rect(rect);
+        }
+    }
+
+    /// Draws `sprite` at its natural size inside of `rect`, aligned
+    /// horizontally according to `align` and centered vertically.
+    pub fn draw_aligned(&mut self, sprite: &Sprite, rect: Rectangle, align: Align) {
+        let (w, h) = sprite.size();
+
+        self.phi.renderer.copy_sprite(sprite, Rectangle {
+            x: align.place(rect.x, rect.w, w),
+            y: rect.y + (rect.h - h) / 2.0,
+            w: w,
+            h: h,
+        });
+    }
+}
+
+
+/// An element of a user interface. Widgets are generic over the type of
+/// message `M` they report to their owner when the user interacts with them.
+pub trait Widget<M> {
+    /// The size the widget would like to be drawn at.
+    fn measure(&mut self, frame: &mut Frame) -> (f64, f64);
+
+    /// Draws the widget inside of `rect`. If `focus` is `Some(i)`, the `i`-th
+    /// focusable element of the widget has the focus.
+    fn render(&mut self, frame: &mut Frame, rect: Rectangle, focus: Option<usize>);
+
+    /// The number of elements in the widget which can receive the focus.
+    fn focusables(&self) -> usize {
+        0
+    }
+
+    /// Reacts to an input sent to the `focus`-th focusable element.
+    fn handle(&mut self, _input: Input, _focus: usize) -> Response<M> {
+        Response::Ignored
+    }
+}
+
+
+/// A string rendered with the theme's font, which is only rendered again when
+/// its content or appearance changes.
+pub struct Text {
+    content: String,
+    cache: Option<(i32, Color, Sprite)>,
+}
+
+impl Text {
+    pub fn new(content: &str) -> Text {
+        Text {
+            content: content.to_string(),
+            cache: None,
+        }
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    pub fn set(&mut self, content: &str) {
+        if self.content != content {
+            self.content = content.to_string();
+            self.cache = None;
+        }
+    }
+
+    /// Returns the text rendered at the given size and color, or `None` if the
+    /// theme's font could not be used.
+    pub fn sprite(&mut self, frame: &mut Frame, size: i32, color: Color) -> Option<Sprite> {
+        let stale = match self.cache {
+            Some((cached_size, cached_color, _)) =>
+                cached_size != size || cached_color != color,
+            None => true,
+        };
+
+        if stale {
+            self.cache = frame.phi.ttf_str_sprite(&self.content, frame.theme.font, size, color)
+                .map(|sprite| (size, color, sprite));
+        }
+
+        self.cache.as_ref().map(|&(_, _, ref sprite)| sprite.clone())
+    }
+
+    /// Returns the dimensions of the text rendered with the given appearance.
+    /// Measuring with the appearance used to render avoids rendering twice.
+    pub fn size(&mut self, frame: &mut Frame, size: i32, color: Color) -> (f64, f64) {
+        self.sprite(frame, size, color).map_or((0.0, 0.0), |sprite| sprite.size())
+    }
+}
+
+
+/// The animated text size and color of a focusable widget.
+pub struct Highlight {
+    focused: bool,
+    size: Property<f64>,
+    color: Property<Color>,
+}
+
+impl Highlight {
+    pub fn new() -> Highlight {
+        Highlight {
+            focused: false,
+            size: Property::new(0.0),
+            color: Property::new(Color::RGB(0, 0, 0)),
+        }
+    }
+
+    /// Starts transitioning to the appropriate appearance if the focus changed
+    /// since the last frame. Returns the current text size and color.
+    pub fn update(&mut self, frame: &mut Frame, focused: bool) -> (i32, Color) {
+        let theme = frame.theme;
+
+        // Not yet drawn: start in the right state rather than animating.
+        if self.size.get() == 0.0 {
+            self.focused = focused;
+            let (size, color) = Highlight::target(theme, focused);
+            self.size.set(size);
+            self.color.set(color);
+        }
+
+        if self.focused != focused {
+            self.focused = focused;
+            let (size, color) = Highlight::target(theme, focused);
+            let ease = if focused { Ease::BackOut } else { Ease::QuadOut };
+
+            frame.tweens.animate(&self.size,
+                Tween::new(self.size.get(), size, theme.transition_time).ease(ease));
+            frame.tweens.animate(&self.color,
+                Tween::new(self.color.get(), color, theme.transition_time));
+        }
+
+        (self.size.get() as i32, self.color.get())
+    }
+
+    /// Returns the current text size and color, without reacting to changes
+    /// of focus. Used to measure widgets before they are rendered.
+    pub fn current(&self, theme: &Theme) -> (i32, Color) {
+        if self.size.get() == 0.0 {
+            let (size, color) = Highlight::target(theme, self.focused);
+            (size as i32, color)
+        } else {
+            (self.size.get() as i32, self.color.get())
+        }
+    }
+
+    fn target(theme: &Theme, focused: bool) -> (f64, Color) {
+        if focused {
+            (theme.focus_font_size as f64, theme.focus_text)
+        } else {
+            (theme.font_size as f64, theme.text)
+        }
+    }
+}
+
+
+/// The root of a tree of widgets, which keeps track of the focus and of the
+/// animations of its widgets.
+///
+/// ```
+/// let mut ui = Ui::new(Panel::new(Stack::vertical()
+///         .with(Button::new("New Game", MenuAction::NewGame))
+///         .with(Button::new("Quit", MenuAction::Quit)))
+///     .min_width(360.0), Theme::with_font("assets/belligerent.ttf"));
+///
+/// // Every frame
+/// for action in ui.handle(&phi.events) { ... }
+/// ui.render(phi);
+/// ```
+pub struct Ui<M> {
+    root: Box<Widget<M>>,
+    theme: Theme,
+    focus: usize,
+    tweens: TweenManager,
+}
+
+impl<M> Ui<M> {
+    pub fn new<W>(root: W, theme: Theme) -> Ui<M>
+    where W: Widget<M> + 'static {
+        Ui {
+            root: Box::new(root),
+            theme: theme,
+            focus: 0,
+            tweens: TweenManager::new(),
+        }
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    /// The index of the focused element, in the order widgets were declared.
+    pub fn focus(&self) -> usize {
+        self.focus
+    }
+
+    pub fn set_focus(&mut self, focus: usize) {
+        let count = self.root.focusables();
+        self.focus = if count == 0 { 0 } else { focus.min(count - 1) };
+    }
+
+    /// Sends this frame's inputs to the focused widget, moving the focus when
+    /// they are ignored. Returns the messages emitted by the widgets.
+    pub fn handle(&mut self, events: &Events) -> Vec<M> {
+        let mut messages = Vec::new();
+
+        for input in Input::from_events(events) {
+            if let Some(message) = self.handle_input(input) {
+                messages.push(message);
+            }
+        }
+
+        messages
+    }
+
+    /// Sends a single input to the focused widget.
+    pub fn handle_input(&mut self, input: Input) -> Option<M> {
+        let count = self.root.focusables();
+        if count == 0 {
+            return None;
+        }
+
+        match self.root.handle(input, self.focus) {
+            Response::Message(message) => return Some(message),
+            Response::Consumed => return None,
+            Response::Ignored => {},
+        }
+
+        // Wrap around at both ends
+        match input {
+            Input::Up | Input::Left =>
+                self.focus = if self.focus == 0 { count - 1 } else { self.focus - 1 },
+            Input::Down | Input::Right =>
+                self.focus = (self.focus + 1) % count,
+            _ => {},
+        }
+
+        None
+    }
+
+    /// Advances animations by `elapsed` seconds and draws the interface in
+    /// the center of the window.
+    pub fn render(&mut self, phi: &mut Phi, elapsed: f64) {
+        let (win_w, win_h) = phi.output_size();
+        self.render_in(phi, elapsed, Rectangle { x: 0.0, y: 0.0, w: win_w, h: win_h });
+    }
+
+    /// Advances animations by `elapsed` seconds and draws the interface in
+    /// the center of `area`.
+    pub fn render_in(&mut self, phi: &mut Phi, elapsed: f64, area: Rectangle) {
+        self.tweens.update(elapsed);
+
+        let mut frame = Frame {
+            phi: phi,
+            theme: &self.theme,
+            tweens: &mut self.tweens,
+        };
+
+        let (w, h) = self.root.measure(&mut frame);
+        let (w, h) = (w.min(area.w), h.min(area.h));
+        let rect = Rectangle {
+            x: area.x + (area.w - w) / 2.0,
+            y: area.y + (area.h - h) / 2.0,
+            w: w,
+            h: h,
+        };
+
+        let focus = if self.root.focusables() > 0 { Some(self.focus) } else { None };
+        self.root.render(&mut frame, rect, focus);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A widget with the given number of focusable elements, which ignore
+    /// every input.
+    struct Focusables(usize);
+
+    impl Widget<()> for Focusables {
+        fn measure(&mut self, _frame: &mut Frame) -> (f64, f64) {
+            (0.0, 0.0)
+        }
+
+        fn render(&mut self, _frame: &mut Frame, _rect: Rectangle, _focus: Option<usize>) {}
+
+        fn focusables(&self) -> usize {
+            self.0
+        }
+    }
+
+    fn ui_with(focusables: usize) -> Ui<()> {
+        Ui::new(Focusables(focusables), Theme::with_font("font.ttf"))
+    }
+
+    #[test]
+    fn focus_wraps_around_at_both_ends() {
+        let mut ui = ui_with(3);
+
+        let mut moves = Vec::new();
+        for &input in &[Input::Down, Input::Down, Input::Down, Input::Up, Input::Left, Input::Right] {
+            ui.handle_input(input);
+            moves.push(ui.focus());
+        }
+
+        assert_eq!(moves, vec![1, 2, 0, 2, 1, 2]);
+    }
+
+    #[test]
+    fn other_inputs_keep_the_focus() {
+        let mut ui = ui_with(3);
+        ui.set_focus(1);
+
+        ui.handle_input(Input::Activate);
+        ui.handle_input(Input::Back);
+        assert_eq!(ui.focus(), 1);
+    }
+
+    #[test]
+    fn focus_stays_on_existing_elements() {
+        let mut ui = ui_with(3);
+        ui.set_focus(10);
+        assert_eq!(ui.focus(), 2);
+
+        let mut empty = ui_with(0);
+        empty.set_focus(4);
+        assert_eq!(empty.focus(), 0);
+        assert_eq!(empty.handle_input(Input::Down), None);
+        assert_eq!(empty.focus(), 0);
+    }
+
+    #[test]
+    fn widgets_consuming_inputs_keep_the_focus() {
+        let mut ui = Ui::new(Slider::new("Volume", 100.0, 0.0, 100.0, 10.0, Box::new(|value| value)),
+                             Theme::with_font("font.ttf"));
+
+        // Already at the maximum, but the slider still keeps the input.
+        assert_eq!(ui.handle_input(Input::Right), None);
+        assert_eq!(ui.handle_input(Input::Left), Some(90.0));
+        assert_eq!(ui.focus(), 0);
+    }
+}
@@ -0,0 +1,532 @@
+use ::phi::data::Rectangle;
+use ::phi::ui::{Align, Frame, Highlight, Input, Response, Text, Widget};
+use ::sdl2::pixels::Color;
+
+/// Static text.
+pub struct Label {
+    text: Text,
+    align: Align,
+    size: Option<i32>,
+    color: Option<Color>,
+}
+
+impl Label {
+    pub fn new(text: &str) -> Label {
+        Label {
+            text: Text::new(text),
+            align: Align::Center,
+            size: None,
+            color: None,
+        }
+    }
+
+    pub fn align(self, align: Align) -> Label {
+        Label { align: align, ..self }
+    }
+
+    /// Overrides the theme's font size.
+    pub fn size(self, size: i32) -> Label {
+        Label { size: Some(size), ..self }
+    }
+
+    /// Overrides the theme's text color.
+    pub fn color(self, color: Color) -> Label {
+        Label { color: Some(color), ..self }
+    }
+
+    fn appearance(&self, frame: &Frame) -> (i32, Color) {
+        (self.size.unwrap_or(frame.theme.font_size),
+         self.color.unwrap_or(frame.theme.text))
+    }
+}
+
+impl<M> Widget<M> for Label {
+    fn measure(&mut self, frame: &mut Frame) -> (f64, f64) {
+        let (size, color) = self.appearance(frame);
+        let (w, _) = self.text.size(frame, size, color);
+        (w, frame.theme.item_height)
+    }
+
+    fn render(&mut self, frame: &mut Frame, rect: Rectangle, _: Option<usize>) {
+        let (size, color) = self.appearance(frame);
+        if let Some(sprite) = self.text.sprite(frame, size, color) {
+            frame.draw_aligned(&sprite, rect, self.align);
+        }
+    }
+}
+
+
+/// Text which sends a message when activated.
+pub struct Button<M: Clone> {
+    text: Text,
+    highlight: Highlight,
+    message: M,
+}
+
+impl<M: Clone> Button<M> {
+    pub fn new(text: &str, message: M) -> Button<M> {
+        Button {
+            text: Text::new(text),
+            highlight: Highlight::new(),
+            message: message,
+        }
+    }
+}
+
+impl<M: Clone> Widget<M> for Button<M> {
+    fn measure(&mut self, frame: &mut Frame) -> (f64, f64) {
+        let (size, color) = self.highlight.current(frame.theme);
+        let (w, _) = self.text.size(frame, size, color);
+        (w, frame.theme.item_height)
+    }
+
+    fn render(&mut self, frame: &mut Frame, rect: Rectangle, focus: Option<usize>) {
+        let (size, color) = self.highlight.update(frame, focus.is_some());
+        if let Some(sprite) = self.text.sprite(frame, size, color) {
+            frame.draw_aligned(&sprite, rect, Align::Center);
+        }
+    }
+
+    fn focusables(&self) -> usize {
+        1
+    }
+
+    fn handle(&mut self, input: Input, _: usize) -> Response<M> {
+        match input {
+            Input::Activate => Response::Message(self.message.clone()),
+            _ => Response::Ignored,
+        }
+    }
+}
+
+
+/// Renders a widget's label on the left of `rect` and returns the space left
+/// on its right for the widget's value.
+fn render_label(frame: &mut Frame, text: &mut Text, highlight: &mut Highlight,
+                rect: Rectangle, focused: bool) -> Rectangle {
+    let (size, color) = highlight.update(frame, focused);
+    let mut label_w = 0.0;
+
+    if let Some(sprite) = text.sprite(frame, size, color) {
+        label_w = sprite.size().0;
+        frame.draw_aligned(&sprite, rect, Align::Start);
+    }
+
+    let value_x = rect.x + label_w + frame.theme.spacing;
+    Rectangle {
+        x: value_x,
+        y: rect.y,
+        w: (rect.x + rect.w - value_x).max(0.0),
+        h: rect.h,
+    }
+}
+
+/// Measures a labelled widget whose value takes `value_w` pixels.
+fn measure_labelled(frame: &mut Frame, text: &mut Text, highlight: &Highlight,
+                    value_w: f64) -> (f64, f64) {
+    let (size, color) = highlight.current(frame.theme);
+    let (label_w, _) = text.size(frame, size, color);
+    (label_w + frame.theme.spacing + value_w, frame.theme.item_height)
+}
+
+
+/// A labelled on/off switch.
+pub struct Toggle<M> {
+    text: Text,
+    highlight: Highlight,
+    value: bool,
+    on_change: Box<Fn(bool) -> M>,
+}
+
+impl<M> Toggle<M> {
+    /// Creates a toggle which calls `on_change` with its new value to create
+    /// the message sent when it is switched.
+    pub fn new(text: &str, value: bool, on_change: Box<Fn(bool) -> M>) -> Toggle<M> {
+        Toggle {
+            text: Text::new(text),
+            highlight: Highlight::new(),
+            value: value,
+            on_change: on_change,
+        }
+    }
+
+    pub fn value(&self) -> bool {
+        self.value
+    }
+}
+
+impl<M> Widget<M> for Toggle<M> {
+    fn measure(&mut self, frame: &mut Frame) -> (f64, f64) {
+        let box_side = frame.theme.item_height / 2.0;
+        measure_labelled(frame, &mut self.text, &self.highlight, box_side)
+    }
+
+    fn render(&mut self, frame: &mut Frame, rect: Rectangle, focus: Option<usize>) {
+        let value_rect = render_label(frame, &mut self.text, &mut self.highlight,
+                                      rect, focus.is_some());
+
+        let side = frame.theme.item_height / 2.0;
+        let border = frame.theme.border_width;
+        let check = Rectangle {
+            x: value_rect.x + value_rect.w - side,
+            y: value_rect.y + (value_rect.h - side) / 2.0,
+            w: side,
+            h: side,
+        };
+
+        let (border_color, track, accent) = (frame.theme.border, frame.theme.track, frame.theme.accent);
+        frame.fill(check, border_color);
+        frame.fill(Rectangle {
+            x: check.x + border,
+            y: check.y + border,
+            w: check.w - border * 2.0,
+            h: check.h - border * 2.0,
+        }, if self.value { accent } else { track });
+    }
+
+    fn focusables(&self) -> usize {
+        1
+    }
+
+    fn handle(&mut self, input: Input, _: usize) -> Response<M> {
+        match input {
+            Input::Activate | Input::Left | Input::Right => {
+                self.value = !self.value;
+                Response::Message((self.on_change)(self.value))
+            },
+            _ => Response::Ignored,
+        }
+    }
+}
+
+
+/// A labelled number picked in a range, adjusted with left and right.
+pub struct Slider<M> {
+    text: Text,
+    highlight: Highlight,
+    value: f64,
+    min: f64,
+    max: f64,
+    step: f64,
+    on_change: Box<Fn(f64) -> M>,
+}
+
+impl<M> Slider<M> {
+    /// Creates a slider going from `min` to `max` by increments of `step`,
+    /// which calls `on_change` with its new value to create the message sent
+    /// when it is moved.
+    pub fn new(text: &str, value: f64, min: f64, max: f64, step: f64,
+               on_change: Box<Fn(f64) -> M>) -> Slider<M> {
+        Slider {
+            text: Text::new(text),
+            highlight: Highlight::new(),
+            value: value.max(min).min(max),
+            min: min,
+            max: max,
+            step: step,
+            on_change: on_change,
+        }
+    }
+
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+}
+
+impl<M> Widget<M> for Slider<M> {
+    fn measure(&mut self, frame: &mut Frame) -> (f64, f64) {
+        let slider_w = frame.theme.slider_width;
+        measure_labelled(frame, &mut self.text, &self.highlight, slider_w)
+    }
+
+    fn render(&mut self, frame: &mut Frame, rect: Rectangle, focus: Option<usize>) {
+        let value_rect = render_label(frame, &mut self.text, &mut self.highlight,
+                                      rect, focus.is_some());
+
+        let w = frame.theme.slider_width.min(value_rect.w);
+        let h = frame.theme.item_height / 4.0;
+        let bar = Rectangle {
+            x: value_rect.x + value_rect.w - w,
+            y: value_rect.y + (value_rect.h - h) / 2.0,
+            w: w,
+            h: h,
+        };
+
+        let range = self.max - self.min;
+        let ratio = if range > 0.0 { (self.value - self.min) / range } else { 0.0 };
+
+        let (track, accent) = (frame.theme.track, frame.theme.accent);
+        frame.fill(bar, track);
+        frame.fill(Rectangle { w: bar.w * ratio, ..bar }, accent);
+    }
+
+    fn focusables(&self) -> usize {
+        1
+    }
+
+    fn handle(&mut self, input: Input, _: usize) -> Response<M> {
+        let value = match input {
+            Input::Left => (self.value - self.step).max(self.min),
+            Input::Right => (self.value + self.step).min(self.max),
+            _ => return Response::Ignored,
+        };
+
+        if value == self.value {
+            return Response::Consumed;
+        }
+
+        self.value = value;
+        Response::Message((self.on_change)(value))
+    }
+}
+
+
+/// A labelled value picked among several options, cycled through with left
+/// and right.
+pub struct Choice<M> {
+    text: Text,
+    highlight: Highlight,
+    options: Vec<Text>,
+    selected: usize,
+    on_change: Box<Fn(usize) -> M>,
+}
+
+impl<M> Choice<M> {
+    /// Creates a choice which calls `on_change` with the index of the newly
+    /// selected option to create the message sent when it changes.
+    pub fn new(text: &str, options: &[String], selected: usize,
+               on_change: Box<Fn(usize) -> M>) -> Choice<M> {
+        assert!(!options.is_empty(), "Choice created without any option");
+
+        Choice {
+            text: Text::new(text),
+            highlight: Highlight::new(),
+            options: options.iter().map(|option| Text::new(&format!("< {} >", option))).collect(),
+            selected: selected.min(options.len() - 1),
+            on_change: on_change,
+        }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+}
+
+impl<M> Widget<M> for Choice<M> {
+    fn measure(&mut self, frame: &mut Frame) -> (f64, f64) {
+        let (size, color) = self.highlight.current(frame.theme);
+        let value_w = self.options[self.selected].size(frame, size, color).0;
+        measure_labelled(frame, &mut self.text, &self.highlight, value_w)
+    }
+
+    fn render(&mut self, frame: &mut Frame, rect: Rectangle, focus: Option<usize>) {
+        let value_rect = render_label(frame, &mut self.text, &mut self.highlight,
+                                      rect, focus.is_some());
+
+        let (size, color) = self.highlight.current(frame.theme);
+        if let Some(sprite) = self.options[self.selected].sprite(frame, size, color) {
+            frame.draw_aligned(&sprite, value_rect, Align::End);
+        }
+    }
+
+    fn focusables(&self) -> usize {
+        1
+    }
+
+    fn handle(&mut self, input: Input, _: usize) -> Response<M> {
+        let count = self.options.len();
+
+        self.selected = match input {
+            Input::Left => (self.selected + count - 1) % count,
+            Input::Right | Input::Activate => (self.selected + 1) % count,
+            _ => return Response::Ignored,
+        };
+
+        Response::Message((self.on_change)(self.selected))
+    }
+}
+
+
+/// A scrolling list of items. Up and down move the selection inside of the
+/// list, and only move the focus away from it at either end.
+pub struct List<M> {
+    items: Vec<Text>,
+    selected: usize,
+    /// Index of the first visible item
+    scroll: usize,
+    /// Number of items shown at once
+    rows: usize,
+    focused: bool,
+    on_activate: Box<Fn(usize) -> M>,
+}
+
+impl<M> List<M> {
+    /// Creates a list showing `rows` items at a time, which calls
+    /// `on_activate` with the index of the selected item to create the
+    /// message sent when it is activated.
+    pub fn new(items: &[String], rows: usize, on_activate: Box<Fn(usize) -> M>) -> List<M> {
+        List {
+            items: items.iter().map(|item| Text::new(item)).collect(),
+            selected: 0,
+            scroll: 0,
+            rows: rows.max(1),
+            focused: false,
+            on_activate: on_activate,
+        }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Scrolls so that the selected item is visible.
+    fn follow_selection(&mut self) {
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + self.rows {
+            self.scroll = self.selected + 1 - self.rows;
+        }
+    }
+}
+
+impl<M> Widget<M> for List<M> {
+    fn measure(&mut self, frame: &mut Frame) -> (f64, f64) {
+        let (size, color) = (frame.theme.font_size, frame.theme.text);
+        let mut w: f64 = 0.0;
+
+        for item in &mut self.items {
+            w = w.max(item.size(frame, size, color).0);
+        }
+
+        let rows = self.rows.min(self.items.len());
+        (w + frame.theme.spacing * 2.0, frame.theme.item_height * rows as f64)
+    }
+
+    fn render(&mut self, frame: &mut Frame, rect: Rectangle, focus: Option<usize>) {
+        self.focused = focus.is_some();
+
+        let item_h = frame.theme.item_height;
+        let end = (self.scroll + self.rows).min(self.items.len());
+
+        for i in self.scroll..end {
+            let row = Rectangle {
+                x: rect.x,
+                y: rect.y + item_h * (i - self.scroll) as f64,
+                w: rect.w,
+                h: item_h,
+            };
+
+            let color = if i == self.selected {
+                let highlight = if self.focused { frame.theme.accent } else { frame.theme.track };
+                frame.fill(row, highlight);
+                frame.theme.focus_text
+            } else {
+                frame.theme.text
+            };
+
+            let (size, spacing) = (frame.theme.font_size, frame.theme.spacing);
+            if let Some(sprite) = self.items[i].sprite(frame, size, color) {
+                frame.draw_aligned(&sprite, Rectangle {
+                    x: row.x + spacing,
+                    w: row.w - spacing * 2.0,
+                    ..row
+                }, Align::Start);
+            }
+        }
+    }
+
+    fn focusables(&self) -> usize {
+        if self.items.is_empty() { 0 } else { 1 }
+    }
+
+    fn handle(&mut self, input: Input, _: usize) -> Response<M> {
+        match input {
+            Input::Up if self.selected > 0 => {
+                self.selected -= 1;
+            },
+            Input::Down if self.selected + 1 < self.items.len() => {
+                self.selected += 1;
+            },
+            Input::Activate => return Response::Message((self.on_activate)(self.selected)),
+            _ => return Response::Ignored,
+        }
+
+        self.follow_selection();
+        Response::Consumed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The message of a response, and whether the widget used the input.
+    fn outcome<M>(response: Response<M>) -> (Option<M>, bool) {
+        match response {
+            Response::Ignored => (None, false),
+            Response::Consumed => (None, true),
+            Response::Message(message) => (Some(message), true),
+        }
+    }
+
+    fn slider(value: f64) -> Slider<f64> {
+        Slider::new("Volume", value, 0.0, 100.0, 30.0, Box::new(|value| value))
+    }
+
+    #[test]
+    fn slider_starts_within_its_range() {
+        assert_eq!(slider(150.0).value(), 100.0);
+        assert_eq!(slider(-5.0).value(), 0.0);
+        assert_eq!(slider(45.0).value(), 45.0);
+    }
+
+    #[test]
+    fn slider_stops_at_its_ends() {
+        let mut slider = slider(20.0);
+
+        assert_eq!(outcome(slider.handle(Input::Left, 0)), (Some(0.0), true));
+        assert_eq!(outcome(slider.handle(Input::Left, 0)), (None, true));
+        assert_eq!(slider.value(), 0.0);
+
+        for &expected in &[30.0, 60.0, 90.0, 100.0] {
+            assert_eq!(outcome(slider.handle(Input::Right, 0)), (Some(expected), true));
+        }
+        assert_eq!(outcome(slider.handle(Input::Right, 0)), (None, true));
+        assert_eq!(slider.value(), 100.0);
+
+        assert_eq!(outcome(slider.handle(Input::Down, 0)), (None, false));
+    }
+
+    fn list(count: usize, rows: usize) -> List<usize> {
+        let items: Vec<String> = (0..count).map(|i| format!("Item {}", i)).collect();
+        List::new(&items, rows, Box::new(|index| index))
+    }
+
+    #[test]
+    fn list_scrolls_to_follow_the_selection() {
+        let mut list = list(5, 2);
+
+        let mut moves = Vec::new();
+        for &input in &[Input::Down, Input::Down, Input::Down, Input::Down, Input::Up, Input::Up] {
+            outcome(list.handle(input, 0));
+            moves.push((list.selected(), list.scroll));
+        }
+
+        assert_eq!(moves, vec![(1, 0), (2, 1), (3, 2), (4, 3), (3, 3), (2, 2)]);
+        assert_eq!(outcome(list.handle(Input::Activate, 0)), (Some(2), true));
+    }
+
+    #[test]
+    fn list_lets_the_focus_go_at_both_ends() {
+        assert_eq!(list(0, 5).focusables(), 0);
+
+        let mut list = list(3, 5);
+
+        assert_eq!(outcome(list.handle(Input::Up, 0)), (None, false));
+        assert_eq!(outcome(list.handle(Input::Down, 0)), (None, true));
+        assert_eq!(outcome(list.handle(Input::Down, 0)), (None, true));
+        assert_eq!(outcome(list.handle(Input::Down, 0)), (None, false));
+        assert_eq!((list.selected(), list.scroll), (2, 0));
+        assert_eq!(list.focusables(), 1);
+    }
+}
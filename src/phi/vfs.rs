@@ -0,0 +1,416 @@
+//! A virtual file system, so that assets are found wherever the game is
+//! launched from, and may be shipped inside of it.
+//!
+//! Sources of files are mounted at a path, such as `assets`: a directory,
+//! a tar archive, or files embedded in the binary. A file such as
+//! `assets/spaceship.png` is then read from the last source mounted at a
+//! path it is in, which has it. Files which no source has are read as
+//! usual, relative to the current directory.
+//!
+//! ```
+//! vfs::mount("assets", Embedded::new(EMBEDDED_ASSETS));
+//! vfs::mount("assets", Directory::new(exe_dir.join("assets")));
+//! let data = try!(vfs::read("assets/spaceship.png"));
+//! ```
+
+use ::std::cell::RefCell;
+use ::std::collections::HashMap;
+use ::std::fs::{self, File};
+use ::std::io::{self, Read};
+use ::std::path::{Component, Path, PathBuf};
+
+/// The size of the blocks of a tar archive.
+const TAR_BLOCK: usize = 512;
+
+thread_local!(static MOUNTS: RefCell<Vec<(PathBuf, Box<Mount>)>> = RefCell::new(Vec::new()));
+thread_local!(static KEPT: RefCell<HashMap<PathBuf, &'static [u8]>> = RefCell::new(HashMap::new()));
+
+/// A source of files, given paths relative to where it is mounted.
+pub trait Mount {
+    /// Reads the file at `path`, or returns `None` if there is none.
+    fn read(&self, path: &Path) -> Option<io::Result<Vec<u8>>>;
+
+    /// Whether there is a file at `path`, without reading it.
+    fn contains(&self, path: &Path) -> bool;
+
+    /// The names of the files in the directory at `path`.
+    fn list(&self, dir: &Path) -> Vec<String>;
+}
+
+/// Files embedded in the binary with `include_bytes!`, by path.
+#[cfg(feature = "embed-assets")]
+pub struct Embedded {
+    files: &'static [(&'static str, &'static [u8])],
+}
+
+#[cfg(feature = "embed-assets")]
+impl Embedded {
+    pub fn new(files: &'static [(&'static str, &'static [u8])]) -> Embedded {
+        Embedded { files: files }
+    }
+}
+
+#[cfg(feature = "embed-assets")]
+impl Mount for Embedded {
+    fn read(&self, path: &Path) -> Option<io::Result<Vec<u8>>> {
+        let key = key(path);
+        self.files.iter()
+            .find(|&&(name, _)| name == key)
+            .map(|&(_, data)| Ok(data.to_vec()))
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        let key = key(path);
+        self.files.iter().any(|&(name, _)| name == key)
+    }
+
+    fn list(&self, dir: &Path) -> Vec<String> {
+        names_in(self.files.iter().map(|&(name, _)| name), dir)
+    }
+}
+
+/// The files of a directory.
+pub struct Directory {
+    root: PathBuf,
+}
+
+impl Directory {
+    pub fn new<P: AsRef<Path>>(root: P) -> Directory {
+        Directory { root: root.as_ref().to_path_buf() }
+    }
+}
+
+impl Mount for Directory {
+    fn read(&self, path: &Path) -> Option<io::Result<Vec<u8>>> {
+        match File::open(self.root.join(path)) {
+            Ok(mut file) => {
+                let mut data = Vec::new();
+                Some(file.read_to_end(&mut data).map(|_| data))
+            },
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        self.root.join(path).is_file()
+    }
+
+    fn list(&self, dir: &Path) -> Vec<String> {
+        list_dir(&self.root.join(dir))
+    }
+}
+
+/// The files of a tar archive, read into memory when it is opened.
+pub struct Archive {
+    data: Vec<u8>,
+    /// Where the contents of every file start in `data`, and their size
+    files: HashMap<String, (usize, usize)>,
+}
+
+impl Archive {
+    /// Opens the archive at `path`, as made by e.g. `tar -cf assets.tar -C
+    /// assets .`. Only its regular files are kept.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Archive> {
+        let mut data = Vec::new();
+        try!(try!(File::open(path)).read_to_end(&mut data));
+        Archive::parse(data)
+    }
+
+    /// Finds the files of the archive read into `data`.
+    fn parse(data: Vec<u8>) -> io::Result<Archive> {
+        let mut files = HashMap::new();
+        let mut offset = 0;
+
+        while offset + TAR_BLOCK <= data.len() {
+            let header = &data[offset..offset + TAR_BLOCK];
+
+            // The archive ends with empty blocks
+            if header.iter().all(|&byte| byte == 0) {
+                break;
+            }
+
+            // Long names are split in a prefix by POSIX archives, which GNU
+            // ones use for other things
+            let mut name = tar_field(&header[0..100]);
+            if &header[257..263] == b"ustar\0" {
+                let prefix = tar_field(&header[345..500]);
+                if !prefix.is_empty() {
+                    name = format!("{}/{}", prefix, name);
+                }
+            }
+
+            let size = try!(u64::from_str_radix(tar_field(&header[124..136]).trim(), 8)
+                .map_err(|_| invalid_data(&format!("invalid size for `{}`", name)))) as usize;
+            let start = offset + TAR_BLOCK;
+            if start + size > data.len() {
+                return Err(invalid_data(&format!("`{}` is cut short", name)));
+            }
+
+            // Regular files only, not directories or links
+            if header[156] == b'0' || header[156] == 0 {
+                files.insert(key(Path::new(&name)), (start, size));
+            }
+
+            offset = start + (size + TAR_BLOCK - 1) / TAR_BLOCK * TAR_BLOCK;
+        }
+
+        Ok(Archive {
+            data: data,
+            files: files,
+        })
+    }
+}
+
+impl Mount for Archive {
+    fn read(&self, path: &Path) -> Option<io::Result<Vec<u8>>> {
+        self.files.get(&key(path))
+            .map(|&(start, size)| Ok(self.data[start..start + size].to_vec()))
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        self.files.contains_key(&key(path))
+    }
+
+    fn list(&self, dir: &Path) -> Vec<String> {
+        names_in(self.files.keys().map(|name| &name[..]), dir)
+    }
+}
+
+/// A text field of a tar header, which ends at the first null byte.
+fn tar_field(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&byte| byte == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// A path as the names of its components joined by `/`, leaving out `.`.
+fn key(path: &Path) -> String {
+    let names: Vec<String> = path.components()
+        .filter(|component| *component != Component::CurDir)
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    names.join("/")
+}
+
+/// The names of the files directly in `dir`, among those at `paths`.
+fn names_in<'a, I: Iterator<Item=&'a str>>(paths: I, dir: &Path) -> Vec<String> {
+    let dir = key(dir);
+    paths
+        .filter_map(|path| {
+            if dir.is_empty() {
+                Some(path)
+            } else if path.starts_with(&dir[..]) && path[dir.len()..].starts_with('/') {
+                Some(&path[dir.len() + 1..])
+            } else {
+                None
+            }
+        })
+        .filter(|name| !name.contains('/'))
+        .map(|name| name.to_string())
+        .collect()
+}
+
+fn list_dir(dir: &Path) -> Vec<String> {
+    match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().map_or(false, |kind| kind.is_file()))
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Makes the files of `mount` available under `point`, before those of the
+/// sources mounted earlier.
+pub fn mount<M: Mount + 'static>(point: &str, mount: M) {
+    MOUNTS.with(|mounts| mounts.borrow_mut().push((PathBuf::from(point), Box::new(mount))));
+}
+
+/// Reads the whole file at `path`.
+pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>> {
+    let path = path.as_ref();
+
+    let found = MOUNTS.with(|mounts| {
+        mounts.borrow().iter().rev()
+            .filter_map(|&(ref point, ref mount)| path.strip_prefix(point).ok().map(|rest| (mount, rest)))
+            .filter_map(|(mount, rest)| mount.read(rest))
+            .next()
+    });
+
+    match found {
+        Some(result) => result,
+        None => {
+            let mut data = Vec::new();
+            try!(try!(File::open(path)).read_to_end(&mut data));
+            Ok(data)
+        },
+    }
+}
+
+/// Like `read`, for files which must stay in memory until the game quits,
+/// such as fonts, which SDL reads from as it renders text. Each file is only
+/// read once.
+pub fn read_kept<P: AsRef<Path>>(path: P) -> io::Result<&'static [u8]> {
+    let path = path.as_ref();
+
+    if let Some(data) = KEPT.with(|kept| kept.borrow().get(path).cloned()) {
+        return Ok(data);
+    }
+
+    // Never freed, as promised
+    let data: &'static [u8] = Box::leak(try!(read(path)).into_boxed_slice());
+
+    KEPT.with(|kept| kept.borrow_mut().insert(path.to_path_buf(), data));
+    Ok(data)
+}
+
+/// Whether there is a file at `path`, in any source or relative to the
+/// current directory.
+pub fn exists<P: AsRef<Path>>(path: P) -> bool {
+    let path = path.as_ref();
+
+    let mounted = MOUNTS.with(|mounts| {
+        mounts.borrow().iter()
+            .filter_map(|&(ref point, ref mount)| path.strip_prefix(point).ok().map(|rest| (mount, rest)))
+            .any(|(mount, rest)| mount.contains(rest))
+    });
+
+    mounted || path.is_file()
+}
+
+/// The paths of the files in the directory at `dir`, from every source,
+/// sorted.
+pub fn list<P: AsRef<Path>>(dir: P) -> Vec<PathBuf> {
+    let dir = dir.as_ref();
+
+    let mut names = list_dir(dir);
+    MOUNTS.with(|mounts| {
+        for &(ref point, ref mount) in mounts.borrow().iter() {
+            if let Ok(rest) = dir.strip_prefix(point) {
+                names.extend(mount.list(rest));
+            }
+        }
+    });
+
+    names.sort();
+    names.dedup();
+    names.into_iter().map(|name| dir.join(name)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tar header for an entry of `size` bytes, with a POSIX prefix if
+    /// there is one.
+    fn header(name: &str, prefix: Option<&str>, kind: u8, size: usize) -> Vec<u8> {
+        let mut header = vec![0; TAR_BLOCK];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        let size = format!("{:011o}", size);
+        header[124..124 + size.len()].copy_from_slice(size.as_bytes());
+        header[156] = kind;
+
+        if let Some(prefix) = prefix {
+            header[257..263].copy_from_slice(b"ustar\0");
+            header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+        }
+        header
+    }
+
+    /// An entry of an archive: its header, then its contents padded to a
+    /// whole block.
+    fn entry(name: &str, prefix: Option<&str>, kind: u8, contents: &[u8]) -> Vec<u8> {
+        let mut entry = header(name, prefix, kind, contents.len());
+        entry.extend_from_slice(contents);
+        while entry.len() % TAR_BLOCK != 0 {
+            entry.push(0);
+        }
+        entry
+    }
+
+    fn archive(entries: &[Vec<u8>]) -> Vec<u8> {
+        let mut data: Vec<u8> = entries.concat();
+        data.extend_from_slice(&[0; 2 * TAR_BLOCK]);
+        data
+    }
+
+    fn read_all(mount: &Mount, path: &str) -> Option<Vec<u8>> {
+        mount.read(Path::new(path)).map(|data| data.unwrap())
+    }
+
+    #[test]
+    fn keys_leave_out_the_current_directory() {
+        assert_eq!(key(Path::new("./levels/01.cfg")), "levels/01.cfg");
+        assert_eq!(key(Path::new("levels/./01.cfg")), "levels/01.cfg");
+        assert_eq!(key(Path::new("spaceship.png")), "spaceship.png");
+        assert_eq!(key(Path::new(".")), "");
+    }
+
+    #[test]
+    fn names_in_lists_only_the_files_directly_in_a_directory() {
+        let paths = ["a.png", "levels/01.cfg", "levels/02.cfg", "levels/old/00.cfg", "levelsx/03.cfg"];
+
+        assert_eq!(names_in(paths.iter().cloned(), Path::new("levels")), vec!["01.cfg", "02.cfg"]);
+        assert_eq!(names_in(paths.iter().cloned(), Path::new("./levels/")), vec!["01.cfg", "02.cfg"]);
+        assert_eq!(names_in(paths.iter().cloned(), Path::new("")), vec!["a.png"]);
+        assert!(names_in(paths.iter().cloned(), Path::new("sounds")).is_empty());
+    }
+
+    #[test]
+    fn archives_keep_regular_files() {
+        let data = archive(&[
+            entry("./", None, b'5', b""),
+            entry("./spaceship.png", None, b'0', b"ship"),
+            entry("./levels/", None, b'5', b""),
+            entry("./levels/01.cfg", None, 0, b"level one"),
+            entry("link.png", None, b'2', b""),
+        ]);
+        let archive = Archive::parse(data).unwrap();
+
+        assert_eq!(read_all(&archive, "spaceship.png"), Some(b"ship".to_vec()));
+        assert_eq!(read_all(&archive, "levels/01.cfg"), Some(b"level one".to_vec()));
+        assert!(archive.contains(Path::new("./levels/01.cfg")));
+        assert!(!archive.contains(Path::new("levels")));
+        assert!(!archive.contains(Path::new("link.png")));
+        assert_eq!(archive.list(Path::new("")), vec!["spaceship.png"]);
+        assert_eq!(archive.list(Path::new("levels")), vec!["01.cfg"]);
+    }
+
+    #[test]
+    fn archives_join_ustar_prefixes() {
+        let data = archive(&[
+            entry("01.cfg", Some("levels"), b'0', b"prefixed"),
+            entry("02.cfg", None, b'0', b"not prefixed"),
+        ]);
+
+        let archive = Archive::parse(data).unwrap();
+        assert_eq!(read_all(&archive, "levels/01.cfg"), Some(b"prefixed".to_vec()));
+        assert_eq!(read_all(&archive, "02.cfg"), Some(b"not prefixed".to_vec()));
+        assert_eq!(read_all(&archive, "01.cfg"), None);
+    }
+
+    #[test]
+    fn archives_cut_short_are_rejected() {
+        let mut data = entry("spaceship.png", None, b'0', &[1; 1000]);
+        data.truncate(TAR_BLOCK + 600);
+        assert_eq!(Archive::parse(data).err().unwrap().kind(), io::ErrorKind::InvalidData);
+
+        let mut data = header("spaceship.png", None, b'0', 4);
+        data[124..136].copy_from_slice(b"not a size\0\0");
+        assert_eq!(Archive::parse(data).err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn archives_without_an_end_are_read_up_to_their_last_block() {
+        let mut data = entry("spaceship.png", None, b'0', b"ship");
+        data.extend_from_slice(&[7; 100]);
+
+        let archive = Archive::parse(data).unwrap();
+        assert_eq!(read_all(&archive, "spaceship.png"), Some(b"ship".to_vec()));
+    }
+}
@@ -0,0 +1,73 @@
+use ::phi::{Phi, View, ViewAction};
+use ::phi::error::{PhiError, PhiResult};
+use ::phi::ui::{Button, Label, Panel, Stack, Ui};
+use ::sdl2::pixels::Color;
+use ::views::shared::menu_theme;
+
+/// The entries of the error screen.
+#[derive(Clone, Copy)]
+enum ErrorAction {
+    Menu,
+    Quit,
+}
+
+/// Shown instead of a view which could not be created, e.g. because one of
+/// its assets is missing, to explain what went wrong.
+pub struct ErrorView {
+    ui: Ui<ErrorAction>,
+}
+
+impl ErrorView {
+    pub fn new(error: &PhiError) -> ErrorView {
+        error!("{}", error);
+
+        let menu = Stack::vertical()
+            .with(Label::new("Something went wrong").size(40))
+            .with(Label::new(&capitalize(&error.to_string())).size(20).color(Color::RGB(255, 90, 90)))
+            .with(Button::new("Main Menu", ErrorAction::Menu))
+            .with(Button::new("Quit", ErrorAction::Quit));
+
+        ErrorView {
+            ui: Ui::new(Panel::new(menu).min_width(480.0), menu_theme()),
+        }
+    }
+}
+
+/// The view created, or the error screen if it could not be.
+pub fn or_error<V: View + 'static>(result: PhiResult<V>) -> Box<View> {
+    match result {
+        Ok(view) => Box::new(view),
+        Err(err) => Box::new(ErrorView::new(&err)),
+    }
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+impl View for ErrorView {
+    fn render(&mut self, phi: &mut Phi, elapsed: f64) -> ViewAction {
+        if phi.events.now.quit || phi.events.now.key_escape == Some(true) {
+            return ViewAction::Quit;
+        }
+
+        for action in self.ui.handle(&phi.events) {
+            match action {
+                ErrorAction::Menu =>
+                    return ViewAction::ChangeView(or_error(::views::main_menu::MainMenuView::new(phi))),
+                ErrorAction::Quit =>
+                    return ViewAction::Quit,
+            }
+        }
+
+        phi.renderer.set_draw_color(Color::RGB(20, 0, 0));
+        phi.renderer.clear();
+        self.ui.render(phi, elapsed);
+
+        ViewAction::None
+    }
+}
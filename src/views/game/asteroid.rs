@@ -0,0 +1,163 @@
+use ::phi::Phi;
+use ::phi::collision::CollisionMask;
+use ::phi::config::{Fields, Line, ParseError};
+use ::phi::data::Rectangle;
+use ::phi::error::PhiResult;
+use ::phi::gfx::{AnimatedSprite, CopySprite, Sprite};
+
+const ASTEROID_PATH: &'static str = "assets/asteroid.png";
+const ASTEROIDS_W: usize = 21;
+const ASTEROIDS_H: usize = 7;
+const ASTEROIDS_TOTAL: usize = ASTEROIDS_W * ASTEROIDS_H - 4;
+const ASTEROID_SIDE: f64 = 96.0;
+
+/// Proportion of an asteroid's side taken by its hitbox. The corners of the
+/// frames are transparent, so the full rectangle would be unfair.
+const HITBOX_RATIO: f64 = 0.7;
+
+#[derive(Clone)]
+pub struct Asteroid {
+    sprite: AnimatedSprite,
+    pub rect: Rectangle,
+    /// Velocity in pixels per second, along x and y.
+    pub vel: (f64, f64),
+    /// Damage the asteroid can still take before it is destroyed.
+    hp: u32,
+    /// Dead asteroids are kept around by the field, waiting to be reused.
+    alive: bool,
+}
+
+impl Asteroid {
+    /// Creates a dead asteroid, which will only appear once it is `reset`.
+    pub fn new(sprite: AnimatedSprite) -> Asteroid {
+        Asteroid {
+            sprite: sprite,
+            rect: Rectangle {
+                w: ASTEROID_SIDE,
+                h: ASTEROID_SIDE,
+                x: 0.0,
+                y: 0.0,
+            },
+            vel: (0.0, 0.0),
+            hp: 0,
+            alive: false,
+        }
+    }
+
+    /// Brings the asteroid (back) to life at `rect`, moving at `vel` pixels
+    /// per second, spinning at `fps` frames per second and able to take `hp`
+    /// damage.
+    pub fn reset(&mut self, rect: Rectangle, vel: (f64, f64), fps: f64, hp: u32) {
+        self.sprite.set_fps(fps);
+        self.rect = rect;
+        self.vel = vel;
+        self.hp = hp.max(1);
+        self.alive = true;
+    }
+
+    /// The part of the asteroid which collides with other objects. It scales
+    /// with the asteroid, so fragments have smaller hitboxes.
+    pub fn hitbox(&self) -> Rectangle {
+        let (w, h) = (self.rect.w * HITBOX_RATIO, self.rect.h * HITBOX_RATIO);
+
+        Rectangle {
+            x: self.rect.x + (self.rect.w - w) / 2.0,
+            y: self.rect.y + (self.rect.h - h) / 2.0,
+            w: w,
+            h: h,
+        }
+    }
+
+    /// Whether the asteroid touches an object occupying `rect`. When the
+    /// object's solid pixels are known through `mask`, they are tested
+    /// against those of the asteroid's current frame, so that only visible
+    /// parts collide.
+    pub fn collides_with(&self, rect: Rectangle, mask: Option<&CollisionMask>) -> bool {
+        if !self.hitbox().overlaps(rect) {
+            return false;
+        }
+
+        match (self.sprite.mask(), mask) {
+            (Some(own), Some(other)) => own.overlaps(self.rect, other, rect),
+            _ => true,
+        }
+    }
+
+    /// Inflicts `amount` damage to the asteroid. Returns whether it was
+    /// destroyed by it.
+    pub fn damage(&mut self, amount: u32) -> bool {
+        self.hp = self.hp.saturating_sub(amount);
+
+        if self.hp == 0 {
+            self.alive = false;
+        }
+
+        !self.alive
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.alive
+    }
+
+    pub fn kill(&mut self) {
+        self.alive = false;
+    }
+
+    /// The asteroid's state, as written to a save file.
+    pub fn save(&self) -> Line {
+        Line::new().rect(self.rect).pair(self.vel).word(self.hp)
+            .word(self.sprite.frame_delay()).word(self.sprite.time())
+    }
+
+    /// Brings the asteroid (back) to life as it was saved.
+    pub fn load(&mut self, fields: &mut Fields) -> Result<(), ParseError> {
+        self.rect = try!(fields.rect());
+        self.vel = try!(fields.pair());
+        self.hp = try!(fields.word::<u32>()).max(1);
+        self.sprite.set_frame_delay(try!(fields.word()));
+        self.sprite.set_time(try!(fields.word()));
+        self.alive = true;
+        Ok(())
+    }
+
+    /// Loads the frames of the spinning asteroid, shared by every asteroid.
+    pub fn get_sprite(phi: &mut Phi, fps: f64) -> PhiResult<AnimatedSprite> {
+        let asteroid_spritesheet = try!(Sprite::load(&mut phi.renderer, ASTEROID_PATH));
+        let mut asteroid_sprites = Vec::with_capacity(ASTEROIDS_TOTAL);
+
+        for yth in 0..ASTEROIDS_H {
+            for xth in 0..ASTEROIDS_W {
+                if (ASTEROIDS_W * yth) + xth >= ASTEROIDS_TOTAL {
+                    break;
+                }
+
+                asteroid_sprites.push(
+                    try!(asteroid_spritesheet.region_of(ASTEROID_PATH, Rectangle {
+                        w: ASTEROID_SIDE,
+                        h: ASTEROID_SIDE,
+                        x: ASTEROID_SIDE * xth as f64,
+                        y: ASTEROID_SIDE * yth as f64,
+                    })));
+            }
+        }
+
+        Ok(AnimatedSprite::with_fps(asteroid_sprites, fps))
+    }
+    
+    /// Moves the asteroid, killing it once it left the screen, which is
+    /// `screen_h` pixels high, through any side but the right one.
+    pub fn update(&mut self, dt: f64, screen_h: f64) {
+        self.rect.x += dt * self.vel.0;
+        self.rect.y += dt * self.vel.1;
+        self.sprite.add_time(dt);
+
+        if self.rect.x <= -self.rect.w ||
+            self.rect.y <= -self.rect.h || self.rect.y >= screen_h {
+            self.alive = false;
+        }
+    }
+
+    pub fn render(&self, phi: &mut Phi) {
+        phi.renderer.copy_sprite(&self.sprite, self.rect);
+    }
+}
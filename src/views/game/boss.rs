@@ -0,0 +1,573 @@
+use ::phi::Phi;
+use ::phi::collision::{self, CollisionMask};
+use ::phi::config::{Config, Fields, Line, ParseError};
+use ::phi::data::Rectangle;
+use ::phi::debug::{DebugOverlay, HITBOX_COLOR};
+use ::phi::error::PhiResult;
+use ::phi::gfx::{AnimatedSprite, CopySprite, DrawRectangle, Sprite};
+use ::phi::tween::Ease;
+use ::sdl2::pixels::Color;
+use ::std::f64::consts::PI;
+use ::views::game::explosion::Explosion;
+use ::views::game::weapons::ProjectilePool;
+use ::views::shared::FONT_PATH;
+
+const BOSS_PATH: &'static str = "assets/boss.png";
+const BOSS_SHOT_PATH: &'static str = "assets/enemy_bullet.png";
+
+const HULL_W: f64 = 160.0;
+const HULL_H: f64 = 240.0;
+/// Parts' frames are laid out on rows of 64 pixels cells, right of the hull.
+const PART_CELL: f64 = 64.0;
+const PART_FRAMES: usize = 4;
+const PART_FPS: f64 = 8.0;
+
+/// Seconds during which the warning blinks before the boss appears.
+const WARNING_TIME: f64 = 3.0;
+/// Seconds taken by the boss to fly to its position.
+const ENTER_TIME: f64 = 3.0;
+/// Seconds of explosions once the core was destroyed.
+const DYING_TIME: f64 = 2.5;
+/// Pixels between the boss and the right edge of the screen when fighting.
+const RIGHT_MARGIN: f64 = 40.0;
+/// Pixels travelled up and down while fighting.
+const BOB_AMPLITUDE: f64 = 60.0;
+
+const SHOT_SPEED: f64 = 260.0;
+
+/// How the boss fights while its health is above a proportion of the total.
+struct Phase {
+    /// The phase starts once health is at or below this proportion
+    threshold: f64,
+    /// Shots per second of each turret
+    turret_rate: f64,
+    /// Bursts per second fired by the core, once exposed
+    core_rate: f64,
+    /// Shots in each burst of the core, in a fan
+    core_burst: u32,
+    /// Radians per second of the up and down motion
+    bob_speed: f64,
+}
+
+static PHASES: [Phase; 3] = [
+    Phase { threshold: 1.0, turret_rate: 0.6, core_rate: 0.0, core_burst: 0, bob_speed: 0.6 },
+    Phase { threshold: 0.66, turret_rate: 0.9, core_rate: 0.4, core_burst: 3, bob_speed: 0.9 },
+    Phase { threshold: 0.33, turret_rate: 1.2, core_rate: 0.7, core_burst: 7, bob_speed: 1.4 },
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PartKind {
+    /// Destroying it destroys the boss, but it cannot be damaged while any
+    /// shield generator stands.
+    Core,
+    /// Fires at the player.
+    Turret,
+    /// Protects the core.
+    Shield,
+}
+
+impl PartKind {
+    /// The row of its frames in `BOSS_PATH`, and the side of each frame.
+    fn frames(self) -> (usize, f64) {
+        match self {
+            PartKind::Core => (0, 64.0),
+            PartKind::Turret => (1, 32.0),
+            PartKind::Shield => (2, 48.0),
+        }
+    }
+
+    fn hp(self) -> u32 {
+        match self {
+            PartKind::Core => 40,
+            PartKind::Turret => 8,
+            PartKind::Shield => 15,
+        }
+    }
+
+    /// Points for destroying it, before the combo multiplier.
+    fn points(self) -> u64 {
+        match self {
+            PartKind::Core => 5000,
+            PartKind::Turret => 300,
+            PartKind::Shield => 500,
+        }
+    }
+}
+
+/// A piece of the boss, with its own animation, health and hitbox.
+#[derive(Clone)]
+struct Part {
+    kind: PartKind,
+    sprite: AnimatedSprite,
+    /// Position of its top left corner, relative to the hull's
+    offset: (f64, f64),
+    hp: u32,
+    /// Seconds until it may fire again
+    cooldown: f64,
+}
+
+impl Part {
+    fn is_alive(&self) -> bool {
+        self.hp > 0
+    }
+}
+
+/// What the boss is doing.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    /// Warning the player it is coming
+    Warning,
+    /// Flying to its position, invulnerable
+    Entering,
+    Fighting,
+    /// Blowing up, after its core was destroyed
+    Dying,
+    /// Gone for good
+    Defeated,
+}
+
+impl State {
+    /// The state whose number is `index`, as given by `state as usize`.
+    fn from_index(index: usize) -> Option<State> {
+        [State::Warning, State::Entering, State::Fighting, State::Dying, State::Defeated]
+            .get(index).cloned()
+    }
+}
+
+/// What happened to a part of the boss which was hit.
+#[derive(Clone, Copy, Debug)]
+pub struct BossHit {
+    pub destroyed: bool,
+    /// Points for destroying the part, or 0 if it survived
+    pub points: u64,
+    /// Where the boss was hit
+    pub rect: Rectangle,
+}
+
+/// A large ship made of several parts, which must be destroyed one by one.
+#[derive(Clone)]
+pub struct Boss {
+    hull: Sprite,
+    parts: Vec<Part>,
+    /// Position of the hull's top left corner
+    pos: (f64, f64),
+    /// Where the hull settles to fight
+    home: (f64, f64),
+    state: State,
+    /// Seconds since the current state started
+    time: f64,
+    /// Seconds spent fighting, which drive the up and down motion
+    bob_time: f64,
+    /// The index of the current entry of `PHASES`
+    phase: usize,
+    max_hp: u32,
+    pub shots: ProjectilePool,
+    explosion_sprite: AnimatedSprite,
+    explosions: Vec<Explosion>,
+    warning: Option<Sprite>,
+}
+
+impl Boss {
+    /// Prepares a boss, which first warns the player, then enters `screen`
+    /// from the right.
+    pub fn new(phi: &mut Phi, screen: Rectangle) -> PhiResult<Boss> {
+        let spritesheet = try!(Sprite::load(&mut phi.renderer, BOSS_PATH));
+        let hull = try!(spritesheet.region_of(BOSS_PATH, Rectangle { x: 0.0, y: 0.0, w: HULL_W, h: HULL_H }));
+
+        let layout = [
+            (PartKind::Core, (64.0, 88.0)),
+            (PartKind::Turret, (30.0, 78.0)),
+            (PartKind::Turret, (30.0, 130.0)),
+            (PartKind::Turret, (76.0, 48.0)),
+            (PartKind::Turret, (76.0, 160.0)),
+            (PartKind::Shield, (108.0, 36.0)),
+            (PartKind::Shield, (108.0, 156.0)),
+        ];
+
+        let mut parts = Vec::with_capacity(layout.len());
+        for &(kind, offset) in &layout {
+            let (row, side) = kind.frames();
+            let frames = try!((0..PART_FRAMES).map(|xth| {
+                spritesheet.region_of(BOSS_PATH, Rectangle {
+                    w: side,
+                    h: side,
+                    x: HULL_W + PART_CELL * xth as f64,
+                    y: PART_CELL * row as f64,
+                })
+            }).collect());
+
+            parts.push(Part {
+                kind: kind,
+                sprite: AnimatedSprite::with_fps(frames, PART_FPS),
+                offset: offset,
+                hp: kind.hp(),
+                cooldown: 1.0 + ::phi::rng::random(),
+            });
+        }
+
+        let max_hp = parts.iter().map(|part| part.hp).sum();
+        let home = (screen.w - HULL_W - RIGHT_MARGIN, (screen.h - HULL_H) / 2.0);
+
+        Ok(Boss {
+            hull: hull,
+            parts: parts,
+            pos: (screen.w, home.1),
+            home: home,
+            state: State::Warning,
+            time: 0.0,
+            bob_time: 0.0,
+            phase: 0,
+            max_hp: max_hp,
+            shots: ProjectilePool::new(try!(Sprite::load(&mut phi.renderer, BOSS_SHOT_PATH))),
+            explosion_sprite: try!(Explosion::get_sprite(phi)),
+            explosions: Vec::new(),
+            warning: phi.ttf_str_sprite("WARNING", FONT_PATH, 72, Color::RGB(255, 60, 60)),
+        })
+    }
+
+    /// The health left, as a proportion of the total.
+    pub fn health(&self) -> f64 {
+        let hp: u32 = self.parts.iter().map(|part| part.hp).sum();
+        hp as f64 / self.max_hp as f64
+    }
+
+    /// Whether the boss is done exploding, and can be forgotten.
+    pub fn is_defeated(&self) -> bool {
+        self.state == State::Defeated
+    }
+
+    fn part_rect(&self, part: &Part) -> Rectangle {
+        let (w, h) = part.sprite.current_sprite().size();
+        Rectangle {
+            x: self.pos.0 + part.offset.0,
+            y: self.pos.1 + part.offset.1,
+            w: w,
+            h: h,
+        }
+    }
+
+    fn hull_rect(&self) -> Rectangle {
+        Rectangle { x: self.pos.0, y: self.pos.1, w: HULL_W, h: HULL_H }
+    }
+
+    fn set_state(&mut self, state: State) {
+        self.state = state;
+        self.time = 0.0;
+    }
+
+    fn explode(&mut self, rect: Rectangle) {
+        self.explosions.push(Explosion::new(self.explosion_sprite.clone(), rect, (0.0, 0.0)));
+    }
+
+    /// Moves the boss and lets its parts fire at `target`, the centre of the
+    /// player's ship, if `can_target`. Its shots are removed once they leave
+    /// `screen`.
+    pub fn update(&mut self, dt: f64, target: (f64, f64), can_target: bool, screen: Rectangle) {
+        self.time += dt;
+
+        for part in &mut self.parts {
+            part.sprite.add_time(dt);
+        }
+
+        for explosion in &mut self.explosions {
+            explosion.update(dt);
+        }
+        self.explosions.retain(|explosion| !explosion.is_finished());
+
+        self.shots.update(dt, screen);
+
+        let state = self.state;
+        match state {
+            State::Warning => if self.time >= WARNING_TIME {
+                self.set_state(State::Entering);
+            },
+
+            State::Entering => {
+                let progress = Ease::CubicOut.apply(self.time / ENTER_TIME);
+                self.pos.0 = screen.w + (self.home.0 - screen.w) * progress;
+
+                if self.time >= ENTER_TIME {
+                    self.set_state(State::Fighting);
+                }
+            },
+
+            State::Fighting => {
+                self.bob_time += dt * PHASES[self.phase].bob_speed;
+                self.pos.1 = self.home.1 + BOB_AMPLITUDE * self.bob_time.sin();
+
+                // Move on to the next phase once health is low enough. Firing
+                // pauses for a moment to let the player notice.
+                let health = self.health();
+                while self.phase + 1 < PHASES.len() && health <= PHASES[self.phase + 1].threshold {
+                    self.phase += 1;
+
+                    for part in &mut self.parts {
+                        part.cooldown = part.cooldown.max(1.0);
+                    }
+                }
+
+                if can_target {
+                    self.fire(dt, target);
+                }
+            },
+
+            State::Dying => {
+                // Chain explosions over the hull
+                if ::phi::rng::random() < dt * 12.0 {
+                    let rect = Rectangle {
+                        x: self.pos.0 + ::phi::rng::random() * (HULL_W - 64.0),
+                        y: self.pos.1 + ::phi::rng::random() * (HULL_H - 64.0),
+                        w: 64.0,
+                        h: 64.0,
+                    };
+                    self.explode(rect);
+                }
+
+                if self.time >= DYING_TIME {
+                    self.set_state(State::Defeated);
+                }
+            },
+
+            State::Defeated => {},
+        }
+    }
+
+    fn fire(&mut self, dt: f64, target: (f64, f64)) {
+        let phase = &PHASES[self.phase];
+        let shielded = self.parts.iter().any(|part| part.kind == PartKind::Shield && part.is_alive());
+        let pos = self.pos;
+
+        for part in self.parts.iter_mut().filter(|part| part.is_alive()) {
+            let rate = match part.kind {
+                PartKind::Turret => phase.turret_rate,
+                PartKind::Core if !shielded => phase.core_rate,
+                _ => 0.0,
+            };
+
+            part.cooldown = (part.cooldown - dt).max(0.0);
+            if rate <= 0.0 || part.cooldown > 0.0 {
+                continue;
+            }
+            part.cooldown = 1.0 / rate;
+
+            let (w, h) = part.sprite.current_sprite().size();
+            let (x, y) = (pos.0 + part.offset.0 + w / 2.0, pos.1 + part.offset.1 + h / 2.0);
+            let aim = (target.1 - y).atan2(target.0 - x);
+
+            match part.kind {
+                PartKind::Turret =>
+                    self.shots.spawn(x, y, (SHOT_SPEED * aim.cos(), SHOT_SPEED * aim.sin())),
+
+                // A fan of shots centred on the player
+                PartKind::Core => for i in 0..phase.core_burst {
+                    let spread = if phase.core_burst == 1 { 0.0 }
+                        else { i as f64 / (phase.core_burst - 1) as f64 - 0.5 };
+                    let angle = aim + spread * PI / 2.0;
+                    self.shots.spawn(x, y, (SHOT_SPEED * angle.cos(), SHOT_SPEED * angle.sin()));
+                },
+
+                PartKind::Shield => {},
+            }
+        }
+    }
+
+    /// Damages the first part colliding with an object occupying `rect`,
+    /// whose solid pixels are `mask` if known. The boss can only be hurt
+    /// while it is fighting, and its core only once every shield generator
+    /// was destroyed. Other parts of the hull merely absorb the hit.
+    pub fn hit(&mut self, rect: Rectangle, mask: Option<&CollisionMask>,
+               damage: u32) -> Option<BossHit> {
+        if !self.touches(rect, mask) {
+            return None;
+        }
+
+        if self.state != State::Fighting {
+            return Some(BossHit { destroyed: false, points: 0, rect: rect });
+        }
+
+        let shielded = self.parts.iter().any(|part| part.kind == PartKind::Shield && part.is_alive());
+
+        let index = self.parts.iter().position(|part| {
+            part.is_alive() &&
+            !(part.kind == PartKind::Core && shielded) &&
+            collision::collides(self.part_rect(part), part.sprite.mask(), rect, mask)
+        });
+
+        let index = match index {
+            Some(index) => index,
+            None => return Some(BossHit { destroyed: false, points: 0, rect: rect }),
+        };
+
+        let part_rect = self.part_rect(&self.parts[index]);
+        let kind = self.parts[index].kind;
+
+        self.parts[index].hp = self.parts[index].hp.saturating_sub(damage);
+        if self.parts[index].is_alive() {
+            return Some(BossHit { destroyed: false, points: 0, rect: rect });
+        }
+
+        self.explode(part_rect);
+
+        if kind == PartKind::Core {
+            // Whatever is left goes down with the core.
+            for part in &mut self.parts {
+                part.hp = 0;
+            }
+            self.set_state(State::Dying);
+        }
+
+        Some(BossHit { destroyed: true, points: kind.points(), rect: part_rect })
+    }
+
+    /// Shows the hitboxes of the hull and of every part left, and the shots.
+    pub fn debug(&self, overlay: &mut DebugOverlay) {
+        overlay.rect(self.hull_rect(), HITBOX_COLOR);
+
+        for part in self.parts.iter().filter(|part| part.is_alive()) {
+            overlay.rect(self.part_rect(part), HITBOX_COLOR);
+        }
+
+        overlay.count("boss parts", self.parts.iter().filter(|part| part.is_alive()).count());
+        self.shots.debug(overlay, "boss shots");
+    }
+
+    /// Writes the boss, each of its parts and its shots to the `boss` section
+    /// of a save file. Explosions are left out.
+    pub fn save(&self, config: &mut Config) {
+        config.set("boss", "boss", Line::new()
+            .pair(self.pos).pair(self.home).word(self.state as usize)
+            .word(self.time).word(self.bob_time).word(self.phase));
+
+        for part in &self.parts {
+            config.add("boss", "part", Line::new().word(part.hp).word(part.cooldown).word(part.sprite.time()));
+        }
+
+        self.shots.save(config, "boss", "shot");
+    }
+
+    /// Restores what `save` wrote into a new boss, whose parts are in the same
+    /// order.
+    pub fn load(&mut self, config: &Config) -> Result<(), ParseError> {
+        let mut part = 0;
+
+        for entry in config.entries().iter().filter(|entry| entry.section == "boss") {
+            let mut fields = Fields::new(entry);
+
+            match &entry.key[..] {
+                "boss" => {
+                    self.pos = try!(fields.pair());
+                    self.home = try!(fields.pair());
+                    self.state = match State::from_index(try!(fields.word())) {
+                        Some(state) => state,
+                        None => return Err(fields.error("invalid state")),
+                    };
+                    self.time = try!(fields.word());
+                    self.bob_time = try!(fields.word());
+                    self.phase = try!(fields.word::<usize>()).min(PHASES.len() - 1);
+                },
+                "part" => {
+                    if part >= self.parts.len() {
+                        return Err(fields.error("too many parts"));
+                    }
+
+                    self.parts[part].hp = try!(fields.word());
+                    self.parts[part].cooldown = try!(fields.word());
+                    self.parts[part].sprite.set_time(try!(fields.word()));
+                    part += 1;
+                },
+                "shot" => try!(self.shots.load(&mut fields)),
+                _ => return Err(fields.error("unknown entry")),
+            }
+
+            try!(fields.end());
+        }
+
+        Ok(())
+    }
+
+    /// Whether the boss' hull touches an object occupying `rect`, whose solid
+    /// pixels are `mask` if known.
+    pub fn touches(&self, rect: Rectangle, mask: Option<&CollisionMask>) -> bool {
+        match self.state {
+            State::Entering | State::Fighting =>
+                collision::collides(self.hull_rect(), self.hull.mask(), rect, mask),
+            _ => false,
+        }
+    }
+
+    pub fn render(&self, phi: &mut Phi) {
+        match self.state {
+            State::Entering | State::Fighting | State::Dying => {
+                phi.renderer.copy_sprite(&self.hull, self.hull_rect());
+
+                for part in self.parts.iter().filter(|part| part.is_alive()) {
+                    phi.renderer.copy_sprite(&part.sprite, self.part_rect(part));
+                }
+            },
+            _ => {},
+        }
+
+        self.shots.render(phi);
+
+        for explosion in &self.explosions {
+            explosion.render(phi);
+        }
+    }
+
+    /// Draws the warning before the boss appears, then its health bar at the
+    /// top of the screen while it fights.
+    pub fn render_overlay(&self, phi: &mut Phi) {
+        let (win_w, win_h) = phi.output_size();
+
+        match self.state {
+            State::Warning => {
+                // Blink twice per second
+                if (self.time * 4.0) as u32 % 2 == 1 {
+                    return;
+                }
+
+                if let Some(ref warning) = self.warning {
+                    let (w, h) = warning.size();
+                    phi.renderer.copy_sprite(warning, Rectangle {
+                        x: (win_w - w) / 2.0,
+                        y: (win_h - h) / 2.0,
+                        w: w,
+                        h: h,
+                    });
+                }
+            },
+
+            State::Entering | State::Fighting => {
+                // Fills up as the boss enters, then empties as it is damaged.
+                let fill = if self.state == State::Entering {
+                    (self.time / ENTER_TIME).min(1.0)
+                } else {
+                    self.health()
+                };
+
+                let frame = Rectangle {
+                    x: win_w * 0.2,
+                    y: 60.0,
+                    w: win_w * 0.6,
+                    h: 16.0,
+                };
+                let bar = Rectangle {
+                    x: frame.x + 2.0,
+                    y: frame.y + 2.0,
+                    w: (frame.w - 4.0) * fill,
+                    h: frame.h - 4.0,
+                };
+
+                phi.renderer.set_draw_color(Color::RGB(60, 20, 20));
+                phi.renderer.fill_rectangle(frame);
+
+                if let Some(bar) = bar.to_sdl() {
+                    phi.renderer.set_draw_color(Color::RGB(220, 40, 40));
+                    phi.renderer.fill_rect(bar);
+                }
+            },
+
+            _ => {},
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use ::phi::Phi;
+use ::phi::collision::{self, CollisionMask};
+use ::phi::config::{Fields, Line, ParseError};
+use ::phi::data::Rectangle;
+use ::phi::error::PhiResult;
+use ::phi::gfx::{AnimatedSprite, CopySprite, Sprite};
+use ::views::game::level::{format_pattern, parse_pattern};
+use ::views::game::pattern::{Pattern, Situation};
+
+const ENEMIES_PATH: &'static str = "assets/enemies.png";
+/// Frames of each kind of enemy, laid out on a single row of the spritesheet.
+const ENEMY_FRAMES: usize = 4;
+const ENEMY_FPS: f64 = 12.0;
+const ENEMY_W: f64 = 48.0;
+const ENEMY_H: f64 = 40.0;
+
+/// A type of enemy ship: how it looks, moves and fights.
+#[derive(Clone, Debug)]
+pub struct EnemyKind {
+    pub name: &'static str,
+    /// The row of `ENEMIES_PATH` holding its frames
+    pub row: usize,
+    pub hp: u32,
+    /// Points for destroying it, before the combo multiplier
+    pub points: u64,
+    pub pattern: Pattern,
+    /// Shots per second, or 0 if it never fires
+    pub fire_rate: f64,
+    /// Pixels travelled by its shots every second
+    pub shot_speed: f64,
+    /// Whether its shots are aimed at the player, rather than fired forward
+    pub aimed: bool,
+    /// The first wave in which it may appear
+    pub first_wave: u32,
+    /// How often it appears relative to other kinds
+    pub weight: f64,
+}
+
+impl EnemyKind {
+    /// Every kind of enemy in the game.
+    pub fn all() -> Vec<EnemyKind> {
+        vec![
+            // Flies straight through the screen, firing ahead.
+            EnemyKind {
+                name: "scout",
+                row: 0,
+                hp: 1,
+                points: 100,
+                pattern: Pattern::Straight(-160.0, 0.0),
+                fire_rate: 0.5,
+                shot_speed: 300.0,
+                aimed: false,
+                first_wave: 1,
+                weight: 3.0,
+            },
+
+            // Weaves up and down on its way.
+            EnemyKind {
+                name: "weaver",
+                row: 1,
+                hp: 2,
+                points: 150,
+                pattern: Pattern::Straight(-120.0, 0.0)
+                    .with(Pattern::Sine { amplitude: 80.0, period: 2.0 }),
+                fire_rate: 0.6,
+                shot_speed: 260.0,
+                aimed: true,
+                first_wave: 1,
+                weight: 2.0,
+            },
+
+            // Comes in slowly, then throws itself at the player.
+            EnemyKind {
+                name: "diver",
+                row: 2,
+                hp: 2,
+                points: 200,
+                pattern: Pattern::Straight(-100.0, 0.0)
+                    .then(1.5, Pattern::Dive { speed: 320.0, turn: 2.0 }),
+                fire_rate: 0.0,
+                shot_speed: 0.0,
+                aimed: false,
+                first_wave: 2,
+                weight: 2.0,
+            },
+
+            // Stops near the right edge to strafe the player, then leaves.
+            EnemyKind {
+                name: "gunship",
+                row: 3,
+                hp: 5,
+                points: 400,
+                pattern: Pattern::Straight(-140.0, 0.0)
+                    .then(1.2, Pattern::Strafe { speed: 90.0 })
+                    .then(5.0, Pattern::Straight(-220.0, 0.0)),
+                fire_rate: 1.5,
+                shot_speed: 320.0,
+                aimed: false,
+                first_wave: 3,
+                weight: 1.0,
+            },
+        ]
+    }
+
+    /// Loads the frames of every kind of enemy, in the order of `all()`.
+    pub fn get_sprites(phi: &mut Phi, kinds: &[EnemyKind]) -> PhiResult<Vec<AnimatedSprite>> {
+        let spritesheet = try!(Sprite::load(&mut phi.renderer, ENEMIES_PATH));
+
+        kinds.iter().map(|kind| {
+            let frames = try!((0..ENEMY_FRAMES).map(|xth| {
+                spritesheet.region_of(ENEMIES_PATH, Rectangle {
+                    w: ENEMY_W,
+                    h: ENEMY_H,
+                    x: ENEMY_W * xth as f64,
+                    y: ENEMY_H * kind.row as f64,
+                })
+            }).collect());
+
+            Ok(AnimatedSprite::with_fps(frames, ENEMY_FPS))
+        }).collect()
+    }
+}
+
+
+#[derive(Clone)]
+pub struct Enemy {
+    /// The index of its kind in the fleet
+    pub kind: usize,
+    /// How it moves, usually that of its kind
+    pattern: Pattern,
+    sprite: AnimatedSprite,
+    pub rect: Rectangle,
+    /// Velocity in pixels per second, along x and y.
+    pub vel: (f64, f64),
+    hp: u32,
+    /// Seconds since it appeared
+    time: f64,
+    /// Seconds until it may fire again
+    pub cooldown: f64,
+    alive: bool,
+}
+
+impl Enemy {
+    /// Creates an enemy of the given kind, appearing with its top left corner
+    /// at `(x, y)` and moving along `pattern`.
+    pub fn new(kind: usize, info: &EnemyKind, sprite: AnimatedSprite, pattern: Pattern,
+               x: f64, y: f64) -> Enemy {
+        Enemy {
+            kind: kind,
+            pattern: pattern,
+            sprite: sprite,
+            rect: Rectangle {
+                x: x,
+                y: y,
+                w: ENEMY_W,
+                h: ENEMY_H,
+            },
+            vel: (0.0, 0.0),
+            hp: info.hp,
+            time: 0.0,
+            // Give the player a moment before the first shot.
+            cooldown: if info.fire_rate > 0.0 { 1.0 / info.fire_rate } else { 0.0 },
+            alive: true,
+        }
+    }
+
+    pub fn center(&self) -> (f64, f64) {
+        (self.rect.x + self.rect.w / 2.0, self.rect.y + self.rect.h / 2.0)
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.alive
+    }
+
+    /// Inflicts `amount` damage to the enemy. Returns whether it was
+    /// destroyed by it.
+    pub fn damage(&mut self, amount: u32) -> bool {
+        self.hp = self.hp.saturating_sub(amount);
+
+        if self.hp == 0 {
+            self.alive = false;
+        }
+
+        !self.alive
+    }
+
+    /// Whether the enemy touches an object occupying `rect`, whose solid
+    /// pixels are `mask` if known.
+    pub fn collides_with(&self, rect: Rectangle, mask: Option<&CollisionMask>) -> bool {
+        collision::collides(self.rect, self.sprite.mask(), rect, mask)
+    }
+
+    /// Moves the enemy along its pattern, towards or around `target`. It is
+    /// killed once it left `screen`.
+    pub fn update(&mut self, dt: f64, target: (f64, f64), screen: Rectangle) {
+        self.time += dt;
+        self.sprite.add_time(dt);
+
+        self.vel = self.pattern.velocity(self.time, &Situation {
+            position: self.center(),
+            velocity: self.vel,
+            target: target,
+            dt: dt,
+        });
+
+        self.rect.x += self.vel.0 * dt;
+        self.rect.y += self.vel.1 * dt;
+
+        // Enemies appear from the right edge, so only leave through the others
+        // or if they turned back.
+        if self.rect.x <= -self.rect.w || self.rect.x >= screen.w + self.rect.w ||
+            self.rect.y <= -self.rect.h || self.rect.y >= screen.h {
+            self.alive = false;
+        }
+    }
+
+    /// The enemy's state, as written to a save file. Its pattern is only
+    /// written if it differs from `usual`, that of its kind.
+    pub fn save(&self, usual: &Pattern) -> Line {
+        let line = Line::new().word(self.kind).rect(self.rect).pair(self.vel)
+            .word(self.hp).word(self.time).word(self.cooldown).word(self.sprite.time());
+
+        if self.pattern == *usual { line } else { line.word(format_pattern(&self.pattern)) }
+    }
+
+    /// Restores what `save` wrote after the kind into a new enemy of that
+    /// kind.
+    pub fn load(&mut self, fields: &mut Fields) -> Result<(), ParseError> {
+        self.rect = try!(fields.rect());
+        self.vel = try!(fields.pair());
+        self.hp = try!(fields.word::<u32>()).max(1);
+        self.time = try!(fields.word());
+        self.cooldown = try!(fields.word());
+        self.sprite.set_time(try!(fields.word()));
+
+        let pattern = fields.rest();
+        if !pattern.is_empty() {
+            self.pattern = try!(parse_pattern(&pattern).map_err(|message| fields.error(&message)));
+        }
+
+        Ok(())
+    }
+
+    pub fn render(&self, phi: &mut Phi) {
+        phi.renderer.copy_sprite(&self.sprite, self.rect);
+    }
+}
@@ -0,0 +1,70 @@
+use ::phi::Phi;
+use ::phi::data::Rectangle;
+use ::phi::error::PhiResult;
+use ::phi::gfx::{AnimatedSprite, CopySprite, Sprite};
+
+const EXPLOSION_PATH: &'static str = "assets/explosion.png";
+const EXPLOSIONS_WIDE: usize = 4;
+const EXPLOSIONS_HIGH: usize = 4;
+const EXPLOSIONS_TOTAL: usize = EXPLOSIONS_WIDE * EXPLOSIONS_HIGH;
+const EXPLOSION_SIDE: f64 = 64.0;
+const EXPLOSION_FPS: f64 = 16.0;
+
+/// An animation played once where something was destroyed.
+#[derive(Clone)]
+pub struct Explosion {
+    sprite: AnimatedSprite,
+    pub rect: Rectangle,
+    /// Velocity in pixels per second, along x and y.
+    vel: (f64, f64),
+    /// Seconds since the explosion started
+    time: f64,
+}
+
+impl Explosion {
+    /// Loads the frames of the explosion, which may be shared by every
+    /// explosion.
+    pub fn get_sprite(phi: &mut Phi) -> PhiResult<AnimatedSprite> {
+        let spritesheet = try!(Sprite::load(&mut phi.renderer, EXPLOSION_PATH));
+        let mut frames = Vec::with_capacity(EXPLOSIONS_TOTAL);
+
+        for yth in 0..EXPLOSIONS_HIGH {
+            for xth in 0..EXPLOSIONS_WIDE {
+                frames.push(try!(spritesheet.region_of(EXPLOSION_PATH, Rectangle {
+                    w: EXPLOSION_SIDE,
+                    h: EXPLOSION_SIDE,
+                    x: EXPLOSION_SIDE * xth as f64,
+                    y: EXPLOSION_SIDE * yth as f64,
+                })));
+            }
+        }
+
+        Ok(AnimatedSprite::with_fps(frames, EXPLOSION_FPS))
+    }
+
+    /// Starts an explosion covering `rect`, drifting at `vel`.
+    pub fn new(sprite: AnimatedSprite, rect: Rectangle, vel: (f64, f64)) -> Explosion {
+        Explosion {
+            sprite: sprite,
+            rect: rect,
+            vel: vel,
+            time: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, dt: f64) {
+        self.time += dt;
+        self.rect.x += self.vel.0 * dt;
+        self.rect.y += self.vel.1 * dt;
+        self.sprite.add_time(dt);
+    }
+
+    /// Whether every frame has been shown once.
+    pub fn is_finished(&self) -> bool {
+        self.time >= self.sprite.frames() as f64 / EXPLOSION_FPS
+    }
+
+    pub fn render(&self, phi: &mut Phi) {
+        phi.renderer.copy_sprite(&self.sprite, self.rect);
+    }
+}
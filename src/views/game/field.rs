@@ -0,0 +1,741 @@
+use ::phi::Phi;
+use ::phi::collision::CollisionMask;
+use ::phi::config::{Config, Entry, Fields, Line, ParseError};
+use ::phi::data::Rectangle;
+use ::phi::error::PhiResult;
+use ::phi::debug::{DebugOverlay, HITBOX_COLOR};
+use ::phi::gfx::AnimatedSprite;
+use ::std::path::Path;
+use ::views::game::asteroid::Asteroid;
+use ::views::game::explosion::Explosion;
+
+/// Where the pacing of the asteroid field is tuned.
+const DIFFICULTY_PATH: &'static str = "assets/difficulty.cfg";
+const DIFFICULTY_VERSION: u32 = 1;
+
+/// Asteroids allocated up front. More are only created if the difficulty
+/// allows more of them on screen at once.
+const POOL_CAPACITY: usize = 16;
+/// The most fragments a destroyed asteroid may split into.
+const MAX_FRAGMENTS: u32 = 8;
+
+/// A kind of asteroid which may be spawned, how often relative to others, and
+/// how much damage it takes to destroy it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SizeClass {
+    pub side: f64,
+    pub weight: f64,
+    pub hp: u32,
+}
+
+/// A horizontal strip of the screen where asteroids may appear, expressed as
+/// proportions of the screen's height, and how often relative to others.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Band {
+    pub top: f64,
+    pub bottom: f64,
+    pub weight: f64,
+}
+
+/// The pacing of the asteroid field. Waves last `wave_length` seconds, with
+/// `wave_break` seconds of calm in between, and each wave is harder than the
+/// previous one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Difficulty {
+    pub wave_length: f64,
+    pub wave_break: f64,
+
+    /// Asteroids spawned per second during the first wave
+    pub rate: f64,
+    /// Added to `rate` for every following wave
+    pub rate_per_wave: f64,
+    pub max_rate: f64,
+    /// The most asteroids allowed on screen at once
+    pub max_asteroids: usize,
+
+    /// Range of speeds during the first wave, in pixels per second
+    pub min_speed: f64,
+    pub max_speed: f64,
+    /// Added to both ends of the range for every following wave
+    pub speed_per_wave: f64,
+
+    pub sizes: Vec<SizeClass>,
+    pub bands: Vec<Band>,
+
+    /// Chance, in `[0, 1]`, that a destroyed asteroid splits into fragments
+    pub split_chance: f64,
+    /// Number of fragments a split asteroid breaks into, up to `MAX_FRAGMENTS`
+    pub fragments: u32,
+    /// Asteroids smaller than this never split
+    pub min_split_side: f64,
+    /// Fragments are this many times faster than their parent
+    pub fragment_speed: f64,
+    /// Vertical speed separating fragments, in pixels per second
+    pub fragment_spread: f64,
+
+    /// Enemy ships spawned per second during the first wave
+    pub enemy_rate: f64,
+    /// Added to `enemy_rate` for every following wave
+    pub enemy_rate_per_wave: f64,
+    pub max_enemy_rate: f64,
+    /// The most enemy ships allowed on screen at once
+    pub max_enemies: usize,
+
+    /// A boss appears after every wave which is a multiple of this, or never
+    /// if 0
+    pub boss_every: u32,
+}
+
+impl Difficulty {
+    pub fn new() -> Difficulty {
+        Difficulty {
+            wave_length: 30.0,
+            wave_break: 4.0,
+
+            rate: 0.6,
+            rate_per_wave: 0.25,
+            max_rate: 3.0,
+            max_asteroids: 20,
+
+            min_speed: 50.0,
+            max_speed: 150.0,
+            speed_per_wave: 20.0,
+
+            sizes: vec![
+                SizeClass { side: 96.0, weight: 1.0, hp: 3 },
+            ],
+            bands: vec![
+                Band { top: 0.0, bottom: 1.0, weight: 1.0 },
+            ],
+
+            split_chance: 1.0,
+            fragments: 2,
+            min_split_side: 64.0,
+            fragment_speed: 1.5,
+            fragment_spread: 80.0,
+
+            enemy_rate: 0.1,
+            enemy_rate_per_wave: 0.05,
+            max_enemy_rate: 0.6,
+            max_enemies: 6,
+
+            boss_every: 3,
+        }
+    }
+
+    /// Loads the difficulty from `DIFFICULTY_PATH`, falling back to the
+    /// defaults with a warning if it is missing or invalid.
+    pub fn load() -> Difficulty {
+        let config = match Config::load_asset(Path::new(DIFFICULTY_PATH)) {
+            Ok(config) => config,
+            Err(err) => {
+                warn!("Could not read {}: {}. Using default difficulty.", DIFFICULTY_PATH, err);
+                return Difficulty::new();
+            },
+        };
+
+        match Difficulty::from_config(&config) {
+            Ok(difficulty) => difficulty,
+            Err(err) => {
+                warn!("Invalid difficulty in {}: {}. Using default difficulty.", DIFFICULTY_PATH, err);
+                Difficulty::new()
+            },
+        }
+    }
+
+    /// Reads the difficulty from a parsed configuration. Missing values keep
+    /// their default.
+    pub fn from_config(config: &Config) -> Result<Difficulty, ParseError> {
+        match try!(config.parsed::<u32>("", "version")) {
+            Some(DIFFICULTY_VERSION) => {},
+            _ => return Err(ParseError {
+                line: config.entry("", "version").map_or(0, |entry| entry.line),
+                message: format!("expected `version = {}`", DIFFICULTY_VERSION),
+            }),
+        }
+
+        let mut difficulty = Difficulty::new();
+
+        {
+            let mut read = |section: &str, key: &str, value: &mut f64| -> Result<(), ParseError> {
+                if let Some(parsed) = try!(config.parsed(section, key)) {
+                    *value = parsed;
+                }
+                Ok(())
+            };
+
+            try!(read("waves", "length", &mut difficulty.wave_length));
+            try!(read("waves", "break", &mut difficulty.wave_break));
+            try!(read("spawn", "rate", &mut difficulty.rate));
+            try!(read("spawn", "rate_per_wave", &mut difficulty.rate_per_wave));
+            try!(read("spawn", "max_rate", &mut difficulty.max_rate));
+            try!(read("speed", "min", &mut difficulty.min_speed));
+            try!(read("speed", "max", &mut difficulty.max_speed));
+            try!(read("speed", "per_wave", &mut difficulty.speed_per_wave));
+            try!(read("fragments", "chance", &mut difficulty.split_chance));
+            try!(read("fragments", "min_side", &mut difficulty.min_split_side));
+            try!(read("fragments", "speed", &mut difficulty.fragment_speed));
+            try!(read("fragments", "spread", &mut difficulty.fragment_spread));
+            try!(read("enemies", "rate", &mut difficulty.enemy_rate));
+            try!(read("enemies", "rate_per_wave", &mut difficulty.enemy_rate_per_wave));
+            try!(read("enemies", "max_rate", &mut difficulty.max_enemy_rate));
+        }
+
+        if let Some(max_asteroids) = try!(config.parsed("spawn", "max_asteroids")) {
+            difficulty.max_asteroids = max_asteroids;
+        }
+
+        if let Some(max_enemies) = try!(config.parsed("enemies", "max_enemies")) {
+            difficulty.max_enemies = max_enemies;
+        }
+
+        if let Some(boss_every) = try!(config.parsed("boss", "every")) {
+            difficulty.boss_every = boss_every;
+        }
+
+        if let Some(fragments) = try!(config.parsed("fragments", "count")) {
+            difficulty.fragments = fragments;
+        }
+
+        let invalid = |section: &str, key: &str, message: &str| ParseError {
+            line: config.entry(section, key).map_or(0, |entry| entry.line),
+            message: message.to_string(),
+        };
+
+        if !(difficulty.wave_length > 0.0 && difficulty.wave_length.is_finite()) {
+            return Err(invalid("waves", "length", "waves must last longer than 0 seconds"));
+        }
+        if !is_amount(difficulty.wave_break) {
+            return Err(invalid("waves", "break", "breaks must last 0 seconds or more"));
+        }
+
+        for &(key, speed) in &[("min", difficulty.min_speed), ("max", difficulty.max_speed),
+                               ("per_wave", difficulty.speed_per_wave)] {
+            if !is_amount(speed) {
+                return Err(invalid("speed", key, "speeds must be 0 or more"));
+            }
+        }
+        if difficulty.max_speed < difficulty.min_speed {
+            let key = if config.entry("speed", "max").is_some() { "max" } else { "min" };
+            return Err(invalid("speed", key, "the maximum speed must not be below the minimum"));
+        }
+
+        if difficulty.fragments > MAX_FRAGMENTS {
+            return Err(invalid("fragments", "count",
+                               &format!("asteroids split into at most {} fragments", MAX_FRAGMENTS)));
+        }
+
+        let sizes = try!(entries(config, "sizes", 3));
+        if !sizes.is_empty() {
+            difficulty.sizes.clear();
+
+            for &(entry, ref values) in &sizes {
+                let (side, weight, hp) = (values[0], values[1], values[2]);
+
+                if !(side > 0.0 && side.is_finite()) {
+                    return Err(entry_error(entry, "asteroids must be larger than 0 pixels"));
+                }
+                if !(hp >= 1.0 && hp <= ::std::u32::MAX as f64 && hp.fract() == 0.0) {
+                    return Err(entry_error(entry, "asteroids must take a whole number of hits, at least 1"));
+                }
+
+                difficulty.sizes.push(SizeClass { side: side, weight: weight, hp: hp as u32 });
+            }
+        }
+
+        let bands = try!(entries(config, "bands", 3));
+        if !bands.is_empty() {
+            difficulty.bands.clear();
+
+            for &(entry, ref values) in &bands {
+                let (top, bottom, weight) = (values[0], values[1], values[2]);
+
+                if !(0.0 <= top && top < bottom && bottom <= 1.0) {
+                    return Err(entry_error(entry, "bands must fit in [0, 1], with the top above the bottom"));
+                }
+
+                difficulty.bands.push(Band { top: top, bottom: bottom, weight: weight });
+            }
+        }
+
+        Ok(difficulty)
+    }
+
+    /// The wave being played `time` seconds after the start, starting at 1.
+    pub fn wave_at(&self, time: f64) -> u32 {
+        (time / (self.wave_length + self.wave_break)) as u32 + 1
+    }
+
+    /// Whether `time` falls in the calm between two waves.
+    pub fn is_break_at(&self, time: f64) -> bool {
+        time % (self.wave_length + self.wave_break) >= self.wave_length
+    }
+
+    /// The number of asteroids spawned per second during `wave`.
+    pub fn rate_for(&self, wave: u32) -> f64 {
+        (self.rate + self.rate_per_wave * (wave - 1) as f64).min(self.max_rate)
+    }
+
+    /// The number of enemy ships spawned per second during `wave`.
+    pub fn enemy_rate_for(&self, wave: u32) -> f64 {
+        (self.enemy_rate + self.enemy_rate_per_wave * (wave - 1) as f64).min(self.max_enemy_rate)
+    }
+
+    /// Whether a boss appears at the end of `wave`.
+    pub fn has_boss_after(&self, wave: u32) -> bool {
+        self.boss_every > 0 && wave % self.boss_every == 0
+    }
+
+    /// The range of asteroid speeds during `wave`.
+    pub fn speeds_for(&self, wave: u32) -> (f64, f64) {
+        let bonus = self.speed_per_wave * (wave - 1) as f64;
+        (self.min_speed + bonus, self.max_speed + bonus)
+    }
+}
+
+/// Reads every entry of `section` as a list of `count` numbers, along with the
+/// entry it came from.
+fn entries<'a>(config: &'a Config, section: &str, count: usize)
+               -> Result<Vec<(&'a Entry, Vec<f64>)>, ParseError> {
+    config.entries().iter()
+        .filter(|entry| entry.section == section)
+        .map(|entry| numbers(entry, count).map(|values| (entry, values)))
+        .collect()
+}
+
+/// Whether `value` may be used as a duration or a speed.
+fn is_amount(value: f64) -> bool {
+    value >= 0.0 && value.is_finite()
+}
+
+fn entry_error(entry: &Entry, message: &str) -> ParseError {
+    ParseError {
+        line: entry.line,
+        message: message.to_string(),
+    }
+}
+
+fn numbers(entry: &Entry, count: usize) -> Result<Vec<f64>, ParseError> {
+    let values: Result<Vec<f64>, _> = entry.value.split_whitespace()
+        .map(|value| value.parse())
+        .collect();
+
+    match values {
+        Ok(ref values) if values.len() == count => Ok(values.clone()),
+        _ => Err(ParseError {
+            line: entry.line,
+            message: format!("expected {} numbers for `{}`", count, entry.key),
+        }),
+    }
+}
+
+/// Picks the index of an item at random, proportionally to its weight.
+fn pick_weighted<T, F>(items: &[T], weight: F) -> usize
+where F: Fn(&T) -> f64 {
+    let total: f64 = items.iter().map(|item| weight(item).max(0.0)).sum();
+    let mut roll = ::phi::rng::random() * total;
+
+    for (i, item) in items.iter().enumerate() {
+        roll -= weight(item).max(0.0);
+        if roll < 0.0 {
+            return i;
+        }
+    }
+
+    items.len() - 1
+}
+
+
+/// What happened to an asteroid which was hit.
+#[derive(Clone, Copy, Debug)]
+pub struct Hit {
+    pub destroyed: bool,
+    /// The side of the asteroid, in pixels
+    pub side: f64,
+    /// Where the asteroid was hit
+    pub rect: Rectangle,
+}
+
+/// Every asteroid on screen, and the schedule deciding when new ones appear.
+#[derive(Clone)]
+pub struct AsteroidField {
+    difficulty: Difficulty,
+    asteroids: Vec<Asteroid>,
+    explosions: Vec<Explosion>,
+    /// Frames shared by all asteroids
+    sprite: AnimatedSprite,
+    /// Frames shared by all explosions
+    explosion_sprite: AnimatedSprite,
+    /// Seconds since the field started
+    time: f64,
+    /// Asteroids owed by the spawn rate. One is spawned whenever it reaches 1.
+    pending: f64,
+    /// Whether the clock and spawning are stopped, e.g. during a boss fight
+    held: bool,
+}
+
+impl AsteroidField {
+    pub fn new(phi: &mut Phi) -> PhiResult<AsteroidField> {
+        let sprite = try!(Asteroid::get_sprite(phi, 15.0));
+
+        Ok(AsteroidField {
+            difficulty: Difficulty::load(),
+            asteroids: (0..POOL_CAPACITY).map(|_| Asteroid::new(sprite.clone())).collect(),
+            explosions: Vec::new(),
+            sprite: sprite,
+            explosion_sprite: try!(Explosion::get_sprite(phi)),
+            time: 0.0,
+            pending: 0.0,
+            held: false,
+        })
+    }
+
+    /// The wave currently being played, starting at 1.
+    pub fn wave(&self) -> u32 {
+        self.difficulty.wave_at(self.time)
+    }
+
+    /// The pacing of the field, shared with whatever else spawns in waves.
+    pub fn difficulty(&self) -> &Difficulty {
+        &self.difficulty
+    }
+
+    /// Whether the field is calm between two waves.
+    pub fn is_break(&self) -> bool {
+        self.difficulty.is_break_at(self.time)
+    }
+
+    /// Stops or resumes the wave clock. While held, asteroids on screen keep
+    /// moving, but no new ones appear.
+    pub fn set_held(&mut self, held: bool) {
+        self.held = held;
+    }
+
+    pub fn update(&mut self, phi: &mut Phi, dt: f64) {
+        if !self.held {
+            self.time += dt;
+        }
+
+        let (_, win_h) = phi.output_size();
+        for asteroid in self.asteroids.iter_mut().filter(|a| a.is_alive()) {
+            asteroid.update(dt, win_h);
+        }
+
+        for explosion in &mut self.explosions {
+            explosion.update(dt);
+        }
+        self.explosions.retain(|explosion| !explosion.is_finished());
+
+        if self.held || self.is_break() {
+            self.pending = 0.0;
+            return;
+        }
+
+        self.pending += self.difficulty.rate_for(self.wave()) * dt;
+
+        while self.pending >= 1.0 {
+            self.pending -= 1.0;
+            self.spawn(phi);
+        }
+    }
+
+    /// Sends `count` asteroids at once, e.g. when a level asks for it. The
+    /// difficulty still limits how many may be on screen.
+    pub fn burst(&mut self, phi: &mut Phi, count: u32) {
+        for _ in 0..count {
+            self.spawn(phi);
+        }
+    }
+
+    /// Sends a new asteroid from the right edge of the screen, unless there
+    /// already are as many as the difficulty allows.
+    fn spawn(&mut self, phi: &mut Phi) {
+        if self.is_full() {
+            return;
+        }
+
+        let (win_w, win_h) = phi.output_size();
+        let wave = self.wave();
+
+        let size = self.difficulty.sizes[pick_weighted(&self.difficulty.sizes, |s| s.weight)].clone();
+        let band = self.difficulty.bands[pick_weighted(&self.difficulty.bands, |b| b.weight)].clone();
+        let (min_speed, max_speed) = self.difficulty.speeds_for(wave);
+
+        // Keep the asteroid inside of the screen, even if the band is thinner
+        // than the asteroid.
+        let top = (band.top * win_h).max(0.0);
+        let bottom = (band.bottom * win_h - size.side).max(top).min(win_h - size.side);
+        let y = top + ::phi::rng::random() * (bottom - top).max(0.0);
+
+        let rect = Rectangle {
+            x: win_w,
+            y: y,
+            w: size.side,
+            h: size.side,
+        };
+        let vel = min_speed + ::phi::rng::random() * (max_speed - min_speed);
+
+        self.revive(rect, (-vel, 0.0), size.hp);
+    }
+
+    /// Whether there already are as many asteroids on screen as the
+    /// difficulty allows.
+    fn is_full(&self) -> bool {
+        self.asteroids.iter().filter(|a| a.is_alive()).count() >= self.difficulty.max_asteroids
+    }
+
+    /// Brings a dead asteroid of the pool back to life, creating a new one
+    /// only if all of them are in use. Nothing appears if the field is full.
+    fn revive(&mut self, rect: Rectangle, vel: (f64, f64), hp: u32) {
+        if self.is_full() {
+            return;
+        }
+
+        // set animation fps in [10.0, 30.0]
+        let fps = ::phi::rng::random() * 20.0 + 10.0;
+
+        if let Some(asteroid) = self.asteroids.iter_mut().find(|a| !a.is_alive()) {
+            asteroid.reset(rect, vel, fps, hp);
+            return;
+        }
+
+        let mut asteroid = Asteroid::new(self.sprite.clone());
+        asteroid.reset(rect, vel, fps, hp);
+        self.asteroids.push(asteroid);
+    }
+
+    /// Damages the first asteroid colliding with an object occupying `rect`,
+    /// whose solid pixels are `mask` if known. A destroyed asteroid explodes,
+    /// and may split into fragments.
+    pub fn hit(&mut self, rect: Rectangle, mask: Option<&CollisionMask>,
+               damage: u32) -> Option<Hit> {
+        let index = match self.asteroids.iter()
+            .position(|a| a.is_alive() && a.collides_with(rect, mask)) {
+            Some(index) => index,
+            None => return None,
+        };
+
+        let destroyed = self.asteroids[index].damage(damage);
+        let (rect, vel) = (self.asteroids[index].rect, self.asteroids[index].vel);
+
+        if destroyed {
+            self.explode(rect, (vel.0 * 0.5, vel.1 * 0.5));
+
+            if rect.w >= self.difficulty.min_split_side &&
+                ::phi::rng::random() < self.difficulty.split_chance {
+                self.split(rect, vel);
+            }
+        }
+
+        Some(Hit {
+            destroyed: destroyed,
+            side: rect.w,
+            rect: rect,
+        })
+    }
+
+    /// Destroys every asteroid on screen at once, without splitting them,
+    /// e.g. for a smart bomb.
+    pub fn destroy_all(&mut self) -> Vec<Hit> {
+        let mut hits = Vec::new();
+
+        for asteroid in self.asteroids.iter_mut().filter(|a| a.is_alive()) {
+            asteroid.kill();
+            hits.push(Hit {
+                destroyed: true,
+                side: asteroid.rect.w,
+                rect: asteroid.rect,
+            });
+        }
+
+        for hit in &hits {
+            self.explode(hit.rect, (0.0, 0.0));
+        }
+
+        hits
+    }
+
+    /// Plays an explosion covering `rect`, drifting at `vel`. Also used for
+    /// whatever else gets destroyed among the asteroids.
+    pub fn explode(&mut self, rect: Rectangle, vel: (f64, f64)) {
+        self.explosions.push(Explosion::new(self.explosion_sprite.clone(), rect, vel));
+    }
+
+    /// Breaks an asteroid which occupied `rect` into smaller, faster fragments
+    /// flying apart vertically, as many as the field has room for.
+    fn split(&mut self, rect: Rectangle, vel: (f64, f64)) {
+        let count = self.difficulty.fragments.min(MAX_FRAGMENTS);
+        if count == 0 {
+            return;
+        }
+
+        let side = rect.w / 2.0;
+        // Fragments are weaker, but always take at least one hit.
+        let hp = self.difficulty.sizes.iter()
+            .filter(|size| size.side <= side)
+            .map(|size| size.hp)
+            .max()
+            .unwrap_or(1);
+
+        for i in 0..count {
+            // Spread evenly in [-1, 1], with some randomness so that fragments
+            // of different asteroids do not all look alike.
+            let spread = if count == 1 { 0.0 }
+                else { (i as f64 / (count - 1) as f64) * 2.0 - 1.0 };
+            let jitter = ::phi::rng::random() * 0.5 - 0.25;
+
+            let fragment_rect = Rectangle {
+                x: rect.x + (rect.w - side) / 2.0,
+                y: rect.y + (rect.h - side) / 2.0 + spread * side / 2.0,
+                w: side,
+                h: side,
+            };
+            let fragment_vel = (
+                vel.0 * self.difficulty.fragment_speed,
+                vel.1 + (spread + jitter) * self.difficulty.fragment_spread,
+            );
+
+            self.revive(fragment_rect, fragment_vel, hp);
+        }
+    }
+
+    pub fn render(&self, phi: &mut Phi) {
+        for asteroid in self.asteroids.iter().filter(|a| a.is_alive()) {
+            asteroid.render(phi);
+        }
+
+        for explosion in &self.explosions {
+            explosion.render(phi);
+        }
+    }
+
+    /// Shows the hitbox and velocity of every asteroid on screen.
+    pub fn debug(&self, overlay: &mut DebugOverlay) {
+        let alive: Vec<&Asteroid> = self.asteroids.iter().filter(|a| a.is_alive()).collect();
+
+        for asteroid in &alive {
+            overlay.rect(asteroid.hitbox(), HITBOX_COLOR);
+            overlay.velocity(asteroid.rect, asteroid.vel);
+        }
+
+        overlay.count("asteroids", alive.len());
+        overlay.count("asteroid pool", self.asteroids.len());
+        overlay.count("explosions", self.explosions.len());
+    }
+
+    /// Writes the wave clock and every asteroid on screen to the `asteroids`
+    /// section of a save file. Explosions are left out.
+    pub fn save(&self, config: &mut Config) {
+        config.set("asteroids", "field", Line::new().word(self.time).word(self.pending).flag(self.held));
+
+        for asteroid in self.asteroids.iter().filter(|a| a.is_alive()) {
+            config.add("asteroids", "asteroid", asteroid.save());
+        }
+    }
+
+    /// Restores what `save` wrote into a new field.
+    pub fn load(&mut self, config: &Config) -> Result<(), ParseError> {
+        for entry in config.entries().iter().filter(|entry| entry.section == "asteroids") {
+            let mut fields = Fields::new(entry);
+
+            match &entry.key[..] {
+                "field" => {
+                    self.time = try!(fields.word());
+                    self.pending = try!(fields.word());
+                    self.held = try!(fields.flag());
+                },
+                "asteroid" => {
+                    let mut asteroid = Asteroid::new(self.sprite.clone());
+                    try!(asteroid.load(&mut fields));
+
+                    match self.asteroids.iter().position(|a| !a.is_alive()) {
+                        Some(index) => self.asteroids[index] = asteroid,
+                        None => self.asteroids.push(asteroid),
+                    }
+                },
+                _ => return Err(fields.error("unknown entry")),
+            }
+
+            try!(fields.end());
+        }
+
+        Ok(())
+    }
+
+    /// The asteroids currently on screen.
+    pub fn alive_mut<'a>(&'a mut self) -> Box<Iterator<Item = &'a mut Asteroid> + 'a> {
+        Box::new(self.asteroids.iter_mut().filter(|a| a.is_alive()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Difficulty, ParseError> {
+        Difficulty::from_config(&Config::parse(&format!("version = 1\n{}", text)).unwrap())
+    }
+
+    /// The line of the error reported for `text`, which comes after the
+    /// version on line 1.
+    fn error_line(text: &str) -> Option<usize> {
+        parse(text).err().map(|err| err.line)
+    }
+
+    #[test]
+    fn reads_values_and_keeps_defaults() {
+        let difficulty = parse("[waves]\nbreak = 0\n[speed]\nmin = 10\nmax = 10\n\
+                                [sizes]\nsmall = 32 2 1\nlarge = 128 1 5\n\
+                                [bands]\ntop = 0 0.5 1\n[fragments]\ncount = 8\n").unwrap();
+
+        assert_eq!(difficulty.wave_break, 0.0);
+        assert_eq!((difficulty.min_speed, difficulty.max_speed), (10.0, 10.0));
+        assert_eq!(difficulty.wave_length, Difficulty::new().wave_length);
+        assert_eq!(difficulty.sizes, vec![
+            SizeClass { side: 32.0, weight: 2.0, hp: 1 },
+            SizeClass { side: 128.0, weight: 1.0, hp: 5 },
+        ]);
+        assert_eq!(difficulty.bands, vec![Band { top: 0.0, bottom: 0.5, weight: 1.0 }]);
+        assert_eq!(difficulty.fragments, 8);
+
+        assert_eq!(parse(""), Ok(Difficulty::new()));
+    }
+
+    #[test]
+    fn requires_the_version() {
+        assert!(Difficulty::from_config(&Config::parse("").unwrap()).is_err());
+        assert_eq!(Difficulty::from_config(&Config::parse("version = 2\n").unwrap()).unwrap_err().line, 1);
+    }
+
+    #[test]
+    fn rejects_invalid_durations_and_speeds() {
+        assert_eq!(error_line("[waves]\nlength = 0\n"), Some(3));
+        assert_eq!(error_line("[waves]\nlength = inf\n"), Some(3));
+        assert_eq!(error_line("[waves]\nbreak = -1\n"), Some(3));
+        assert_eq!(error_line("[waves]\nbreak = NaN\n"), Some(3));
+        assert_eq!(error_line("[speed]\nmin = -5\n"), Some(3));
+        assert_eq!(error_line("[speed]\nper_wave = inf\n"), Some(3));
+        assert_eq!(error_line("[speed]\nmax = 20\nmin = 30\n"), Some(3));
+        assert_eq!(error_line("[speed]\nmin = 200\n"), Some(3));
+    }
+
+    #[test]
+    fn rejects_invalid_sizes_and_bands() {
+        assert_eq!(error_line("[sizes]\nsmall = 64 1 2\nbroken = 64 1 0\n"), Some(4));
+        assert_eq!(error_line("[sizes]\nsmall = 64 1 1.5\n"), Some(3));
+        assert_eq!(error_line("[sizes]\nsmall = 64 1 -2\n"), Some(3));
+        assert_eq!(error_line("[sizes]\nsmall = 0 1 2\n"), Some(3));
+        assert_eq!(error_line("[sizes]\nsmall = 64 1\n"), Some(3));
+
+        assert_eq!(error_line("[bands]\nflat = 0.5 0.5 1\n"), Some(3));
+        assert_eq!(error_line("[bands]\nupside_down = 0.7 0.3 1\n"), Some(3));
+        assert_eq!(error_line("[bands]\nbelow = 0.5 1.5 1\n"), Some(3));
+        assert_eq!(error_line("[bands]\nabove = -0.5 0.5 1\n"), Some(3));
+    }
+
+    #[test]
+    fn limits_fragments() {
+        assert_eq!(error_line("[fragments]\ncount = 9\n"), Some(3));
+        assert_eq!(parse("[fragments]\ncount = 0\n").unwrap().fragments, 0);
+    }
+}
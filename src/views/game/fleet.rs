@@ -0,0 +1,238 @@
+use ::phi::Phi;
+use ::phi::collision::CollisionMask;
+use ::phi::config::{Config, Fields, Line, ParseError};
+use ::phi::data::Rectangle;
+use ::phi::error::PhiResult;
+use ::phi::debug::{DebugOverlay, HITBOX_COLOR};
+use ::phi::gfx::{AnimatedSprite, Sprite};
+use ::views::game::enemy::{Enemy, EnemyKind};
+use ::views::game::field::Difficulty;
+use ::views::game::pattern::Pattern;
+use ::views::game::weapons::ProjectilePool;
+
+const ENEMY_BULLET_PATH: &'static str = "assets/enemy_bullet.png";
+
+/// What happened to an enemy which was hit.
+#[derive(Clone, Copy, Debug)]
+pub struct EnemyHit {
+    pub destroyed: bool,
+    /// Points for destroying it, before the combo multiplier
+    pub points: u64,
+    /// Where the enemy was hit
+    pub rect: Rectangle,
+    pub vel: (f64, f64),
+}
+
+/// Every enemy ship on screen and their shots.
+#[derive(Clone)]
+pub struct EnemyFleet {
+    kinds: Vec<EnemyKind>,
+    /// The frames of each kind, in the same order as `kinds`
+    sprites: Vec<AnimatedSprite>,
+    enemies: Vec<Enemy>,
+    pub shots: ProjectilePool,
+    /// Enemies owed by the spawn rate. One is spawned whenever it reaches 1.
+    pending: f64,
+}
+
+impl EnemyFleet {
+    pub fn new(phi: &mut Phi) -> PhiResult<EnemyFleet> {
+        let kinds = EnemyKind::all();
+        let sprites = try!(EnemyKind::get_sprites(phi, &kinds));
+        let shot_sprite = try!(Sprite::load(&mut phi.renderer, ENEMY_BULLET_PATH));
+
+        Ok(EnemyFleet {
+            kinds: kinds,
+            sprites: sprites,
+            enemies: Vec::new(),
+            shots: ProjectilePool::new(shot_sprite),
+            pending: 0.0,
+        })
+    }
+
+    /// Moves the enemies and lets them fire at `target`, the centre of the
+    /// player's ship, if `can_target`. New enemies appear at the pace given
+    /// by `difficulty` for `wave`, unless it is `calm` between two waves.
+    pub fn update(&mut self, dt: f64, difficulty: &Difficulty, wave: u32, calm: bool,
+                  target: (f64, f64), can_target: bool, screen: Rectangle) {
+        for enemy in &mut self.enemies {
+            let kind = &self.kinds[enemy.kind];
+            enemy.update(dt, target, screen);
+
+            enemy.cooldown = (enemy.cooldown - dt).max(0.0);
+
+            // Only fire when fully on screen, and never at a destroyed ship.
+            let on_screen = enemy.rect.x + enemy.rect.w <= screen.w;
+            if !enemy.is_alive() || !can_target || !on_screen ||
+                kind.fire_rate <= 0.0 || enemy.cooldown > 0.0 {
+                continue;
+            }
+
+            let (x, y) = (enemy.rect.x, enemy.rect.y + enemy.rect.h / 2.0);
+            let vel = if kind.aimed {
+                let (dx, dy) = (target.0 - x, target.1 - y);
+                let length = (dx * dx + dy * dy).sqrt().max(1.0);
+                (dx / length * kind.shot_speed, dy / length * kind.shot_speed)
+            } else {
+                (-kind.shot_speed, 0.0)
+            };
+
+            self.shots.spawn(x, y, vel);
+            enemy.cooldown = 1.0 / kind.fire_rate;
+        }
+        self.enemies.retain(|enemy| enemy.is_alive());
+
+        self.shots.update(dt, screen);
+
+        if calm {
+            self.pending = 0.0;
+            return;
+        }
+
+        self.pending += difficulty.enemy_rate_for(wave) * dt;
+
+        while self.pending >= 1.0 {
+            self.pending -= 1.0;
+
+            if self.enemies.len() < difficulty.max_enemies {
+                self.spawn(wave, screen);
+            }
+        }
+    }
+
+    /// Sends an enemy of a kind available during `wave` from the right edge
+    /// of the screen.
+    pub fn spawn(&mut self, wave: u32, screen: Rectangle) {
+        let available: Vec<usize> = (0..self.kinds.len())
+            .filter(|&i| self.kinds[i].first_wave <= wave)
+            .collect();
+
+        if available.is_empty() {
+            return;
+        }
+
+        let total: f64 = available.iter().map(|&i| self.kinds[i].weight.max(0.0)).sum();
+        let mut roll = ::phi::rng::random() * total;
+        let mut kind = available[available.len() - 1];
+
+        for &i in &available {
+            roll -= self.kinds[i].weight.max(0.0);
+            if roll < 0.0 {
+                kind = i;
+                break;
+            }
+        }
+
+        // Keep some room above and below for patterns which weave around.
+        let y = 0.1 + ::phi::rng::random() * 0.8;
+        self.spawn_kind(kind, y, None, screen);
+    }
+
+    /// Sends an enemy of the given kind (its index in `EnemyKind::all()`) from
+    /// the right edge of the screen, at a height given as a proportion of the
+    /// screen's. It follows `pattern`, or that of its kind if `None`.
+    pub fn spawn_kind(&mut self, kind: usize, y: f64, pattern: Option<Pattern>, screen: Rectangle) {
+        let h = self.sprites[kind].current_sprite().size().1;
+        let y = (y * screen.h - h / 2.0).max(0.0).min(screen.h - h);
+        let pattern = pattern.unwrap_or_else(|| self.kinds[kind].pattern.clone());
+
+        let enemy = Enemy::new(kind, &self.kinds[kind], self.sprites[kind].clone(),
+                               pattern, screen.w, y);
+        self.enemies.push(enemy);
+    }
+
+    /// Damages the first enemy colliding with an object occupying `rect`,
+    /// whose solid pixels are `mask` if known.
+    pub fn hit(&mut self, rect: Rectangle, mask: Option<&CollisionMask>,
+               damage: u32) -> Option<EnemyHit> {
+        let kinds = &self.kinds;
+
+        self.enemies.iter_mut()
+            .find(|enemy| enemy.is_alive() && enemy.collides_with(rect, mask))
+            .map(|enemy| EnemyHit {
+                destroyed: enemy.damage(damage),
+                points: kinds[enemy.kind].points,
+                rect: enemy.rect,
+                vel: enemy.vel,
+            })
+    }
+
+    /// Shows the hitbox and velocity of every enemy on screen and their
+    /// shots.
+    pub fn debug(&self, overlay: &mut DebugOverlay) {
+        for enemy in self.enemies.iter().filter(|e| e.is_alive()) {
+            overlay.rect(enemy.rect, HITBOX_COLOR);
+            overlay.velocity(enemy.rect, enemy.vel);
+        }
+
+        overlay.count("enemies", self.enemies.len());
+        self.shots.debug(overlay, "enemy shots");
+    }
+
+    /// Writes every enemy on screen and their shots to the `enemies` section
+    /// of a save file.
+    pub fn save(&self, config: &mut Config) {
+        config.set("enemies", "fleet", Line::new().word(self.pending));
+
+        for enemy in self.enemies.iter().filter(|e| e.is_alive()) {
+            config.add("enemies", "enemy", enemy.save(&self.kinds[enemy.kind].pattern));
+        }
+
+        self.shots.save(config, "enemies", "shot");
+    }
+
+    /// Restores what `save` wrote into a new fleet.
+    pub fn load(&mut self, config: &Config) -> Result<(), ParseError> {
+        for entry in config.entries().iter().filter(|entry| entry.section == "enemies") {
+            let mut fields = Fields::new(entry);
+
+            match &entry.key[..] {
+                "fleet" => self.pending = try!(fields.word()),
+                "enemy" => {
+                    let kind = try!(fields.word::<usize>());
+                    if kind >= self.kinds.len() {
+                        return Err(fields.error("invalid kind of enemy"));
+                    }
+
+                    let mut enemy = Enemy::new(kind, &self.kinds[kind], self.sprites[kind].clone(),
+                                               self.kinds[kind].pattern.clone(), 0.0, 0.0);
+                    try!(enemy.load(&mut fields));
+                    self.enemies.push(enemy);
+                },
+                "shot" => try!(self.shots.load(&mut fields)),
+                _ => return Err(fields.error("unknown entry")),
+            }
+
+            try!(fields.end());
+        }
+
+        Ok(())
+    }
+
+    /// Destroys every enemy on screen and their shots at once, e.g. for a
+    /// smart bomb.
+    pub fn destroy_all(&mut self) -> Vec<EnemyHit> {
+        let kinds = &self.kinds;
+        let hits = self.enemies.iter()
+            .filter(|enemy| enemy.is_alive())
+            .map(|enemy| EnemyHit {
+                destroyed: true,
+                points: kinds[enemy.kind].points,
+                rect: enemy.rect,
+                vel: enemy.vel,
+            })
+            .collect();
+
+        self.enemies.clear();
+        self.shots.clear();
+        hits
+    }
+
+    pub fn render(&self, phi: &mut Phi) {
+        for enemy in self.enemies.iter().filter(|e| e.is_alive()) {
+            enemy.render(phi);
+        }
+
+        self.shots.render(phi);
+    }
+}
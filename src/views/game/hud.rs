@@ -0,0 +1,175 @@
+use ::phi::Phi;
+use ::phi::data::Rectangle;
+use ::phi::gfx::{CopySprite, Sprite};
+use ::sdl2::pixels::Color;
+use ::views::game::player::Player;
+use ::views::shared::FONT_PATH;
+
+const HUD_FONT_SIZE: i32 = 28;
+/// Pixels between the HUD and the edges of the screen.
+const HUD_MARGIN: f64 = 16.0;
+
+/// A corner of the screen to which some text is attached. Positions are
+/// computed every frame, so the HUD follows the window when it is resized.
+#[derive(Clone, Copy)]
+enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// A line of text which is only rendered again when it changes.
+struct HudText {
+    content: String,
+    color: Color,
+    sprite: Option<Sprite>,
+}
+
+impl HudText {
+    fn new() -> HudText {
+        HudText {
+            content: String::new(),
+            color: Color::RGB(255, 255, 255),
+            sprite: None,
+        }
+    }
+
+    fn set(&mut self, phi: &mut Phi, content: String, color: Color) {
+        if self.sprite.is_some() && self.content == content && self.color == color {
+            return;
+        }
+
+        self.sprite = phi.ttf_str_sprite(&content, FONT_PATH, HUD_FONT_SIZE, color);
+        self.content = content;
+        self.color = color;
+    }
+
+    /// Draws the text in `corner`, on the given `line` counted from the edge
+    /// of the screen.
+    fn render(&self, phi: &mut Phi, corner: Corner, line: usize) {
+        let sprite = match self.sprite {
+            Some(ref sprite) => sprite,
+            None => return,
+        };
+
+        let (win_w, win_h) = phi.output_size();
+        let (w, h) = sprite.size();
+        let offset = line as f64 * h;
+
+        let x = match corner {
+            Corner::TopLeft | Corner::BottomLeft => HUD_MARGIN,
+            Corner::TopRight | Corner::BottomRight => win_w - w - HUD_MARGIN,
+        };
+        let y = match corner {
+            Corner::TopLeft | Corner::TopRight => HUD_MARGIN + offset,
+            Corner::BottomLeft | Corner::BottomRight => win_h - h - HUD_MARGIN - offset,
+        };
+
+        phi.renderer.copy_sprite(sprite, Rectangle { x: x, y: y, w: w, h: h });
+    }
+}
+
+/// What is shown of each player: the score in a top corner, with the combo
+/// multiplier and the power-ups running below it, and the lives left in a
+/// bottom corner.
+struct PlayerHud {
+    score: HudText,
+    multiplier: HudText,
+    lives: HudText,
+    effects: Vec<HudText>,
+}
+
+impl PlayerHud {
+    fn new() -> PlayerHud {
+        PlayerHud {
+            score: HudText::new(),
+            multiplier: HudText::new(),
+            lives: HudText::new(),
+            effects: Vec::new(),
+        }
+    }
+
+    /// Draws the player's information in the top and bottom corners of the
+    /// given side, leaving the bottom line for the stage on the right.
+    fn render(&mut self, phi: &mut Phi, player: &Player, name: &str, left: bool) {
+        let white = Color::RGB(255, 255, 255);
+        let score = &player.score;
+        let (top, bottom, bottom_line) =
+            if left { (Corner::TopLeft, Corner::BottomLeft, 0) }
+            else { (Corner::TopRight, Corner::BottomRight, 1) };
+
+        // The multiplier fades from yellow to white as the combo runs out, in
+        // a few steps so that it is not rendered again every frame.
+        let fade = ((score.combo_left() * 8.0).round() * 31.0) as u8;
+        let multiplier_color =
+            if score.multiplier() > 1 { Color::RGB(255, 255, 255 - fade) }
+            else { white };
+
+        self.score.set(phi, format!("{}{}", name, score.points()), white);
+        self.multiplier.set(phi, format!("x{}", score.multiplier()), multiplier_color);
+        self.lives.set(phi, format!("Lives: {}", player.ship.lives), white);
+
+        self.score.render(phi, top, 0);
+        self.multiplier.render(phi, top, 1);
+        self.lives.render(phi, bottom, bottom_line);
+
+        // One line per power-up, with the whole seconds left, which turns red
+        // when about to run out.
+        let effects = player.ship.effects();
+        while self.effects.len() < effects.len() {
+            self.effects.push(HudText::new());
+        }
+        self.effects.truncate(effects.len());
+
+        for (i, effect) in effects.iter().enumerate() {
+            let color =
+                if effect.left > 3.0 { Color::RGB(120, 220, 255) }
+                else { Color::RGB(255, 90, 90) };
+
+            self.effects[i].set(phi, format!("{} {}", effect.kind.name(), effect.left.ceil()), color);
+            self.effects[i].render(phi, top, i + 2);
+        }
+    }
+}
+
+/// The information drawn over the game: that of the first player on the left
+/// of the screen, that of the second one on the right, or a prompt for them to
+/// join, and the current stage of the game, e.g. its wave, in the bottom right
+/// corner.
+pub struct Hud {
+    players: [PlayerHud; 2],
+    stage: HudText,
+    prompt: HudText,
+}
+
+impl Hud {
+    pub fn new() -> Hud {
+        Hud {
+            players: [PlayerHud::new(), PlayerHud::new()],
+            stage: HudText::new(),
+            prompt: HudText::new(),
+        }
+    }
+
+    /// Draws the HUD of the players, along with `prompt` if there is only one.
+    pub fn render(&mut self, phi: &mut Phi, players: &[Player], stage: &str, prompt: Option<&str>) {
+        let white = Color::RGB(255, 255, 255);
+
+        if players.len() == 1 {
+            self.players[0].render(phi, &players[0], "Score: ", true);
+
+            if let Some(prompt) = prompt {
+                self.prompt.set(phi, prompt.to_string(), Color::RGB(255, 220, 120));
+                self.prompt.render(phi, Corner::BottomRight, 1);
+            }
+        } else {
+            for (i, player) in players.iter().enumerate().take(2) {
+                self.players[i].render(phi, player, &format!("P{}: ", i + 1), i == 0);
+            }
+        }
+
+        self.stage.set(phi, stage.to_string(), white);
+        self.stage.render(phi, Corner::BottomRight, 0);
+    }
+}
@@ -0,0 +1,621 @@
+use ::phi::config::{Config, ConfigError, Entry, ParseError};
+use ::phi::vfs;
+use ::std::io;
+use ::std::path::{Path, PathBuf};
+use ::views::game::enemy::EnemyKind;
+use ::views::game::pattern::Pattern;
+
+/// Where level files are looked for.
+pub const LEVELS_DIR: &'static str = "assets/levels";
+const LEVEL_EXTENSION: &'static str = "cfg";
+const LEVEL_VERSION: u32 = 1;
+/// The most asteroids an event may bring at once.
+const MAX_ASTEROIDS: u32 = 100;
+
+/// Something happening at a given time of a level.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// An enemy of the given kind (its index in `EnemyKind::all()`) enters
+    /// at a height given as a proportion of the screen's, following its own
+    /// pattern unless another one is given.
+    Enemy { kind: usize, y: f64, pattern: Option<Pattern> },
+    /// Asteroids appear at once.
+    Asteroids(u32),
+    /// The background scrolls this many times faster than usual.
+    Scroll(f64),
+    /// A boss appears. The level's clock stops until it is defeated.
+    Boss,
+    /// The level is complete.
+    End,
+}
+
+/// A timeline of events, read from a file such as:
+///
+/// ```text
+/// version = 1
+/// name = Outer belt
+/// # Whether the usual waves of asteroids and enemies run too
+/// waves = off
+///
+/// [events]
+/// # seconds = event
+/// 2 = enemy scout 0.3
+/// 4 = enemy weaver 0.5 pattern straight -120 0 + sine 80 2
+/// 10 = asteroids 5
+/// 12 = scroll 2
+/// 30 = boss
+/// 40 = end
+/// ```
+///
+/// Patterns are made of `straight X Y`, `sine AMPLITUDE PERIOD`,
+/// `dive SPEED TURN`, `strafe SPEED` and `stop`, added with `+` and chained
+/// with `then SECONDS`. A pattern in `( ` and ` )` is added or chained as a
+/// whole.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Level {
+    pub name: String,
+    /// Whether the endless waves of the asteroid field run during the level
+    pub waves: bool,
+    /// Events ordered by time, in seconds
+    events: Vec<(f64, Event)>,
+}
+
+impl Level {
+    /// An empty level, e.g. to start editing a new file.
+    pub fn new(name: &str) -> Level {
+        Level {
+            name: name.to_string(),
+            waves: false,
+            events: Vec::new(),
+        }
+    }
+
+    /// Every event with its time in seconds, ordered by time.
+    pub fn events(&self) -> &[(f64, Event)] {
+        &self.events
+    }
+
+    /// Adds an event at `time`, after those already happening then.
+    pub fn add(&mut self, time: f64, event: Event) {
+        let index = self.events.iter().position(|&(t, _)| t > time).unwrap_or(self.events.len());
+        self.events.insert(index, (time, event));
+    }
+
+    /// Removes the event at `index` of `events()`.
+    pub fn remove(&mut self, index: usize) -> (f64, Event) {
+        self.events.remove(index)
+    }
+
+    /// Seconds until the last event.
+    pub fn duration(&self) -> f64 {
+        self.events.last().map_or(0.0, |&(time, _)| time)
+    }
+
+    /// How fast the background scrolls at `time`, given the `scroll` events
+    /// before it.
+    pub fn scroll_at(&self, time: f64) -> f64 {
+        self.events.iter()
+            .take_while(|&&(t, _)| t < time)
+            .fold(1.0, |factor, &(_, ref event)| match *event {
+                Event::Scroll(new) => new,
+                _ => factor,
+            })
+    }
+
+    /// The level in the format read by `from_config`. Comments of the file it
+    /// was read from are lost.
+    pub fn to_config(&self) -> Config {
+        let mut config = Config::new();
+        config.set("", "version", LEVEL_VERSION);
+        config.set("", "name", &self.name);
+        config.set("", "waves", if self.waves { "on" } else { "off" });
+
+        for &(time, ref event) in &self.events {
+            config.add("events", &time.to_string(), format_event(event));
+        }
+
+        config
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        self.to_config().save(path)
+    }
+
+    /// A path in `LEVELS_DIR` for a new level file, numbered after the
+    /// existing ones so that it is listed last.
+    pub fn unused_path() -> PathBuf {
+        let count = Level::list().len();
+        (count + 1..).map(|n| Path::new(LEVELS_DIR).join(format!("{:02}-new-level.{}", n, LEVEL_EXTENSION)))
+            .find(|path| !vfs::exists(path))
+            .unwrap()
+    }
+
+    /// The level files found in `LEVELS_DIR`, whether shipped with the game
+    /// or saved since, in alphabetical order.
+    pub fn list() -> Vec<PathBuf> {
+        vfs::list(LEVELS_DIR).into_iter()
+            .filter(|path| path.extension().map_or(false, |ext| ext == LEVEL_EXTENSION))
+            .collect()
+    }
+
+    pub fn load(path: &Path) -> Result<Level, ConfigError> {
+        let config = try!(Config::load_asset(path));
+        Ok(try!(Level::from_config(&config)))
+    }
+
+    /// Reads and validates a level from a parsed configuration.
+    pub fn from_config(config: &Config) -> Result<Level, ParseError> {
+        match try!(config.parsed::<u32>("", "version")) {
+            Some(LEVEL_VERSION) => {},
+            _ => return Err(ParseError {
+                line: config.entry("", "version").map_or(0, |entry| entry.line),
+                message: format!("expected `version = {}`", LEVEL_VERSION),
+            }),
+        }
+
+        let waves = match config.entry("", "waves") {
+            None => false,
+            Some(entry) => match &entry.value[..] {
+                "on" => true,
+                "off" => false,
+                _ => return Err(error(entry, "expected `on` or `off`")),
+            },
+        };
+
+        let mut events = Vec::new();
+
+        for entry in config.entries().iter().filter(|entry| entry.section == "events") {
+            let time = match entry.key.parse::<f64>() {
+                Ok(time) if time >= 0.0 => time,
+                _ => return Err(error(entry, &format!("`{}` is not a time in seconds", entry.key))),
+            };
+
+            events.push((time, try!(parse_event(entry))));
+        }
+
+        // Events at the same time keep the order of the file.
+        events.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        Ok(Level {
+            name: config.get("", "name").unwrap_or("Untitled").to_string(),
+            waves: waves,
+            events: events,
+        })
+    }
+}
+
+fn error(entry: &Entry, message: &str) -> ParseError {
+    ParseError {
+        line: entry.line,
+        message: message.to_string(),
+    }
+}
+
+fn parse_event(entry: &Entry) -> Result<Event, ParseError> {
+    let tokens: Vec<&str> = entry.value.split_whitespace().collect();
+
+    let number = |i: usize| -> Result<f64, ParseError> {
+        tokens.get(i)
+            .and_then(|token| parse_number(token))
+            .ok_or_else(|| error(entry, &format!("expected a number after `{}`", tokens[i - 1])))
+    };
+
+    let expect_len = |len: usize| -> Result<(), ParseError> {
+        if tokens.len() > len {
+            Err(error(entry, &format!("unexpected `{}`", tokens[len])))
+        } else {
+            Ok(())
+        }
+    };
+
+    match tokens.get(0).map(|token| *token) {
+        Some("enemy") => {
+            let name = match tokens.get(1) {
+                Some(name) => *name,
+                None => return Err(error(entry, "expected the kind of enemy after `enemy`")),
+            };
+
+            let kinds = EnemyKind::all();
+            let kind = match kinds.iter().position(|kind| kind.name == name) {
+                Some(kind) => kind,
+                None => {
+                    let names: Vec<&str> = kinds.iter().map(|kind| kind.name).collect();
+                    return Err(error(entry, &format!("unknown enemy `{}`, expected one of: {}",
+                                                     name, names.join(", "))));
+                },
+            };
+
+            // An optional height, then an optional pattern
+            let mut next = 2;
+            let mut y = 0.5;
+            if let Some(value) = tokens.get(2).and_then(|token| token.parse::<f64>().ok()) {
+                if !value.is_finite() || value < 0.0 || value > 1.0 {
+                    return Err(error(entry, "the height of an enemy must be between 0 and 1"));
+                }
+                y = value;
+                next = 3;
+            }
+
+            let pattern = match tokens.get(next) {
+                None => None,
+                Some(&"pattern") => Some(try!(parse_pattern(&tokens[next + 1..])
+                    .map_err(|message| error(entry, &message)))),
+                Some(token) => return Err(error(entry, &format!("unexpected `{}`", token))),
+            };
+
+            Ok(Event::Enemy { kind: kind, y: y, pattern: pattern })
+        },
+
+        Some("asteroids") => {
+            let count = try!(number(1));
+            try!(expect_len(2));
+
+            if count < 1.0 || count.fract() != 0.0 {
+                return Err(error(entry, "expected a whole number of asteroids"));
+            }
+            if count > MAX_ASTEROIDS as f64 {
+                return Err(error(entry, &format!("at most {} asteroids may appear at once", MAX_ASTEROIDS)));
+            }
+            Ok(Event::Asteroids(count as u32))
+        },
+
+        Some("scroll") => {
+            let factor = try!(number(1));
+            try!(expect_len(2));
+
+            if factor < 0.0 {
+                return Err(error(entry, "the scrolling speed cannot be negative"));
+            }
+            Ok(Event::Scroll(factor))
+        },
+
+        Some("boss") => {
+            try!(expect_len(1));
+            Ok(Event::Boss)
+        },
+
+        Some("end") => {
+            try!(expect_len(1));
+            Ok(Event::End)
+        },
+
+        Some(command) => Err(error(entry, &format!(
+            "unknown event `{}`, expected enemy, asteroids, scroll, boss or end", command))),
+
+        None => Err(error(entry, "missing event")),
+    }
+}
+
+/// Reads a number, refusing `NaN` and infinities, which nothing can move by.
+fn parse_number(token: &str) -> Option<f64> {
+    token.parse::<f64>().ok().and_then(|number| if number.is_finite() { Some(number) } else { None })
+}
+
+/// Writes an event the way `parse_event` reads it.
+fn format_event(event: &Event) -> String {
+    match *event {
+        Event::Enemy { kind, y, ref pattern } => {
+            let name = EnemyKind::all()[kind].name;
+            match *pattern {
+                Some(ref pattern) => format!("enemy {} {} pattern {}", name, y, format_pattern(pattern)),
+                None => format!("enemy {} {}", name, y),
+            }
+        },
+        Event::Asteroids(count) => format!("asteroids {}", count),
+        Event::Scroll(factor) => format!("scroll {}", factor),
+        Event::Boss => "boss".to_string(),
+        Event::End => "end".to_string(),
+    }
+}
+
+/// Writes a pattern the way `parse_pattern` reads it.
+pub fn format_pattern(pattern: &Pattern) -> String {
+    match *pattern {
+        Pattern::Straight(x, y) => format!("straight {} {}", x, y),
+        Pattern::Sine { amplitude, period } => format!("sine {} {}", amplitude, period),
+        Pattern::Dive { speed, turn } => format!("dive {} {}", speed, turn),
+        Pattern::Strafe { speed } => format!("strafe {}", speed),
+        Pattern::Stop => "stop".to_string(),
+        Pattern::Sum(ref patterns) => patterns.iter()
+            .map(format_part)
+            .collect::<Vec<_>>()
+            .join(" + "),
+        Pattern::Sequence(ref steps) => {
+            let mut text = String::new();
+            for (i, &(ref step, duration)) in steps.iter().enumerate() {
+                text.push_str(&format_part(step));
+                if i + 1 < steps.len() {
+                    text.push_str(&format!(" then {} ", duration));
+                }
+            }
+            text
+        },
+    }
+}
+
+/// Writes a pattern summed with others or followed by others, in brackets
+/// if it is a sequence, whose own steps would be read as part of the outer
+/// pattern otherwise.
+fn format_part(pattern: &Pattern) -> String {
+    match *pattern {
+        Pattern::Sequence(_) => format!("( {} )", format_pattern(pattern)),
+        ref pattern => format_pattern(pattern),
+    }
+}
+
+/// Reads a pattern from its words, e.g. `straight -100 0 then 1.5 dive 320 2`
+/// or `( straight -100 0 then 2 stop ) + sine 80 2`.
+pub fn parse_pattern(tokens: &[&str]) -> Result<Pattern, String> {
+    let mut pos = 0;
+    let pattern = try!(parse_sequence(tokens, &mut pos));
+
+    match tokens.get(pos) {
+        Some(token) => Err(format!("expected `then` or `+`, found `{}`", token)),
+        None => Ok(pattern),
+    }
+}
+
+/// Reads sums chained with `then`, starting at `pos`, which is moved past
+/// them.
+fn parse_sequence(tokens: &[&str], pos: &mut usize) -> Result<Pattern, String> {
+    let mut steps = Vec::new();
+    let mut pattern = try!(parse_sum(tokens, pos));
+
+    while tokens.get(*pos) == Some(&"then") {
+        let duration = match tokens.get(*pos + 1).and_then(|token| parse_number(token)) {
+            Some(duration) if duration > 0.0 => duration,
+            _ => return Err("expected a duration in seconds after `then`".to_string()),
+        };
+
+        *pos += 2;
+        // Not `Pattern::then`, which would merge a sequence in brackets with
+        // this one
+        steps.push((pattern, duration));
+        pattern = try!(parse_sum(tokens, pos));
+    }
+
+    if steps.is_empty() {
+        return Ok(pattern);
+    }
+    steps.push((pattern, 0.0));
+    Ok(Pattern::Sequence(steps))
+}
+
+/// Reads movements added with `+`, starting at `pos`, which is moved past
+/// them.
+fn parse_sum(tokens: &[&str], pos: &mut usize) -> Result<Pattern, String> {
+    let mut pattern = try!(parse_movement(tokens, pos));
+
+    while tokens.get(*pos) == Some(&"+") {
+        *pos += 1;
+        pattern = pattern.with(try!(parse_movement(tokens, pos)));
+    }
+
+    Ok(pattern)
+}
+
+/// Reads a single movement, or a whole pattern in brackets.
+fn parse_movement(tokens: &[&str], pos: &mut usize) -> Result<Pattern, String> {
+    let name = match tokens.get(*pos) {
+        Some(name) => *name,
+        None => return Err("expected a movement: straight, sine, dive, strafe or stop".to_string()),
+    };
+
+    if name == "(" {
+        *pos += 1;
+        let pattern = try!(parse_sequence(tokens, pos));
+        if tokens.get(*pos) != Some(&")") {
+            return Err("expected `)` after the pattern in brackets".to_string());
+        }
+        *pos += 1;
+        return Ok(pattern);
+    }
+
+    let arity = match name {
+        "straight" | "sine" | "dive" => 2,
+        "strafe" => 1,
+        "stop" => 0,
+        _ => return Err(format!(
+            "unknown movement `{}`, expected straight, sine, dive, strafe or stop", name)),
+    };
+
+    let mut args = Vec::with_capacity(arity);
+    for i in 0..arity {
+        match tokens.get(*pos + 1 + i).and_then(|token| parse_number(token)) {
+            Some(arg) => args.push(arg),
+            None => return Err(format!("`{}` expects {} numbers", name, arity)),
+        }
+    }
+    *pos += 1 + arity;
+
+    Ok(match name {
+        "straight" => Pattern::Straight(args[0], args[1]),
+        "sine" => {
+            if args[1] <= 0.0 {
+                return Err("the period of `sine` must be positive".to_string());
+            }
+            Pattern::Sine { amplitude: args[0], period: args[1] }
+        },
+        "dive" => Pattern::Dive { speed: args[0], turn: args[1] },
+        "strafe" => Pattern::Strafe { speed: args[0] },
+        _ => Pattern::Stop,
+    })
+}
+
+
+/// Plays the events of a level as time goes by.
+#[derive(Clone)]
+pub struct LevelScript {
+    pub level: Level,
+    /// Seconds since the level started
+    time: f64,
+    /// The index of the next event to happen
+    next: usize,
+}
+
+impl LevelScript {
+    pub fn new(level: Level) -> LevelScript {
+        LevelScript::starting_at(level, 0.0)
+    }
+
+    /// Plays the level from `time` seconds in, e.g. to preview it. Earlier
+    /// events are skipped.
+    pub fn starting_at(level: Level, time: f64) -> LevelScript {
+        let next = level.events.iter().position(|&(t, _)| t >= time).unwrap_or(level.events.len());
+
+        LevelScript {
+            level: level,
+            // Just before, so that events at `time` happen on the first frame
+            time: time - 1e-6,
+            next: next,
+        }
+    }
+
+    /// Writes the level and how far it was played to the `level` and `level
+    /// events` sections of a save file, so that it resumes the same even if
+    /// its file changed since.
+    pub fn save(&self, config: &mut Config) {
+        for entry in self.level.to_config().entries() {
+            let section = if entry.section.is_empty() { "level".to_string() }
+                else { format!("level {}", entry.section) };
+            config.add(&section, &entry.key, &entry.value);
+        }
+
+        config.set("level", "time", self.time);
+        config.set("level", "next", self.next);
+    }
+
+    /// Reads back what `save` wrote, if a level was being played.
+    pub fn load(config: &Config) -> Result<Option<LevelScript>, ParseError> {
+        if config.entry("level", "version").is_none() {
+            return Ok(None);
+        }
+
+        let mut level_config = Config::new();
+        for entry in config.entries() {
+            if entry.section == "level" {
+                level_config.add("", &entry.key, &entry.value);
+            } else if entry.section.starts_with("level ") {
+                level_config.add(&entry.section["level ".len()..], &entry.key, &entry.value);
+            }
+        }
+
+        let level = try!(Level::from_config(&level_config));
+        let time = try!(config.parsed::<f64>("level", "time")).unwrap_or(0.0);
+        let next = try!(config.parsed::<usize>("level", "next")).unwrap_or(0);
+
+        Ok(Some(LevelScript {
+            next: next.min(level.events.len()),
+            level: level,
+            time: time,
+        }))
+    }
+
+    /// Advances the clock, and returns the events which are due.
+    pub fn update(&mut self, dt: f64) -> Vec<Event> {
+        self.time += dt;
+
+        let mut due = Vec::new();
+        while self.next < self.level.events.len() && self.level.events[self.next].0 <= self.time {
+            due.push(self.level.events[self.next].1.clone());
+            self.next += 1;
+        }
+
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::phi::config::Config;
+
+    fn level(events: &str) -> Result<Level, ParseError> {
+        Level::from_config(&Config::parse(&format!("version = 1\nname = Test\n[events]\n{}", events)).unwrap())
+    }
+
+    #[test]
+    fn reads_events_in_order() {
+        let level = level("4 = boss\n2 = asteroids 5\n2 = scroll 2\n10 = end\n").unwrap();
+
+        assert_eq!(level.events(), &[
+            (2.0, Event::Asteroids(5)),
+            (2.0, Event::Scroll(2.0)),
+            (4.0, Event::Boss),
+            (10.0, Event::End),
+        ][..]);
+        assert_eq!(level.scroll_at(3.0), 2.0);
+    }
+
+    #[test]
+    fn rejects_too_many_asteroids() {
+        assert!(level("1 = asteroids 100\n").is_ok());
+        assert_eq!(level("1 = asteroids 4000000000\n").unwrap_err().line, 4);
+        assert!(level("1 = asteroids 2.5\n").is_err());
+    }
+
+    #[test]
+    fn rejects_numbers_which_are_not_finite() {
+        assert!(level("1 = enemy scout NaN\n").is_err());
+        assert!(level("1 = scroll inf\n").is_err());
+        assert!(level("1 = enemy scout 0.5 pattern straight NaN 0\n").is_err());
+        assert!(level("1 = enemy scout 0.5 pattern stop then inf stop\n").is_err());
+    }
+
+    fn pattern(text: &str) -> Pattern {
+        let tokens: Vec<&str> = text.split_whitespace().collect();
+        parse_pattern(&tokens).unwrap()
+    }
+
+    /// Checks that `text` is read back the same once written.
+    fn round_trip(text: &str) {
+        let read = pattern(text);
+        assert_eq!(pattern(&format_pattern(&read)), read, "{}", format_pattern(&read));
+    }
+
+    #[test]
+    fn patterns_round_trip() {
+        round_trip("straight -100 0");
+        round_trip("sine 80 2 + straight -120 0");
+        round_trip("straight -100 0 then 1.5 dive 320 2 then 0.5 stop");
+        round_trip("( straight -100 0 then 2 stop ) + sine 80 2");
+        round_trip("strafe 90 + ( stop then 1 straight -50 0 ) then 3 ( dive 300 1 then 2 stop )");
+    }
+
+    #[test]
+    fn sums_of_sequences_keep_every_step() {
+        let steps = Pattern::Straight(-100.0, 0.0).then(2.0, Pattern::Stop).then(1.0, Pattern::Strafe { speed: 50.0 });
+        let sum = steps.clone().with(Pattern::Sine { amplitude: 80.0, period: 2.0 });
+
+        assert_eq!(pattern(&format_pattern(&sum)), sum);
+    }
+
+    #[test]
+    fn brackets_keep_sequences_apart() {
+        // The sequence in brackets lasts 3 seconds as a whole, its last step
+        // 2 of them, rather than its last step lasting 3.
+        assert_eq!(pattern("( stop then 1 straight -50 0 ) then 3 sine 80 2"), Pattern::Sequence(vec![
+            (Pattern::Sequence(vec![(Pattern::Stop, 1.0), (Pattern::Straight(-50.0, 0.0), 0.0)]), 3.0),
+            (Pattern::Sine { amplitude: 80.0, period: 2.0 }, 0.0),
+        ]));
+
+        let tokens = ["(", "stop", "then", "1", "stop"];
+        assert!(parse_pattern(&tokens).is_err());
+    }
+
+    #[test]
+    fn levels_round_trip() {
+        let level = level(concat!("2 = enemy weaver 0.3 pattern ( straight -120 0 then 2 stop ) + sine 80 2\n",
+                                  "4 = asteroids 5\n6 = scroll 1.5\n30 = boss\n40 = end\n")).unwrap();
+        let again = Level::from_config(&Config::parse(&level.to_config().to_string()).unwrap()).unwrap();
+
+        assert_eq!(again, level);
+    }
+
+    #[test]
+    fn rejects_unknown_enemies_and_events() {
+        assert!(level("1 = enemy dragon\n").is_err());
+        assert!(level("1 = party\n").is_err());
+        assert!(level("later = boss\n").is_err());
+    }
+}
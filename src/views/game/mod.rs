@@ -0,0 +1,963 @@
+mod asteroid;
+mod boss;
+pub mod enemy;
+mod explosion;
+mod field;
+mod fleet;
+mod hud;
+pub mod level;
+pub mod netplay;
+pub mod pattern;
+mod player;
+mod powerup;
+pub mod save;
+mod score;
+mod ship;
+mod weapons;
+
+use self::boss::Boss;
+use self::field::AsteroidField;
+use self::fleet::EnemyFleet;
+use self::hud::Hud;
+use self::level::{Event, Level, LevelScript};
+use self::player::Player;
+use self::powerup::{PowerUpKind, PowerUps};
+use self::score::Score;
+use self::ship::{Controls, ShipInput};
+use ::phi::{Phi, View, ViewAction};
+use ::phi::console::{self, Command};
+use ::phi::config::{Config, ConfigError, LoadError, ParseError};
+use ::phi::data::Rectangle;
+use ::phi::debug::REGION_COLOR;
+use ::phi::error::{PhiError, PhiResult};
+use ::phi::gfx::DrawRectangle;
+use ::phi::rng::Rng;
+use ::phi::rollback::Checksum;
+use ::phi::ui::{Button, Label, Panel, Stack, Ui};
+use ::sdl2::pixels::Color;
+use ::sdl2::render::BlendMode;
+use ::std::fs;
+use ::std::io;
+use ::std::path::PathBuf;
+use ::views::error::{ErrorView, or_error};
+use ::views::options::SHOW_HITBOXES;
+use ::views::shared::{BgSet, menu_theme};
+
+// Constants
+
+/// Proportion of the screen's width in which the ships may move.
+const MOVABLE_WIDTH: f64 = 0.70;
+
+/// Damage dealt to the player's ship when it runs into an asteroid or an
+/// enemy, neither of which survives the collision.
+const COLLISION_DAMAGE: u32 = 1;
+/// Damage dealt to the player's ship by each enemy shot.
+const ENEMY_SHOT_DAMAGE: u32 = 1;
+
+/// Chances that destroying something drops a power-up.
+const ASTEROID_DROP_CHANCE: f64 = 0.06;
+const ENEMY_DROP_CHANCE: f64 = 0.2;
+const BOSS_PART_DROP_CHANCE: f64 = 1.0;
+
+/// Most things brought in at once from the console.
+const MAX_SPAWN: u32 = 100;
+
+/// The entries of the pause menu.
+#[derive(Clone, Copy)]
+enum PauseAction {
+    Resume,
+    Restart,
+    Options,
+    /// Save the game to resume it later, then quit to the menu
+    Save,
+    Quit,
+}
+
+// View Definition
+pub struct ShipView {
+    /// One or two players, the second one joining at any time
+    players: Vec<Player>,
+    asteroids: AsteroidField,
+    enemies: EnemyFleet,
+    boss: Option<Boss>,
+    /// The last wave after which a boss appeared, so it only does once
+    boss_wave: u32,
+    /// The level being played, if any, rather than endless waves
+    script: Option<LevelScript>,
+    /// Whether the level reached its end, which happens once any boss is
+    /// defeated
+    completed: bool,
+    /// The file being edited and the time the level editor was at, when
+    /// previewing a level from it. Leaving the game goes back to the editor.
+    editing: Option<(PathBuf, f64)>,
+    /// The pause menu, while the game is paused
+    paused: Option<Ui<PauseAction>>,
+    powerups: PowerUps,
+    hud: Hud,
+    /// Seconds played, e.g. to blink the prompt for a second player to join
+    time: f64,
+    /// Where the game's random numbers come from, so that it may be simulated
+    /// again the same way
+    rng: Rng,
+    /// Why the game cannot go on, e.g. if the boss's images could not be
+    /// loaded while it was simulated
+    failure: Option<PhiError>,
+    backgrounds: BgSet,
+}
+
+/// Everything which changes as the game is simulated, to go back to it later.
+/// The backgrounds only scroll by, and are left out.
+#[derive(Clone)]
+struct Snapshot {
+    players: Vec<Player>,
+    asteroids: AsteroidField,
+    enemies: EnemyFleet,
+    boss: Option<Boss>,
+    boss_wave: u32,
+    script: Option<LevelScript>,
+    completed: bool,
+    powerups: PowerUps,
+    time: f64,
+    rng: Rng,
+}
+
+impl ShipView {
+    pub fn new(phi: &mut Phi) -> PhiResult<ShipView> {
+        let backgrounds = try!(BgSet::new(&mut phi.renderer));
+        ShipView::with_backgrounds(phi, backgrounds)
+        }
+
+
+    pub fn with_backgrounds(phi: &mut Phi, backgrounds: BgSet) -> PhiResult<ShipView> {
+        ShipView::with_script(phi, backgrounds, None)
+    }
+
+    /// Plays the events of `level`, rather than endless waves.
+    pub fn with_level(phi: &mut Phi, backgrounds: BgSet, level: Level) -> PhiResult<ShipView> {
+        ShipView::with_script(phi, backgrounds, Some(LevelScript::new(level)))
+    }
+
+    /// Plays `level` from `time` seconds in, then goes back to the level
+    /// editor, which was editing the file at `path`.
+    pub fn preview(phi: &mut Phi, backgrounds: BgSet, level: Level, path: PathBuf, time: f64)
+                   -> PhiResult<ShipView> {
+        let scroll = level.scroll_at(time);
+        let mut view = try!(ShipView::with_script(phi, backgrounds, Some(LevelScript::starting_at(level, time))));
+        view.backgrounds.set_scroll(scroll);
+        view.editing = Some((path, time));
+        Ok(view)
+    }
+
+    /// A game of endless waves for two players from the start, whose random
+    /// numbers all come from `seed`, as played over the network.
+    pub fn co_op(phi: &mut Phi, backgrounds: BgSet, seed: u64) -> PhiResult<ShipView> {
+        let mut view = try!(ShipView::with_backgrounds(phi, backgrounds));
+        let player = try!(Player::new(phi, 1));
+        view.players.push(player);
+        view.rng = Rng::new(seed);
+        Ok(view)
+    }
+
+    fn with_script(phi: &mut Phi, mut backgrounds: BgSet, script: Option<LevelScript>)
+                   -> PhiResult<ShipView> {
+        backgrounds.set_scroll(1.0);
+
+        let mut view = ShipView {
+            players: vec![try!(Player::new(phi, 0))],
+
+            asteroids: try!(AsteroidField::new(phi)),
+
+            enemies: try!(EnemyFleet::new(phi)),
+
+            boss: None,
+            boss_wave: 0,
+
+            script: script,
+            completed: false,
+            editing: None,
+            paused: None,
+
+            powerups: try!(PowerUps::new(phi)),
+
+            hud: Hud::new(),
+            time: 0.0,
+            rng: Rng::from_entropy(),
+            failure: None,
+
+            backgrounds: backgrounds,
+        };
+
+        let waves = view.has_waves();
+        view.asteroids.set_held(!waves);
+        Ok(view)
+    }
+
+    /// Whether the endless waves of asteroids and enemies are running, which
+    /// levels may turn off.
+    fn has_waves(&self) -> bool {
+        self.script.as_ref().map_or(true, |script| script.level.waves)
+    }
+
+    /// The level being played, if any.
+    fn level(&self) -> Option<Level> {
+        self.script.as_ref().map(|script| script.level.clone())
+    }
+
+    /// The backgrounds to hand over to the next view, scrolling as usual.
+    fn leave_backgrounds(&self) -> BgSet {
+        let mut backgrounds = self.backgrounds.clone();
+        backgrounds.set_scroll(1.0);
+        backgrounds
+    }
+
+    /// Goes back to the level editor, if previewing from it.
+    fn leave_preview(&self, phi: &mut Phi) -> Option<ViewAction> {
+        self.editing.as_ref().map(|&(ref path, time)| {
+            ViewAction::ChangeView(or_error(::views::level_editor::LevelEditorView::with_level(
+                phi, self.leave_backgrounds(), self.level().unwrap(), path.clone(), time)))
+        })
+    }
+
+    /// Leaves the game for the main menu, or the level editor if previewing.
+    fn quit(&self, phi: &mut Phi) -> ViewAction {
+        if let Some(action) = self.leave_preview(phi) {
+            return action;
+        }
+        ViewAction::ChangeView(Box::new(
+            ::views::main_menu::MainMenuView::with_backgrounds(phi, self.leave_backgrounds())))
+    }
+
+    /// Starts the same game, level or preview over.
+    fn restart(&self, phi: &mut Phi) -> ViewAction {
+        let backgrounds = self.leave_backgrounds();
+        let view = match (self.level(), self.editing.clone()) {
+            (Some(level), Some((path, time))) => ShipView::preview(phi, backgrounds, level, path, time),
+            (Some(level), None) => ShipView::with_level(phi, backgrounds, level),
+            (None, _) => ShipView::with_backgrounds(phi, backgrounds),
+        };
+        ViewAction::ChangeView(or_error(view))
+    }
+
+    fn pause(&mut self) {
+        self.paused = Some(self.pause_menu(None));
+    }
+
+    /// The pause menu, with an `error` at the bottom if any. Previews of the
+    /// level editor cannot be saved.
+    fn pause_menu(&self, error: Option<&str>) -> Ui<PauseAction> {
+        let quit = if self.editing.is_some() { "Quit to Editor" } else { "Quit to Menu" };
+
+        let mut menu = Stack::vertical()
+            .with(Label::new("Paused"))
+            .with(Button::new("Resume", PauseAction::Resume))
+            .with(Button::new("Restart", PauseAction::Restart))
+            .with(Button::new("Options", PauseAction::Options));
+
+        if self.editing.is_none() {
+            menu = menu.with(Button::new("Save and Quit", PauseAction::Save));
+        }
+
+        menu = menu.with(Button::new(quit, PauseAction::Quit));
+
+        if let Some(error) = error {
+            menu = menu.with(Label::new(error).size(20).color(Color::RGB(255, 90, 90)));
+        }
+
+        Ui::new(Panel::new(menu).min_width(360.0), menu_theme())
+    }
+
+    /// Runs the pause menu over the frozen game.
+    fn render_paused(&mut self, phi: &mut Phi, elapsed: f64) -> ViewAction {
+        let mut actions = match self.paused {
+            Some(ref mut ui) => ui.handle(&phi.events),
+            None => Vec::new(),
+        };
+
+        if phi.events.now.key_escape == Some(true) {
+            actions.push(PauseAction::Resume);
+        }
+
+        for action in actions {
+            match action {
+                PauseAction::Resume => {
+                    self.paused = None;
+                    break;
+                },
+                PauseAction::Restart =>
+                    return self.restart(phi),
+                PauseAction::Options =>
+                    return ViewAction::PushView(Box::new(
+                        ::views::options::OptionsView::pushed(phi, self.leave_backgrounds()))),
+                PauseAction::Save => match self.save(phi) {
+                    Ok(()) => return self.quit(phi),
+                    Err(err) => {
+                        warn!("Could not save the game: {}", err);
+                        self.paused = Some(self.pause_menu(Some("Could not save the game")));
+                        break;
+                    },
+                },
+                PauseAction::Quit =>
+                    return self.quit(phi),
+            }
+        }
+
+        self.draw_paused(phi, elapsed);
+        ViewAction::None
+    }
+
+    /// Draws the pause menu over the game, where nothing moves, not even the
+    /// backgrounds.
+    fn draw_paused(&mut self, phi: &mut Phi, elapsed: f64) {
+        self.draw(phi, 0.0);
+
+        if let Some(ref mut ui) = self.paused {
+            let (win_w, win_h) = phi.output_size();
+            phi.renderer.set_blend_mode(BlendMode::Blend);
+            phi.renderer.set_draw_color(Color::RGBA(0, 0, 0, 160));
+            phi.renderer.fill_rectangle(Rectangle { x: 0.0, y: 0.0, w: win_w, h: win_h });
+            phi.renderer.set_blend_mode(BlendMode::None);
+
+            ui.render(phi, elapsed);
+        }
+    }
+
+    /// Brings in a boss, which holds back waves and the level until it is
+    /// defeated.
+    fn spawn_boss(&mut self, phi: &mut Phi, screen: Rectangle) -> PhiResult<()> {
+        if self.boss.is_none() {
+            self.boss = Some(try!(Boss::new(phi, screen)));
+            self.asteroids.set_held(true);
+        }
+        Ok(())
+    }
+
+    /// Brings in a boss while the game is simulated, keeping any error for
+    /// `render` to show.
+    fn spawn_boss_or_fail(&mut self, phi: &mut Phi, screen: Rectangle) {
+        if let Err(err) = self.spawn_boss(phi, screen) {
+            self.failure = Some(err);
+        }
+    }
+
+    fn play(&mut self, phi: &mut Phi, event: Event, screen: Rectangle) {
+        match event {
+            Event::Enemy { kind, y, pattern } =>
+                self.enemies.spawn_kind(kind, y, pattern, screen),
+            Event::Asteroids(count) =>
+                self.asteroids.burst(phi, count),
+            Event::Scroll(factor) =>
+                self.backgrounds.set_scroll(factor),
+            Event::Boss =>
+                self.spawn_boss_or_fail(phi, screen),
+            Event::End =>
+                self.completed = true,
+        }
+    }
+
+    /// Destroys every asteroid, enemy and enemy shot on screen, awarding
+    /// their points to the given player.
+    fn smart_bomb(&mut self, player: usize) {
+        let score = &mut self.players[player].score;
+
+        for hit in self.asteroids.destroy_all() {
+            score.kill(Score::asteroid_points(hit.side));
+        }
+
+        for hit in self.enemies.destroy_all() {
+            score.kill(hit.points);
+            self.asteroids.explode(hit.rect, hit.vel);
+        }
+
+        if let Some(ref mut boss) = self.boss {
+            boss.shots.clear();
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            players: self.players.clone(),
+            asteroids: self.asteroids.clone(),
+            enemies: self.enemies.clone(),
+            boss: self.boss.clone(),
+            boss_wave: self.boss_wave,
+            script: self.script.clone(),
+            completed: self.completed,
+            powerups: self.powerups.clone(),
+            time: self.time,
+            rng: self.rng,
+        }
+    }
+
+    fn restore(&mut self, snapshot: &Snapshot) {
+        let snapshot = snapshot.clone();
+        self.players = snapshot.players;
+        self.asteroids = snapshot.asteroids;
+        self.enemies = snapshot.enemies;
+        self.boss = snapshot.boss;
+        self.boss_wave = snapshot.boss_wave;
+        self.script = snapshot.script;
+        self.completed = snapshot.completed;
+        self.powerups = snapshot.powerups;
+        self.time = snapshot.time;
+        self.rng = snapshot.rng;
+    }
+
+    /// A summary of the state of the game, which two peers simulating it the
+    /// same way agree on. It covers everything a save does (the positions and
+    /// velocities of every ship, shot, enemy, asteroid and power-up, and the
+    /// boss) but the backgrounds, which scroll as frames are drawn.
+    fn checksum(&self) -> u64 {
+        let mut config = Config::new();
+        self.save_state(&mut config);
+
+        let mut sum = Checksum::new();
+        for entry in config.entries() {
+            sum.add_str(&entry.section);
+            sum.add_str(&entry.key);
+            sum.add_str(&entry.value);
+        }
+        sum.value()
+    }
+
+    /// The game as a save file, from which `from_config` resumes it.
+    fn to_config(&self) -> Config {
+        let mut config = Config::new();
+        config.set("", "version", save::SAVE_VERSION);
+        self.backgrounds.save(&mut config);
+        self.save_state(&mut config);
+        config
+    }
+
+    /// Writes what `to_config` saves of the game, but its version and
+    /// backgrounds.
+    fn save_state(&self, config: &mut Config) {
+        config.set("", "time", self.time);
+        config.set("", "rng", self.rng.state());
+        config.set("", "players", self.players.len());
+        config.set("", "boss_wave", self.boss_wave);
+        config.set("", "completed", if self.completed { "on" } else { "off" });
+
+        for (i, player) in self.players.iter().enumerate() {
+            player.save(config, &Player::section(i));
+        }
+        self.asteroids.save(config);
+        self.enemies.save(config);
+        if let Some(ref boss) = self.boss {
+            boss.save(config);
+        }
+        self.powerups.save(config);
+        if let Some(ref script) = self.script {
+            script.save(config);
+        }
+    }
+
+    /// Resumes a game saved by `to_config`.
+    fn from_config(phi: &mut Phi, config: &Config) -> Result<ShipView, LoadError> {
+        let line = |key: &str| config.entry("", key).map_or(0, |entry| entry.line);
+
+        match try!(config.parsed::<u32>("", "version")) {
+            Some(save::SAVE_VERSION) => {},
+            _ => return Err(LoadError::from(ParseError {
+                line: line("version"),
+                message: format!("expected `version = {}`", save::SAVE_VERSION),
+            })),
+        }
+
+        let backgrounds = try!(BgSet::load(&phi.renderer, config));
+        let script = try!(LevelScript::load(config));
+        let mut view = try!(ShipView::with_script(phi, backgrounds, script));
+
+        match try!(config.parsed::<usize>("", "players")) {
+            Some(1) => {},
+            Some(2) => {
+                let player = try!(Player::new(phi, 1));
+                view.players.push(player);
+            },
+            _ => return Err(LoadError::from(ParseError {
+                line: line("players"),
+                message: "expected 1 or 2 players".to_string(),
+            })),
+        }
+
+        for (i, player) in view.players.iter_mut().enumerate() {
+            try!(player.load(config, &Player::section(i)));
+        }
+
+        match try!(config.parsed::<u64>("", "rng")) {
+            Some(state) => view.rng = Rng::from_state(state),
+            None => return Err(LoadError::from(ParseError { line: 0, message: "missing `rng`".to_string() })),
+        }
+
+        view.time = try!(config.parsed("", "time")).unwrap_or(0.0);
+        view.boss_wave = try!(config.parsed("", "boss_wave")).unwrap_or(0);
+        view.completed = config.get("", "completed") == Some("on");
+
+        try!(view.asteroids.load(config));
+        try!(view.enemies.load(config));
+        try!(view.powerups.load(config));
+
+        if config.entry("boss", "boss").is_some() {
+            let (win_w, win_h) = phi.output_size();
+            let mut boss = try!(Boss::new(phi, Rectangle { x: 0.0, y: 0.0, w: win_w, h: win_h }));
+            try!(boss.load(config));
+            view.boss = Some(boss);
+        }
+
+        Ok(view)
+    }
+
+    /// Saves the game, to resume it from the main menu.
+    fn save(&self, phi: &Phi) -> io::Result<()> {
+        match save::save_path(phi) {
+            Some(path) => self.to_config().save(&path),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "no directory to save the game in")),
+        }
+    }
+
+    /// Resumes the game saved last, and deletes the save, so that it is only
+    /// resumed once.
+    pub fn load(phi: &mut Phi) -> Result<ShipView, LoadError> {
+        let path = match save::save_path(phi) {
+            Some(path) => path,
+            None => return Err(LoadError::Config(ConfigError::Io(
+                io::Error::new(io::ErrorKind::NotFound, "no directory to load the game from")))),
+        };
+
+        let config = try!(Config::load(&path));
+        let view = try!(ShipView::from_config(phi, &config));
+
+        if let Err(err) = fs::remove_file(&path) {
+            warn!("Could not delete the saved game: {}", err);
+        }
+        Ok(view)
+    }
+
+    /// Brings in `count` things of the kind called `what`, as asked from the
+    /// console.
+    fn spawn(&mut self, phi: &mut Phi, what: &str, count: u32) -> Result<(), String> {
+        if !["asteroid", "enemy", "boss", "powerup"].contains(&what) {
+            return Err(format!("cannot spawn `{}`", what));
+        }
+        if count > MAX_SPAWN {
+            return Err(format!("cannot spawn more than {} at once", MAX_SPAWN));
+        }
+
+        let (win_w, win_h) = phi.output_size();
+        let screen = Rectangle { x: 0.0, y: 0.0, w: win_w, h: win_h };
+        let outer_rng = ::phi::rng::swap(self.rng);
+
+        match what {
+            "asteroid" => self.asteroids.burst(phi, count),
+            "enemy" => for _ in 0..count {
+                self.enemies.spawn(self.asteroids.wave(), screen);
+            },
+            "boss" => if let Err(err) = self.spawn_boss(phi, screen) {
+                self.rng = ::phi::rng::swap(outer_rng);
+                return Err(err.to_string());
+            },
+            _ => for i in 0..count {
+                // In a column in front of the ships, coming from the right
+                let rect = Rectangle {
+                    x: win_w * MOVABLE_WIDTH,
+                    y: win_h * (i + 1) as f64 / (count + 1) as f64,
+                    w: 0.0,
+                    h: 0.0,
+                };
+                self.powerups.drop_from(rect, 1.0);
+            },
+        }
+
+        self.rng = ::phi::rng::swap(outer_rng);
+        Ok(())
+    }
+
+    /// Whether every player lost their last life and their ship's explosion
+    /// faded out.
+    fn is_game_over(&self) -> bool {
+        self.players.iter().all(|player| player.ship.is_game_over())
+    }
+
+    /// Simulates `elapsed` seconds of the game, in which the players' ships
+    /// are controlled by `inputs`, one per player. The game's own random
+    /// numbers are used, so that it may be simulated again the same way.
+    fn step(&mut self, phi: &mut Phi, inputs: &[ShipInput], elapsed: f64) {
+        let outer_rng = ::phi::rng::swap(self.rng);
+
+        let (win_w, win_h) = phi.output_size();
+
+        let movable_region = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            w: win_w * MOVABLE_WIDTH,
+            h: win_h,
+        };
+
+        let screen = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            w: win_w,
+            h: win_h,
+        };
+
+        self.time += elapsed;
+
+        // Move every ship, each kept inside of the movable region, and their
+        // projectiles
+        for (player, &input) in self.players.iter_mut().zip(inputs) {
+            player.ship.update(input, elapsed, movable_region, screen);
+        }
+
+        // Update the asteroids, the enemies and the combo
+        self.asteroids.update(phi, elapsed);
+
+        // Enemies aim at the first player still in the game
+        let target = self.players.iter()
+            .find(|player| player.ship.is_alive())
+            .unwrap_or(&self.players[0])
+            .center();
+        let can_target = self.players.iter().any(|player| player.ship.is_alive());
+        let wave = self.asteroids.wave();
+        let calm = self.asteroids.is_break() || !self.has_waves();
+
+        // Play the level's events, unless a boss is holding it back
+        if self.boss.is_none() {
+            let events = match self.script {
+                Some(ref mut script) => script.update(elapsed),
+                None => Vec::new(),
+            };
+
+            for event in events {
+                self.play(phi, event, screen);
+            }
+        }
+
+        // Bosses appear in the calm after some waves, and hold the next wave
+        // back until they are defeated.
+        if self.has_waves() && calm && self.boss.is_none() && self.boss_wave < wave &&
+            self.asteroids.difficulty().has_boss_after(wave) {
+            self.spawn_boss_or_fail(phi, screen);
+            self.boss_wave = wave;
+        }
+
+        let defeated = match self.boss {
+            Some(ref mut boss) => {
+                boss.update(elapsed, target, can_target, screen);
+                boss.is_defeated()
+            },
+            None => false,
+        };
+
+        if defeated {
+            self.boss = None;
+            let waves = self.has_waves();
+            self.asteroids.set_held(!waves);
+        }
+
+        self.enemies.update(elapsed, self.asteroids.difficulty(), wave,
+                            calm || self.boss.is_some(),
+                            target, can_target, screen);
+
+        self.powerups.update(elapsed);
+        for player in &mut self.players {
+            player.score.update(elapsed);
+        }
+
+        // Collect the power-ups the ships run into
+        for i in 0..self.players.len() {
+            let collected = {
+                let ship = &self.players[i].ship;
+                if ship.is_alive() { self.powerups.collect(ship.rect, ship.mask()) } else { None }
+            };
+
+            if let Some(kind) = collected {
+                self.powerups.picked_up(kind);
+                self.players[i].ship.power_up(kind);
+
+                if kind == PowerUpKind::SmartBomb {
+                    self.smart_bomb(i);
+                }
+            }
+        }
+
+        // Projectiles are used up by whatever they hit, and destroying it is
+        // worth points to whoever fired them.
+        for player in &mut self.players {
+            let score = &mut player.score;
+            let damage = player.ship.weapon.damage;
+
+            for projectile in player.ship.weapon.projectiles.alive_mut() {
+                if let Some(hit) = self.asteroids.hit(projectile.rect, None, damage) {
+                    projectile.kill();
+
+                    if hit.destroyed {
+                        score.kill(Score::asteroid_points(hit.side));
+                        self.powerups.drop_from(hit.rect, ASTEROID_DROP_CHANCE);
+                    }
+                } else if let Some(hit) = self.enemies.hit(projectile.rect, None, damage) {
+                    projectile.kill();
+
+                    if hit.destroyed {
+                        score.kill(hit.points);
+                        self.asteroids.explode(hit.rect, hit.vel);
+                        self.powerups.drop_from(hit.rect, ENEMY_DROP_CHANCE);
+                    }
+                } else if let Some(ref mut boss) = self.boss {
+                    if let Some(hit) = boss.hit(projectile.rect, None, damage) {
+                        projectile.kill();
+
+                        if hit.destroyed {
+                            score.kill(hit.points);
+                            self.powerups.drop_from(hit.rect, BOSS_PART_DROP_CHANCE);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Asteroids and enemies running into a ship are destroyed, and hurt
+        // it, as do enemy shots and the hull of a boss. A shield takes the
+        // hits instead.
+        for player in &mut self.players {
+            let ship = &mut player.ship;
+            if !ship.is_vulnerable() && !ship.is_shielded() {
+                continue;
+            }
+
+            let damage = {
+                let rect = ship.rect;
+                let mask = ship.mask();
+
+                let rammed_asteroid = self.asteroids.hit(rect, mask, ::std::u32::MAX).is_some();
+
+                let rammed_enemy = match self.enemies.hit(rect, mask, ::std::u32::MAX) {
+                    Some(hit) => {
+                        self.asteroids.explode(hit.rect, hit.vel);
+                        true
+                    },
+                    None => false,
+                };
+
+                let shot = self.enemies.shots.hit(rect, mask);
+
+                let (rammed_boss, shot_by_boss) = match self.boss {
+                    Some(ref mut boss) => (boss.touches(rect, mask), boss.shots.hit(rect, mask)),
+                    None => (false, false),
+                };
+
+                if rammed_asteroid || rammed_enemy || rammed_boss { COLLISION_DAMAGE }
+                else if shot || shot_by_boss { ENEMY_SHOT_DAMAGE }
+                else { 0 }
+            };
+
+            if damage > 0 && ship.is_vulnerable() {
+                player.score.break_combo();
+
+                if ship.damage(damage) {
+                    self.asteroids.explode(ship.rect, (0.0, 0.0));
+                }
+            }
+        }
+
+        self.rng = ::phi::rng::swap(outer_rng);
+    }
+
+    /// Draws the game, `elapsed` seconds after it was last drawn.
+    fn draw(&mut self, phi: &mut Phi, elapsed: f64) {
+        // Clear screen
+        phi.renderer.set_draw_color(Color::RGB(0, 0, 0));
+        phi.renderer.clear();
+
+        // Render backgrounds
+        {
+            let _zone = phi.profiler.zone("backgrounds");
+            self.backgrounds.back.render(&mut phi.renderer, elapsed);
+            self.backgrounds.middle.render(&mut phi.renderer, elapsed);
+        }
+
+        let zone = phi.profiler.zone("entities");
+
+        // Render ship bounding boxes for debugging
+        if phi.settings.gameplay(SHOW_HITBOXES, false) {
+            for player in self.players.iter().filter(|player| player.ship.is_alive()) {
+                phi.renderer.set_draw_color(Color::RGB(200, 200, 50));
+                phi.renderer.fill_rectangle(player.ship.rect);
+            }
+        }
+
+        // Render ships
+        for player in &self.players {
+            player.ship.render(phi);
+        }
+        self.asteroids.render(phi);
+        self.enemies.render(phi);
+        self.powerups.render(phi);
+        if let Some(ref boss) = self.boss {
+            boss.render(phi);
+        }
+
+        drop(zone);
+
+        // Render foreground
+        {
+            let _zone = phi.profiler.zone("foreground");
+            self.backgrounds.front.render(&mut phi.renderer, elapsed);
+        }
+
+        // Render the HUD over everything else
+        let zone = phi.profiler.zone("hud");
+        if let Some(ref boss) = self.boss {
+            boss.render_overlay(phi);
+        }
+        let stage = match self.script {
+            Some(ref script) => script.level.name.clone(),
+            None => format!("Wave {}", self.asteroids.wave()),
+        };
+        let prompt = format!("Player 2: press {} or (A) to join", phi.events.bindings.key_p2_fire.name());
+        let blink = (self.time * 2.0) as u32 % 2 == 0;
+        self.hud.render(phi, &self.players, &stage,
+                        if self.players.len() < 2 && blink { Some(&prompt) } else { None });
+        drop(zone);
+
+        // Describe what the debug overlay shows, if enabled
+        if phi.debug.is_enabled() {
+            let (win_w, win_h) = phi.output_size();
+            phi.debug.rect(Rectangle { x: 0.0, y: 0.0, w: win_w * MOVABLE_WIDTH, h: win_h }, REGION_COLOR);
+
+            for player in &self.players {
+                player.ship.debug(&mut phi.debug);
+            }
+            self.asteroids.debug(&mut phi.debug);
+            self.enemies.debug(&mut phi.debug);
+            self.powerups.debug(&mut phi.debug);
+            if let Some(ref boss) = self.boss {
+                boss.debug(&mut phi.debug);
+            }
+        }
+    }
+}
+
+
+impl View for ShipView {
+    fn render(&mut self, phi: &mut Phi, elapsed: f64) -> ViewAction {
+        if phi.events.now.quit {
+            return ViewAction::Quit;
+        }
+
+        if self.paused.is_some() {
+            return self.render_paused(phi, elapsed);
+        }
+
+        if phi.events.now.key_escape == Some(true) || phi.events.now.focus_lost {
+            self.pause();
+            self.draw_paused(phi, 0.0);
+            return ViewAction::None;
+        }
+
+        // Once every player lost their last life and their ship's explosion
+        // faded out
+        let scores: Vec<u64> = self.players.iter().map(|player| player.score.points()).collect();
+
+        if self.is_game_over() {
+            if let Some(action) = self.leave_preview(phi) {
+                return action;
+            }
+            return ViewAction::ChangeView(Box::new(
+                    ::views::game_over::GameOverView::new(phi, self.leave_backgrounds(),
+                                                       &scores, self.level())));
+        }
+
+        if self.completed && self.boss.is_none() {
+            if let Some(action) = self.leave_preview(phi) {
+                return action;
+            }
+            let level = self.level().unwrap();
+            return ViewAction::ChangeView(Box::new(
+                    ::views::game_over::GameOverView::level_complete(phi, self.leave_backgrounds(),
+                                                                  &scores, level)));
+        }
+
+        // The second player joins by pressing fire
+        if self.players.len() < 2 && Controls::Secondary.fire_pressed(phi) {
+            match Player::new(phi, 1) {
+                Ok(player) => self.players.push(player),
+                Err(err) => return ViewAction::ChangeView(Box::new(ErrorView::new(&err))),
+            }
+        }
+
+        {
+            let _zone = phi.profiler.zone("update");
+            let inputs: Vec<ShipInput> = self.players.iter().map(|player| player.input(phi)).collect();
+            self.step(phi, &inputs, elapsed);
+        }
+
+        if let Some(err) = self.failure.take() {
+            return ViewAction::ChangeView(Box::new(ErrorView::new(&err)));
+        }
+
+        let _zone = phi.profiler.zone("draw");
+        self.draw(phi, elapsed);
+
+        ViewAction::None
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![
+            Command { name: "spawn", usage: "<asteroid|enemy|boss|powerup> [count]",
+                      help: "Bring things into the game" },
+            Command { name: "god", usage: "", help: "Make the ships invulnerable, or not anymore" },
+            Command { name: "seed", usage: "<number>", help: "Restart the random numbers from a seed" },
+            Command { name: "set", usage: "<player_speed|lives> <value>",
+                      help: "Change the ships" },
+        ]
+    }
+
+    fn run_command(&mut self, phi: &mut Phi, name: &str, args: &[&str]) -> Result<ViewAction, String> {
+        match name {
+            "spawn" => {
+                let what: String = try!(console::arg(args, 0));
+                let count = if args.len() > 1 { try!(console::arg(args, 1)) } else { 1 };
+                try!(self.spawn(phi, &what, count));
+            },
+
+            "god" => {
+                let god = !self.players[0].ship.god;
+                for player in &mut self.players {
+                    player.ship.god = god;
+                }
+                phi.console.print(if god { "god mode on" } else { "god mode off" });
+            },
+
+            "seed" => {
+                let seed = try!(console::arg(args, 0));
+                self.rng = Rng::new(seed);
+            },
+
+            "set" => {
+                let what: String = try!(console::arg(args, 0));
+                match &what[..] {
+                    "player_speed" => {
+                        let speed: f64 = try!(console::arg(args, 1));
+                        for player in &mut self.players {
+                            player.ship.speed = speed;
+                        }
+                    },
+                    "lives" => {
+                        let lives: u32 = try!(console::arg(args, 1));
+                        // A ship on screen must have a life to lose
+                        if lives == 0 {
+                            return Err("a ship needs at least one life".to_string());
+                        }
+                        for player in &mut self.players {
+                            player.ship.lives = lives;
+                        }
+                    },
+                    _ => return Err(format!("cannot set `{}`", what)),
+                }
+            },
+
+            _ => return Err(format!("`{}` is not available here", name)),
+        }
+
+        Ok(ViewAction::None)
+    }
+}
@@ -0,0 +1,272 @@
+use ::phi::{Phi, View, ViewAction};
+use ::phi::data::Rectangle;
+use ::phi::error::{PhiError, PhiResult};
+use ::phi::gfx::CopySprite;
+use ::phi::net::{Conditions, Link, LossyLink, UdpLink};
+use ::phi::rng::Rng;
+use ::phi::rollback::{Request, Session, Snapshots};
+use ::sdl2::pixels::Color;
+use ::views::error::ErrorView;
+use ::views::game::{ShipView, Snapshot};
+use ::views::game::ship::{Controls, ShipInput};
+use ::views::shared::{BgSet, FONT_PATH};
+
+/// Seconds simulated by every frame of a networked game, which both peers
+/// must agree on.
+const TICK: f64 = 1.0 / 60.0;
+/// Most frames simulated at once to catch up after a slow one.
+const MAX_TICKS_PER_RENDER: u32 = 4;
+/// Frames between reading the local input and using it.
+const INPUT_DELAY: u32 = 2;
+/// Where the random numbers of every networked game come from, so that both
+/// peers start the same game.
+const NET_SEED: u64 = 0x5eed_1e55_a11e_0001;
+/// The size of the screen the game is simulated on, whatever the size of the
+/// window it is drawn to, so that both peers simulate it the same way.
+const NET_PLAYFIELD: (u32, u32) = (800, 600);
+
+const STATUS_FONT_SIZE: i32 = 20;
+
+/// One side of a networked game: its own copy of the game, and everything
+/// needed to keep it in step with the other peer's.
+struct Peer {
+    game: ShipView,
+    session: Session<ShipInput>,
+    snapshots: Snapshots<Snapshot>,
+    link: Box<Link>,
+    /// The controls of the local player
+    controls: Controls,
+}
+
+impl Peer {
+    fn new(game: ShipView, local: usize, link: Box<Link>, controls: Controls) -> Peer {
+        Peer {
+            game: game,
+            session: Session::new(local, INPUT_DELAY, NET_PLAYFIELD.0 << 16 | NET_PLAYFIELD.1),
+            snapshots: Snapshots::new(),
+            link: link,
+            controls: controls,
+        }
+    }
+
+    /// Reads the local input and simulates the next frame, going back to
+    /// correct any wrong prediction first.
+    fn tick(&mut self, phi: &mut Phi) {
+        while let Some(packet) = self.link.receive() {
+            self.session.receive(&packet);
+        }
+
+        let input = ShipInput::from_events(phi, self.controls);
+        self.session.add_local_input(input);
+
+        for request in self.session.advance() {
+            match request {
+                Request::Save(frame) =>
+                    self.snapshots.save(frame, self.game.snapshot()),
+                Request::Load(frame) => match self.snapshots.load(frame) {
+                    Some(snapshot) => self.game.restore(snapshot),
+                    None => {
+                        error!("No snapshot of frame {} to roll back to", frame);
+                        self.session.lost(frame);
+                    },
+                },
+                Request::Advance(frame, inputs) => {
+                    self.game.step(phi, &inputs, TICK);
+                    self.session.record_checksum(frame, self.game.checksum());
+                },
+            }
+        }
+
+        let packet = self.session.packet();
+        self.link.send(&packet);
+    }
+
+    /// Why the game cannot go on with the other peer, if it set it up
+    /// differently.
+    fn mismatch(&self) -> Option<PhiError> {
+        self.session.mismatch().map(|setup| PhiError::Network(format!(
+            "the other peer plays on a {}x{} playfield instead of {}x{}",
+            setup >> 16, setup & 0xffff, NET_PLAYFIELD.0, NET_PLAYFIELD.1)))
+    }
+
+    fn status(&self) -> String {
+        let sync = match (self.session.desync(), self.session.verified()) {
+            (Some(frame), _) => format!("DESYNC at frame {}", frame),
+            (None, Some(frame)) => format!("in sync up to frame {}", frame),
+            (None, None) => "waiting for the other peer".to_string(),
+        };
+
+        format!("Frame {} (confirmed {}), {} rollbacks, {}",
+                self.session.frame(), self.session.confirmed(), self.session.rollbacks(), sync)
+    }
+}
+
+/// A game of co-op played by two peers, either over UDP, or side by side over
+/// a simulated network to try out the rollback.
+pub struct NetPlayView {
+    /// The peers simulated by this process, the first one being shown
+    peers: Vec<Peer>,
+    /// Seconds not simulated yet
+    accumulator: f64,
+    /// Whether a desync was already reported
+    reported: bool,
+}
+
+impl NetPlayView {
+    /// Plays as the player of index `local`, 0 or 1, listening on the `bind`
+    /// address for packets from the `peer` one.
+    pub fn udp(phi: &mut Phi, backgrounds: BgSet, local: usize, bind: &str, peer: &str)
+               -> PhiResult<NetPlayView> {
+        let link = try!(UdpLink::bind(bind, peer)
+            .map_err(|err| PhiError::Network(format!("cannot connect: {}", err))));
+        let game = try!(on_playfield(phi, |phi| ShipView::co_op(phi, backgrounds, NET_SEED)));
+
+        Ok(NetPlayView::with_peers(vec![
+            Peer::new(game, local, Box::new(link), Controls::Primary),
+        ]))
+    }
+
+    /// Runs both peers in this process, over a simulated network as bad as
+    /// `conditions`. The first player uses the usual controls and the second
+    /// one those of the second player, and both games are checked to stay
+    /// the same.
+    pub fn loopback_test(phi: &mut Phi, backgrounds: BgSet, conditions: Conditions)
+                         -> PhiResult<NetPlayView> {
+        let (first, second) = LossyLink::pair(conditions, Rng::from_entropy().next_u64());
+        let first_game = try!(on_playfield(phi, |phi| ShipView::co_op(phi, backgrounds.clone(), NET_SEED)));
+        let second_game = try!(on_playfield(phi, |phi| ShipView::co_op(phi, backgrounds, NET_SEED)));
+
+        Ok(NetPlayView::with_peers(vec![
+            Peer::new(first_game, 0, Box::new(first), Controls::Primary),
+            Peer::new(second_game, 1, Box::new(second), Controls::Secondary),
+        ]))
+    }
+
+    fn with_peers(peers: Vec<Peer>) -> NetPlayView {
+        NetPlayView {
+            peers: peers,
+            accumulator: 0.0,
+            reported: false,
+        }
+    }
+
+    /// Simulates the frames due since the last render, on every peer.
+    fn simulate(&mut self, phi: &mut Phi) {
+        let mut ticks = 0;
+
+        while self.accumulator >= TICK && ticks < MAX_TICKS_PER_RENDER {
+            for peer in &mut self.peers {
+                peer.tick(phi);
+            }
+            self.accumulator -= TICK;
+            ticks += 1;
+        }
+
+        if ticks == MAX_TICKS_PER_RENDER {
+            self.accumulator = 0.0;
+        }
+    }
+
+    fn render_status(&self, phi: &mut Phi) {
+        let (win_w, win_h) = phi.output_size();
+
+        for (i, peer) in self.peers.iter().enumerate() {
+            let text = format!("Peer {}: {}", i + 1, peer.status());
+            let color = if peer.session.desync().is_some() {
+                Color::RGB(255, 80, 80)
+            } else {
+                Color::RGB(180, 180, 180)
+            };
+
+            if let Some(sprite) = phi.ttf_str_sprite(&text, FONT_PATH, STATUS_FONT_SIZE, color) {
+                let (w, h) = sprite.size();
+                let line = (self.peers.len() - i) as f64;
+                phi.renderer.copy_sprite(&sprite, Rectangle {
+                    x: (win_w - w) / 2.0,
+                    y: win_h - line * (h + 4.0) - 8.0,
+                    w: w,
+                    h: h,
+                });
+            }
+        }
+    }
+}
+
+impl View for NetPlayView {
+    fn render(&mut self, phi: &mut Phi, elapsed: f64) -> ViewAction {
+        if phi.events.now.quit {
+            return ViewAction::Quit;
+        }
+
+        if phi.events.now.key_escape == Some(true) {
+            let backgrounds = self.peers[0].game.leave_backgrounds();
+            return ViewAction::ChangeView(Box::new(
+                ::views::main_menu::MainMenuView::with_backgrounds(phi, backgrounds)));
+        }
+
+        if self.peers[0].game.is_game_over() {
+            let game = &self.peers[0].game;
+            let scores: Vec<u64> = game.players.iter().map(|player| player.score.points()).collect();
+            return ViewAction::ChangeView(Box::new(
+                ::views::game_over::GameOverView::new(phi, game.leave_backgrounds(), &scores, None)));
+        }
+
+        for peer in &self.peers {
+            if let Some(err) = peer.mismatch() {
+                return ViewAction::ChangeView(Box::new(ErrorView::new(&err)));
+            }
+        }
+
+        // Every peer simulates frames of the same length, however fast this
+        // one renders.
+        self.accumulator += elapsed;
+        let simulated = on_playfield(phi, |phi| {
+            self.simulate(phi);
+            Ok(())
+        });
+        if let Err(err) = simulated {
+            return ViewAction::ChangeView(Box::new(ErrorView::new(&err)));
+        }
+
+        for peer in &mut self.peers {
+            if let Some(err) = peer.game.failure.take() {
+                return ViewAction::ChangeView(Box::new(ErrorView::new(&err)));
+            }
+        }
+
+        if !self.reported {
+            if let Some(peer) = self.peers.iter().find(|peer| peer.session.desync().is_some()) {
+                warn!("Netplay: {}", peer.status());
+                self.reported = true;
+            }
+        }
+
+        let drawn = {
+            let game = &mut self.peers[0].game;
+            on_playfield(phi, |phi| {
+                game.draw(phi, elapsed);
+                Ok(())
+            })
+        };
+        if let Err(err) = drawn {
+            return ViewAction::ChangeView(Box::new(ErrorView::new(&err)));
+        }
+        self.render_status(phi);
+
+        ViewAction::None
+    }
+}
+
+/// Runs `f` with the renderer scaled from `NET_PLAYFIELD` to the window, so
+/// that the game is simulated and drawn on the playfield whatever the size of
+/// the window.
+fn on_playfield<T, F>(phi: &mut Phi, f: F) -> PhiResult<T>
+where F: FnOnce(&mut Phi) -> PhiResult<T> {
+    try!(phi.renderer.set_logical_size(NET_PLAYFIELD.0, NET_PLAYFIELD.1)
+        .map_err(|err| PhiError::Network(format!("cannot scale the playfield: {}", err))));
+    let result = f(phi);
+    // Back to the whole window, for what is drawn over the game
+    try!(phi.renderer.set_logical_size(0, 0)
+        .map_err(|err| PhiError::Network(format!("cannot scale the playfield: {}", err))));
+    result
+}
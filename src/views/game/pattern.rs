@@ -0,0 +1,170 @@
+use ::std::f64::consts::PI;
+
+/// How an enemy ship moves. A pattern gives the ship's velocity from the time
+/// since the pattern started, so patterns can be chained with `then` and
+/// summed with `with` to build new behaviours from existing ones.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pattern {
+    /// Constant velocity, in pixels per second along x and y.
+    Straight(f64, f64),
+    /// Oscillates vertically, up to `amplitude` pixels away from where it
+    /// started, once every `period` seconds. Without a positive `period`, it
+    /// does not move at all.
+    Sine { amplitude: f64, period: f64 },
+    /// Heads for the player at `speed` pixels per second, turning by at most
+    /// `turn` radians per second. Once past the player, keeps going straight.
+    Dive { speed: f64, turn: f64 },
+    /// Follows the player's height at up to `speed` pixels per second.
+    Strafe { speed: f64 },
+    /// Stays in place.
+    Stop,
+    /// Moves according to the sum of several patterns.
+    Sum(Vec<Pattern>),
+    /// Follows each pattern for the given number of seconds. The last one
+    /// goes on forever.
+    Sequence(Vec<(Pattern, f64)>),
+}
+
+/// What a pattern may react to.
+#[derive(Clone, Copy, Debug)]
+pub struct Situation {
+    /// The centre of the enemy
+    pub position: (f64, f64),
+    /// The velocity of the enemy during the previous frame
+    pub velocity: (f64, f64),
+    /// The centre of the player's ship
+    pub target: (f64, f64),
+    /// Seconds since the previous frame
+    pub dt: f64,
+}
+
+impl Pattern {
+    /// Follows this pattern for `duration` seconds, then `next`.
+    pub fn then(self, duration: f64, next: Pattern) -> Pattern {
+        match self {
+            Pattern::Sequence(mut steps) => {
+                // The last step lasted forever until now.
+                if let Some(last) = steps.last_mut() {
+                    last.1 = duration;
+                }
+                steps.push((next, 0.0));
+                Pattern::Sequence(steps)
+            },
+            pattern => Pattern::Sequence(vec![(pattern, duration), (next, 0.0)]),
+        }
+    }
+
+    /// Moves according to both this pattern and `other`.
+    pub fn with(self, other: Pattern) -> Pattern {
+        match self {
+            Pattern::Sum(mut patterns) => {
+                patterns.push(other);
+                Pattern::Sum(patterns)
+            },
+            pattern => Pattern::Sum(vec![pattern, other]),
+        }
+    }
+
+    /// The velocity of an enemy `time` seconds after it started following
+    /// this pattern.
+    pub fn velocity(&self, time: f64, situation: &Situation) -> (f64, f64) {
+        match *self {
+            Pattern::Straight(x, y) => (x, y),
+
+            Pattern::Sine { amplitude, period } => {
+                // Would divide by zero, and move the enemy to NaN
+                if period <= 0.0 || !period.is_finite() {
+                    return (0.0, 0.0);
+                }
+
+                let pulsation = 2.0 * PI / period;
+                (0.0, amplitude * pulsation * (pulsation * time).cos())
+            },
+
+            Pattern::Dive { speed, turn } => {
+                let (x, y) = situation.position;
+                let (tx, ty) = situation.target;
+                let (vx, vy) = situation.velocity;
+                let current = if vx == 0.0 && vy == 0.0 { PI } else { vy.atan2(vx) };
+
+                // Past the player: no turning back.
+                let heading = if x <= tx {
+                    current
+                } else {
+                    let wanted = (ty - y).atan2(tx - x);
+                    // Shortest signed angle from the current heading
+                    let mut delta = (wanted - current) % (2.0 * PI);
+                    if delta > PI { delta -= 2.0 * PI; }
+                    if delta < -PI { delta += 2.0 * PI; }
+
+                    let max_turn = turn * situation.dt;
+                    current + delta.max(-max_turn).min(max_turn)
+                };
+
+                (speed * heading.cos(), speed * heading.sin())
+            },
+
+            Pattern::Strafe { speed } => {
+                let dy = situation.target.1 - situation.position.1;
+                // Slow down when close, so as not to shake around the target.
+                let vy = if situation.dt > 0.0 { dy / situation.dt } else { 0.0 };
+                (0.0, vy.max(-speed).min(speed))
+            },
+
+            Pattern::Stop => (0.0, 0.0),
+
+            Pattern::Sum(ref patterns) => patterns.iter()
+                .map(|pattern| pattern.velocity(time, situation))
+                .fold((0.0, 0.0), |(x, y), (dx, dy)| (x + dx, y + dy)),
+
+            Pattern::Sequence(ref steps) => {
+                let mut start = 0.0;
+
+                for (i, &(ref pattern, duration)) in steps.iter().enumerate() {
+                    if i + 1 == steps.len() || time < start + duration {
+                        return pattern.velocity(time - start, situation);
+                    }
+                    start += duration;
+                }
+
+                (0.0, 0.0)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn situation() -> Situation {
+        Situation {
+            position: (400.0, 300.0),
+            velocity: (0.0, 0.0),
+            target: (100.0, 300.0),
+            dt: 1.0 / 60.0,
+        }
+    }
+
+    #[test]
+    fn sine_without_a_period_stays_put() {
+        for &period in &[0.0, -1.0, ::std::f64::NAN, ::std::f64::INFINITY] {
+            let sine = Pattern::Sine { amplitude: 80.0, period: period };
+            assert_eq!(sine.velocity(1.0, &situation()), (0.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn sequences_switch_after_each_duration() {
+        let pattern = Pattern::Straight(-100.0, 0.0).then(2.0, Pattern::Stop);
+
+        assert_eq!(pattern.velocity(1.0, &situation()), (-100.0, 0.0));
+        assert_eq!(pattern.velocity(3.0, &situation()), (0.0, 0.0));
+    }
+
+    #[test]
+    fn sums_add_velocities() {
+        let pattern = Pattern::Straight(-100.0, 0.0).with(Pattern::Straight(0.0, 50.0));
+        assert_eq!(pattern.velocity(0.0, &situation()), (-100.0, 50.0));
+    }
+}
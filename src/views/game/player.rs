@@ -0,0 +1,77 @@
+use ::phi::Phi;
+use ::phi::config::{Config, Fields, ParseError};
+use ::phi::error::PhiResult;
+use ::sdl2::pixels::Color;
+use ::views::game::score::Score;
+use ::views::game::ship::{Controls, Ship, ShipInput};
+
+/// Where each player's ship appears, as a proportion of the screen's height.
+const SPAWN_HEIGHTS: [f64; 2] = [0.1, 0.6];
+/// Pixels between the left edge of the screen and the ships' spawn points.
+const SPAWN_X: f64 = 64.0;
+/// How each player's ship is recoloured. The first one keeps its colors.
+const TINTS: [(u8, u8, u8); 2] = [(255, 255, 255), (255, 150, 120)];
+
+/// Someone playing the game, with their own ship, controls and score.
+#[derive(Clone)]
+pub struct Player {
+    pub ship: Ship,
+    pub score: Score,
+    pub controls: Controls,
+}
+
+impl Player {
+    /// The `index`th player to join, starting from 0, of which there may be
+    /// two.
+    pub fn new(phi: &mut Phi, index: usize) -> PhiResult<Player> {
+        let (_, win_h) = phi.output_size();
+        let mut ship = try!(Ship::new(phi, (SPAWN_X, win_h * SPAWN_HEIGHTS[index])));
+
+        let (r, g, b) = TINTS[index];
+        ship.tint(Color::RGB(r, g, b));
+
+        Ok(Player {
+            ship: ship,
+            score: Score::new(),
+            controls: if index == 0 { Controls::Primary } else { Controls::Secondary },
+        })
+    }
+
+    pub fn input(&self, phi: &Phi) -> ShipInput {
+        ShipInput::from_events(phi, self.controls)
+    }
+
+    /// The section of a save file holding the `index`th player.
+    pub fn section(index: usize) -> String {
+        format!("player {}", index + 1)
+    }
+
+    /// Writes the player's ship and score to `section` of a save file.
+    pub fn save(&self, config: &mut Config, section: &str) {
+        config.set(section, "score", self.score.save());
+        self.ship.save(config, section);
+    }
+
+    /// Restores what `save` wrote into a new player.
+    pub fn load(&mut self, config: &Config, section: &str) -> Result<(), ParseError> {
+        for entry in config.entries().iter().filter(|entry| entry.section == section) {
+            let mut fields = Fields::new(entry);
+
+            if entry.key == "score" {
+                try!(self.score.load(&mut fields));
+            } else {
+                try!(self.ship.load(&entry.key, &mut fields));
+            }
+
+            try!(fields.end());
+        }
+
+        Ok(())
+    }
+
+    /// The centre of the ship, at which enemies aim.
+    pub fn center(&self) -> (f64, f64) {
+        (self.ship.rect.x + self.ship.rect.w / 2.0,
+         self.ship.rect.y + self.ship.rect.h / 2.0)
+    }
+}
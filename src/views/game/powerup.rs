@@ -0,0 +1,247 @@
+use ::phi::Phi;
+use ::phi::collision::{self, CollisionMask};
+use ::phi::config::{Config, Fields, Line, ParseError};
+use ::phi::data::Rectangle;
+use ::phi::debug::{DebugOverlay, HITBOX_COLOR};
+use ::phi::error::PhiResult;
+use ::phi::gfx::{CopySprite, Sprite};
+use ::std::cell::RefCell;
+use ::std::rc::Rc;
+
+const POWERUPS_PATH: &'static str = "assets/powerups.png";
+/// Icons are laid out on a single row, in the order of `PowerUpKind::all()`.
+const POWERUP_SIDE: f64 = 24.0;
+
+/// Pixels per second at which dropped power-ups drift to the left.
+const DRIFT_SPEED: f64 = 60.0;
+/// Pixels travelled up and down while drifting.
+const BOB_AMPLITUDE: f64 = 6.0;
+/// Seconds after which an uncollected power-up fades away.
+const LIFETIME: f64 = 12.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PowerUpKind {
+    /// Fire three shots in a fan
+    Spread,
+    /// Fire twice as often
+    Rapid,
+    /// Absorb every hit
+    Shield,
+    /// Move faster
+    Speed,
+    /// Gain a life
+    ExtraLife,
+    /// Destroy everything on screen
+    SmartBomb,
+}
+
+impl PowerUpKind {
+    pub fn all() -> [PowerUpKind; 6] {
+        [PowerUpKind::Spread, PowerUpKind::Rapid, PowerUpKind::Shield,
+         PowerUpKind::Speed, PowerUpKind::ExtraLife, PowerUpKind::SmartBomb]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PowerUpKind::Spread => "Spread",
+            PowerUpKind::Rapid => "Rapid",
+            PowerUpKind::Shield => "Shield",
+            PowerUpKind::Speed => "Speed",
+            PowerUpKind::ExtraLife => "Extra life",
+            PowerUpKind::SmartBomb => "Smart bomb",
+        }
+    }
+
+    /// Seconds during which the effect lasts, or `None` if it happens once
+    /// and for all when collected.
+    pub fn duration(self) -> Option<f64> {
+        match self {
+            PowerUpKind::Spread => Some(10.0),
+            PowerUpKind::Rapid => Some(10.0),
+            PowerUpKind::Shield => Some(8.0),
+            PowerUpKind::Speed => Some(10.0),
+            PowerUpKind::ExtraLife | PowerUpKind::SmartBomb => None,
+        }
+    }
+
+    /// How often it drops relative to other kinds.
+    fn weight(self) -> f64 {
+        match self {
+            PowerUpKind::Spread | PowerUpKind::Rapid => 3.0,
+            PowerUpKind::Shield | PowerUpKind::Speed => 2.0,
+            PowerUpKind::SmartBomb => 1.0,
+            PowerUpKind::ExtraLife => 0.5,
+        }
+    }
+
+    /// Picks a kind at random, proportionally to their weights.
+    pub fn random() -> PowerUpKind {
+        let kinds = PowerUpKind::all();
+        let total: f64 = kinds.iter().map(|kind| kind.weight()).sum();
+        let mut roll = ::phi::rng::random() * total;
+
+        for &kind in kinds.iter() {
+            roll -= kind.weight();
+            if roll < 0.0 {
+                return kind;
+            }
+        }
+
+        kinds[kinds.len() - 1]
+    }
+}
+
+/// A power-up floating on screen, waiting to be collected.
+#[derive(Clone)]
+struct PowerUp {
+    kind: PowerUpKind,
+    rect: Rectangle,
+    /// Height around which it bobs
+    base_y: f64,
+    /// Seconds since it was dropped
+    time: f64,
+}
+
+/// Every power-up on screen.
+#[derive(Clone)]
+pub struct PowerUps {
+    /// One icon per kind, in the order of `PowerUpKind::all()`
+    sprites: Vec<Sprite>,
+    powerups: Vec<PowerUp>,
+    /// Called with every collected power-up, e.g. to play a sound. Copies of
+    /// the power-ups, e.g. saved to be restored later, share it.
+    on_pickup: Option<Rc<RefCell<Box<FnMut(PowerUpKind)>>>>,
+}
+
+impl PowerUps {
+    pub fn new(phi: &mut Phi) -> PhiResult<PowerUps> {
+        let spritesheet = try!(Sprite::load(&mut phi.renderer, POWERUPS_PATH));
+        let sprites = try!((0..PowerUpKind::all().len()).map(|xth| {
+            spritesheet.region_of(POWERUPS_PATH, Rectangle {
+                w: POWERUP_SIDE,
+                h: POWERUP_SIDE,
+                x: POWERUP_SIDE * xth as f64,
+                y: 0.0,
+            })
+        }).collect());
+
+        Ok(PowerUps {
+            sprites: sprites,
+            powerups: Vec::new(),
+            on_pickup: None,
+        })
+    }
+
+    /// Sets the function called whenever a power-up is collected.
+    pub fn on_pickup(&mut self, hook: Box<FnMut(PowerUpKind)>) {
+        self.on_pickup = Some(Rc::new(RefCell::new(hook)));
+    }
+
+    /// Tells the hook set with `on_pickup` that a ship collected a power-up
+    /// of the given `kind`.
+    pub fn picked_up(&self, kind: PowerUpKind) {
+        if let Some(ref hook) = self.on_pickup {
+            (&mut *hook.borrow_mut())(kind);
+        }
+    }
+
+    /// With the given `chance`, in `[0, 1]`, drops a random power-up at the
+    /// centre of `rect`, where something was destroyed.
+    pub fn drop_from(&mut self, rect: Rectangle, chance: f64) {
+        if ::phi::rng::random() >= chance {
+            return;
+        }
+
+        let y = rect.y + (rect.h - POWERUP_SIDE) / 2.0;
+        self.powerups.push(PowerUp {
+            kind: PowerUpKind::random(),
+            rect: Rectangle {
+                x: rect.x + (rect.w - POWERUP_SIDE) / 2.0,
+                y: y,
+                w: POWERUP_SIDE,
+                h: POWERUP_SIDE,
+            },
+            base_y: y,
+            time: 0.0,
+        });
+    }
+
+    pub fn update(&mut self, dt: f64) {
+        for powerup in &mut self.powerups {
+            powerup.time += dt;
+            powerup.rect.x -= DRIFT_SPEED * dt;
+            powerup.rect.y = powerup.base_y + BOB_AMPLITUDE * (powerup.time * 4.0).sin();
+        }
+
+        self.powerups.retain(|p| p.time < LIFETIME && p.rect.x > -p.rect.w);
+    }
+
+    /// Collects the first power-up touching an object occupying `rect`,
+    /// whose solid pixels are `mask` if known, and returns its kind.
+    pub fn collect(&mut self, rect: Rectangle, mask: Option<&CollisionMask>) -> Option<PowerUpKind> {
+        let index = {
+            let sprites = &self.sprites;
+            self.powerups.iter().position(|p| {
+                let sprite = &sprites[p.kind as usize];
+                collision::collides(p.rect, sprite.mask(), rect, mask)
+            })
+        };
+
+        index.map(|index| self.powerups.remove(index).kind)
+    }
+
+    /// Shows the hitbox of every power-up on screen.
+    pub fn debug(&self, overlay: &mut DebugOverlay) {
+        for powerup in &self.powerups {
+            overlay.rect(powerup.rect, HITBOX_COLOR);
+        }
+
+        overlay.count("power-ups", self.powerups.len());
+    }
+
+    /// Writes every power-up on screen to the `powerups` section of a save
+    /// file.
+    pub fn save(&self, config: &mut Config) {
+        for powerup in &self.powerups {
+            config.add("powerups", "powerup", Line::new()
+                .word(powerup.kind as usize).rect(powerup.rect).word(powerup.base_y).word(powerup.time));
+        }
+    }
+
+    /// Restores what `save` wrote.
+    pub fn load(&mut self, config: &Config) -> Result<(), ParseError> {
+        for entry in config.entries().iter().filter(|entry| entry.section == "powerups") {
+            let mut fields = Fields::new(entry);
+            if entry.key != "powerup" {
+                return Err(fields.error("unknown entry"));
+            }
+
+            let kind = match PowerUpKind::all().get(try!(fields.word::<usize>())) {
+                Some(&kind) => kind,
+                None => return Err(fields.error("invalid power-up")),
+            };
+
+            self.powerups.push(PowerUp {
+                kind: kind,
+                rect: try!(fields.rect()),
+                base_y: try!(fields.word()),
+                time: try!(fields.word()),
+            });
+            try!(fields.end());
+        }
+
+        Ok(())
+    }
+
+    pub fn render(&self, phi: &mut Phi) {
+        for powerup in &self.powerups {
+            // Blink during the last seconds
+            let left = LIFETIME - powerup.time;
+            if left < 3.0 && (left * 8.0) as u32 % 2 == 1 {
+                continue;
+            }
+
+            phi.renderer.copy_sprite(&self.sprites[powerup.kind as usize], powerup.rect);
+        }
+    }
+}
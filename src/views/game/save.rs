@@ -0,0 +1,39 @@
+//! Games in progress written to a file, to be resumed later.
+//!
+//! A save file is read and written like the other configuration files. Every
+//! object of the game is saved on a line of numbers, in the section of
+//! whatever holds it:
+//!
+//! ```text
+//! version = 1
+//! time = 83.4
+//! rng = 1751460218846155387
+//!
+//! [player 1]
+//! ship = 64 300 43 39 3 2 3 0 0 0
+//! effect = 1 4.2
+//! shot = 260.5 312 16 8 600 0
+//!
+//! [asteroids]
+//! asteroid = 900.2 140 96 96 -180 0 3 0.0666 1.25
+//! ```
+//!
+//! Sprites are never saved. Objects load them again from the asset files they
+//! always use, except for the backgrounds, whose lines start with the path
+//! of their image.
+
+use ::phi::Phi;
+use ::std::path::PathBuf;
+
+pub const SAVE_VERSION: u32 = 1;
+const SAVE_FILE: &'static str = "savegame.cfg";
+
+/// Where the game in progress is saved, next to the settings.
+pub fn save_path(phi: &Phi) -> Option<PathBuf> {
+    phi.config_path(SAVE_FILE)
+}
+
+/// Whether there is a saved game to resume. Saves are deleted once resumed.
+pub fn has_save(phi: &Phi) -> bool {
+    save_path(phi).map_or(false, |path| path.exists())
+}
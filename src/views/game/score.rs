@@ -0,0 +1,194 @@
+use ::phi::config::{Fields, Line, ParseError};
+
+/// Seconds after a kill during which the next one continues the combo.
+const COMBO_WINDOW: f64 = 2.5;
+/// Consecutive kills needed to raise the multiplier by one.
+const KILLS_PER_LEVEL: u32 = 5;
+const MAX_MULTIPLIER: u32 = 8;
+
+/// Points for destroying an asteroid, for every 32 pixels of its side.
+const ASTEROID_POINTS: u64 = 50;
+
+/// The points earned by the player, and the combo multiplier applied to new
+/// ones.
+///
+/// Every `KILLS_PER_LEVEL` kills in a row raise the multiplier. Whenever
+/// `COMBO_WINDOW` seconds go by without a kill, it drops back by one level.
+#[derive(Clone)]
+pub struct Score {
+    points: u64,
+    multiplier: u32,
+    /// Kills since the multiplier last changed
+    streak: u32,
+    /// Seconds left before the multiplier decays
+    combo_left: f64,
+}
+
+impl Score {
+    pub fn new() -> Score {
+        Score {
+            points: 0,
+            multiplier: 1,
+            streak: 0,
+            combo_left: 0.0,
+        }
+    }
+
+    pub fn points(&self) -> u64 {
+        self.points
+    }
+
+    pub fn multiplier(&self) -> u32 {
+        self.multiplier
+    }
+
+    /// The proportion of the combo window left before the multiplier decays,
+    /// in [0, 1].
+    pub fn combo_left(&self) -> f64 {
+        (self.combo_left / COMBO_WINDOW).max(0.0).min(1.0)
+    }
+
+    /// The base value of an asteroid whose side is `side` pixels long.
+    pub fn asteroid_points(side: f64) -> u64 {
+        ((side / 32.0).round() as u64).max(1) * ASTEROID_POINTS
+    }
+
+    /// Awards `base` points, multiplied by the current combo, and extends the
+    /// combo.
+    pub fn kill(&mut self, base: u64) {
+        self.points += base * self.multiplier as u64;
+        self.combo_left = COMBO_WINDOW;
+        self.streak += 1;
+
+        if self.streak >= KILLS_PER_LEVEL && self.multiplier < MAX_MULTIPLIER {
+            self.multiplier += 1;
+            self.streak = 0;
+        }
+    }
+
+    /// Ends the combo at once, e.g. when the player gets hit.
+    pub fn break_combo(&mut self) {
+        self.multiplier = 1;
+        self.streak = 0;
+        self.combo_left = 0.0;
+    }
+
+    /// Lets the combo decay when no kill happened for too long.
+    pub fn update(&mut self, dt: f64) {
+        if self.combo_left <= 0.0 {
+            return;
+        }
+
+        self.combo_left -= dt;
+
+        if self.combo_left <= 0.0 {
+            self.streak = 0;
+
+            if self.multiplier > 1 {
+                self.multiplier -= 1;
+                self.combo_left = COMBO_WINDOW;
+            }
+        }
+    }
+
+    /// The score, as written to a save file.
+    pub fn save(&self) -> Line {
+        Line::new().word(self.points).word(self.multiplier).word(self.streak).word(self.combo_left)
+    }
+
+    pub fn load(&mut self, fields: &mut Fields) -> Result<(), ParseError> {
+        self.points = try!(fields.word());
+        self.multiplier = try!(fields.word::<u32>()).max(1).min(MAX_MULTIPLIER);
+        self.streak = try!(fields.word());
+        self.combo_left = try!(fields.word());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A score whose multiplier was raised to `multiplier`.
+    fn score_at(multiplier: u32) -> Score {
+        let mut score = Score::new();
+        for _ in 0..KILLS_PER_LEVEL * (multiplier - 1) {
+            score.kill(1);
+        }
+        assert_eq!(score.multiplier(), multiplier);
+        score
+    }
+
+    #[test]
+    fn raises_the_multiplier_every_few_kills() {
+        let mut score = Score::new();
+
+        for _ in 0..KILLS_PER_LEVEL - 1 {
+            score.kill(100);
+        }
+        assert_eq!(score.multiplier(), 1);
+
+        score.kill(100);
+        assert_eq!(score.multiplier(), 2);
+        assert_eq!(score.points(), 100 * KILLS_PER_LEVEL as u64);
+
+        score.kill(100);
+        assert_eq!(score.points(), 100 * KILLS_PER_LEVEL as u64 + 200);
+    }
+
+    #[test]
+    fn multiplier_is_capped() {
+        let mut score = score_at(MAX_MULTIPLIER);
+
+        for _ in 0..KILLS_PER_LEVEL * 2 {
+            score.kill(1);
+        }
+        assert_eq!(score.multiplier(), MAX_MULTIPLIER);
+    }
+
+    #[test]
+    fn steps_down_when_the_window_expires() {
+        let mut score = score_at(3);
+
+        score.update(COMBO_WINDOW - 0.1);
+        assert_eq!(score.multiplier(), 3);
+
+        score.update(0.2);
+        assert_eq!(score.multiplier(), 2);
+        assert_eq!(score.combo_left(), 1.0);
+
+        score.update(COMBO_WINDOW);
+        assert_eq!(score.multiplier(), 1);
+
+        score.update(COMBO_WINDOW);
+        assert_eq!(score.multiplier(), 1);
+        assert_eq!(score.combo_left(), 0.0);
+    }
+
+    #[test]
+    fn expired_window_resets_the_streak() {
+        let mut score = Score::new();
+        for _ in 0..KILLS_PER_LEVEL - 1 {
+            score.kill(1);
+        }
+
+        score.update(COMBO_WINDOW);
+        score.kill(1);
+        assert_eq!(score.multiplier(), 1);
+    }
+
+    #[test]
+    fn breaking_the_combo_starts_over() {
+        let mut score = score_at(4);
+        for _ in 0..KILLS_PER_LEVEL - 1 {
+            score.kill(1);
+        }
+
+        score.break_combo();
+        assert_eq!(score.multiplier(), 1);
+        assert_eq!(score.combo_left(), 0.0);
+
+        score.kill(1);
+        assert_eq!(score.multiplier(), 1);
+    }
+}
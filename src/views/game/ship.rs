@@ -0,0 +1,535 @@
+use ::phi::Phi;
+use ::phi::collision::CollisionMask;
+use ::phi::config::{Config, Fields, Line, ParseError};
+use ::phi::data::Rectangle;
+use ::phi::error::PhiResult;
+use ::phi::debug::{DebugOverlay, HITBOX_COLOR};
+use ::phi::gfx::{CopySprite, DrawRectangle, Sprite};
+use ::phi::rollback::NetInput;
+use ::sdl2::pixels::Color;
+use ::views::game::powerup::PowerUpKind;
+use ::views::game::weapons::Weapon;
+use ::views::options::AUTO_FIRE;
+
+const SHIP_PATH: &'static str = "assets/spaceship.png";
+
+/// Pixels travelled by the player's ship every second when it is moving.
+const PLAYER_SPEED: f64 = 180.0;
+/// Multiplies the ship's speed while the speed boost is active.
+const SPEED_BOOST: f64 = 1.6;
+
+const SHIP_W: f64 = 43.0;
+const SHIP_H: f64 = 39.0;
+
+/// Hits the ship can take before losing a life.
+const MAX_HEALTH: u32 = 3;
+/// Lives at the start of a game, including the first one.
+const STARTING_LIVES: u32 = 3;
+const MAX_LIVES: u32 = 9;
+
+/// Seconds during which the ship cannot be hit again after taking damage.
+const HIT_INVULNERABILITY: f64 = 1.5;
+/// Seconds during which the ship cannot be hit after respawning.
+const RESPAWN_INVULNERABILITY: f64 = 3.0;
+/// Seconds between losing a life and the ship reappearing.
+const RESPAWN_DELAY: f64 = 2.0;
+/// Times per second the ship blinks while invulnerable.
+const BLINK_RATE: f64 = 10.0;
+
+/// The different states the ship might be in. In the image, they're ordered
+/// from left to right, then from top to bottom.
+#[derive(Clone, Copy)]
+enum ShipFrame {
+    UpNorm      = 0,
+    UpFast      = 1,
+    UpSlow      = 2,
+    MidNorm     = 3,
+    MidFast     = 4,
+    MidSlow     = 5,
+    DownNorm    = 6,
+    DownFast    = 7,
+    DownSlow    = 8,
+}
+
+impl ShipFrame {
+    /// The frame whose number is `index`, as given by `frame as usize`.
+    fn from_index(index: usize) -> Option<ShipFrame> {
+        [ShipFrame::UpNorm, ShipFrame::UpFast, ShipFrame::UpSlow,
+         ShipFrame::MidNorm, ShipFrame::MidFast, ShipFrame::MidSlow,
+         ShipFrame::DownNorm, ShipFrame::DownFast, ShipFrame::DownSlow]
+            .get(index).cloned()
+    }
+}
+
+/// The state of the controls of a ship during a frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ShipInput {
+    pub up: bool,
+    pub down: bool,
+    pub left: bool,
+    pub right: bool,
+    pub fire: bool,
+}
+
+/// The device from which a player controls their ship.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Controls {
+    /// The arrow keys and the fire key
+    Primary,
+    /// The second player's keys, or a gamepad
+    Secondary,
+}
+
+impl Controls {
+    /// Whether the fire button was pressed during the frame, e.g. to join
+    /// the game.
+    pub fn fire_pressed(self, phi: &Phi) -> bool {
+        match self {
+            Controls::Primary => phi.events.now.key_fire == Some(true),
+            Controls::Secondary =>
+                phi.events.now.key_p2_fire == Some(true) || phi.events.now.pad_a == Some(true),
+        }
+    }
+}
+
+impl ShipInput {
+    /// Reads the controls of a player from the keyboard or a gamepad.
+    pub fn from_events(phi: &Phi, controls: Controls) -> ShipInput {
+        let events = &phi.events;
+        let (up, down, left, right, fire) = match controls {
+            Controls::Primary =>
+                (events.key_up, events.key_down, events.key_left, events.key_right,
+                 events.key_fire),
+            Controls::Secondary =>
+                (events.key_p2_up || events.pad_up, events.key_p2_down || events.pad_down,
+                 events.key_p2_left || events.pad_left, events.key_p2_right || events.pad_right,
+                 events.key_p2_fire || events.pad_a),
+        };
+
+        ShipInput {
+            up: up,
+            down: down,
+            left: left,
+            right: right,
+            // With auto-fire, holding the key keeps shooting; otherwise every
+            // shot needs its own key press.
+            fire: if phi.settings.gameplay(AUTO_FIRE, true) { fire }
+                  else { controls.fire_pressed(phi) },
+        }
+    }
+}
+
+impl NetInput for ShipInput {
+    fn to_byte(&self) -> u8 {
+        (self.up as u8) | (self.down as u8) << 1 | (self.left as u8) << 2 |
+            (self.right as u8) << 3 | (self.fire as u8) << 4
+    }
+
+    fn from_byte(byte: u8) -> ShipInput {
+        ShipInput {
+            up: byte & 1 != 0,
+            down: byte & 1 << 1 != 0,
+            left: byte & 1 << 2 != 0,
+            right: byte & 1 << 3 != 0,
+            fire: byte & 1 << 4 != 0,
+        }
+    }
+}
+
+/// A power-up whose effect is still running.
+#[derive(Clone, Copy, Debug)]
+pub struct Effect {
+    pub kind: PowerUpKind,
+    /// Seconds left
+    pub left: f64,
+}
+
+#[derive(Clone)]
+pub struct Ship {
+    pub rect: Rectangle,
+    /// Pixels travelled every second when moving, without power-ups
+    pub speed: f64,
+    sprites: Vec<Sprite>,
+    current: ShipFrame,
+    pub weapon: Weapon,
+
+    pub health: u32,
+    /// Lives left, including the current one.
+    pub lives: u32,
+    /// Seconds left during which the ship cannot be hurt
+    invulnerable: f64,
+    /// Seconds left before the ship reappears, if it was destroyed
+    respawn_in: Option<f64>,
+    /// Where the ship appears at the start and after losing a life
+    spawn_point: (f64, f64),
+    /// The power-ups currently affecting the ship
+    effects: Vec<Effect>,
+    /// Whether the ship cannot be hurt at all, as set from the console
+    pub god: bool,
+}
+
+/// The state of a ship as written on the `ship` line of a save file.
+struct ShipLine {
+    rect: Rectangle,
+    current: ShipFrame,
+    health: u32,
+    lives: u32,
+    invulnerable: f64,
+    respawn_in: Option<f64>,
+}
+
+impl ShipLine {
+    /// Reads the line written by `Ship::save`, refusing a ship which could
+    /// never be in play: one on screen always has health and a life left,
+    /// while one waiting to respawn may have lost its last one.
+    fn read(fields: &mut Fields) -> Result<ShipLine, ParseError> {
+        let rect = try!(fields.rect());
+        let current = match ShipFrame::from_index(try!(fields.word())) {
+            Some(frame) => frame,
+            None => return Err(fields.error("invalid frame")),
+        };
+        let health = try!(fields.word());
+        let lives = try!(fields.word());
+        let invulnerable = try!(fields.word());
+        let respawning = try!(fields.flag());
+        let respawn_in = try!(fields.word());
+
+        if health > MAX_HEALTH || (!respawning && health == 0) {
+            return Err(fields.error("invalid health"));
+        }
+        if lives > MAX_LIVES || (!respawning && lives == 0) {
+            return Err(fields.error("invalid lives"));
+        }
+
+        Ok(ShipLine {
+            rect: rect,
+            current: current,
+            health: health,
+            lives: lives,
+            invulnerable: invulnerable,
+            respawn_in: if respawning { Some(respawn_in) } else { None },
+        })
+    }
+}
+
+impl Ship {
+    pub fn new(phi: &mut Phi, spawn_point: (f64, f64)) -> PhiResult<Ship> {
+        let spritesheet = try!(Sprite::load(&mut phi.renderer, SHIP_PATH));
+        let mut sprites = Vec::with_capacity(9);
+
+        for y in 0..3 {
+            for x in 0..3 {
+                sprites.push(try!(spritesheet.region_of(SHIP_PATH, Rectangle {
+                    w: SHIP_W,
+                    h: SHIP_H,
+                    x: SHIP_W * x as f64,
+                    y: SHIP_H * y as f64,
+                })));
+            }
+        }
+
+        Ok(Ship {
+            rect: Rectangle {
+                x: spawn_point.0,
+                y: spawn_point.1,
+                w: SHIP_W,
+                h: SHIP_H,
+            },
+            speed: PLAYER_SPEED,
+            sprites: sprites,
+            current: ShipFrame::MidNorm,
+            weapon: try!(Weapon::blaster(phi)),
+
+            health: MAX_HEALTH,
+            lives: STARTING_LIVES,
+            invulnerable: 0.0,
+            respawn_in: None,
+            spawn_point: spawn_point,
+            effects: Vec::new(),
+            god: false,
+        })
+    }
+
+    /// Recolours the ship, e.g. to tell players apart.
+    pub fn tint(&mut self, color: Color) {
+        // Every frame comes from the same texture.
+        self.sprites[0].tint(color);
+    }
+
+    /// Whether the ship is on screen, rather than waiting to respawn.
+    pub fn is_alive(&self) -> bool {
+        self.respawn_in.is_none()
+    }
+
+    /// Whether the ship can currently take damage.
+    pub fn is_vulnerable(&self) -> bool {
+        !self.god && self.is_alive() && self.invulnerable == 0.0 && !self.has(PowerUpKind::Shield)
+    }
+
+    /// Whether a shield protects the ship, which still collides with things.
+    pub fn is_shielded(&self) -> bool {
+        self.is_alive() && self.has(PowerUpKind::Shield)
+    }
+
+    /// Whether the effect of a power-up of the given kind is running.
+    pub fn has(&self, kind: PowerUpKind) -> bool {
+        self.effects.iter().any(|effect| effect.kind == kind)
+    }
+
+    /// The power-ups currently affecting the ship.
+    pub fn effects(&self) -> &[Effect] {
+        &self.effects
+    }
+
+    /// Applies the effect of a collected power-up. Collecting one which is
+    /// already running restarts it. Smart bombs are left to the caller,
+    /// since they affect everything but the ship.
+    pub fn power_up(&mut self, kind: PowerUpKind) {
+        match kind.duration() {
+            Some(duration) => {
+                self.effects.retain(|effect| effect.kind != kind);
+                self.effects.push(Effect { kind: kind, left: duration });
+            },
+            None => if kind == PowerUpKind::ExtraLife {
+                self.lives = (self.lives + 1).min(MAX_LIVES);
+            },
+        }
+    }
+
+    /// Whether the ship lost its last life and its explosion is over.
+    pub fn is_game_over(&self) -> bool {
+        self.lives == 0 && self.respawn_in.map_or(false, |time| time <= 0.0)
+    }
+
+    /// The solid pixels of the frame currently shown.
+    pub fn mask(&self) -> Option<&CollisionMask> {
+        self.sprites[self.current as usize].mask()
+    }
+
+    /// Inflicts `amount` damage to the ship, unless it is invulnerable.
+    /// Returns whether the ship lost a life because of it.
+    pub fn damage(&mut self, amount: u32) -> bool {
+        if !self.is_vulnerable() {
+            return false;
+        }
+
+        self.health = self.health.saturating_sub(amount);
+
+        if self.health > 0 {
+            self.invulnerable = HIT_INVULNERABILITY;
+            return false;
+        }
+
+        // A ship on screen should always have a life to lose, but one without
+        // any stays on 0, and the game ends once it exploded.
+        self.lives = self.lives.saturating_sub(1);
+        self.respawn_in = Some(RESPAWN_DELAY);
+        // Power-ups are lost with the ship.
+        self.effects.clear();
+        true
+    }
+
+    /// Shows the ship's hitbox, while it is on screen, and its projectiles.
+    pub fn debug(&self, overlay: &mut DebugOverlay) {
+        if self.is_alive() {
+            overlay.rect(self.rect, HITBOX_COLOR);
+        }
+
+        self.weapon.projectiles.debug(overlay, "player shots");
+    }
+
+    /// Writes the ship, its power-ups and its projectiles to `section` of a
+    /// save file.
+    pub fn save(&self, config: &mut Config, section: &str) {
+        config.set(section, "ship", Line::new()
+            .rect(self.rect).word(self.current as usize)
+            .word(self.health).word(self.lives).word(self.invulnerable)
+            .flag(self.respawn_in.is_some()).word(self.respawn_in.unwrap_or(0.0)));
+
+        for effect in &self.effects {
+            config.add(section, "effect", Line::new().word(effect.kind as usize).word(effect.left));
+        }
+
+        config.set(section, "weapon", self.weapon.save());
+        self.weapon.projectiles.save(config, section, "shot");
+    }
+
+    /// Restores a line written by `save` under `key`.
+    pub fn load(&mut self, key: &str, fields: &mut Fields) -> Result<(), ParseError> {
+        match key {
+            "ship" => {
+                let line = try!(ShipLine::read(fields));
+                self.rect = line.rect;
+                self.current = line.current;
+                self.health = line.health;
+                self.lives = line.lives;
+                self.invulnerable = line.invulnerable;
+                self.respawn_in = line.respawn_in;
+            },
+            "effect" => {
+                let kind = match PowerUpKind::all().get(try!(fields.word::<usize>())) {
+                    Some(&kind) => kind,
+                    None => return Err(fields.error("invalid power-up")),
+                };
+                let left = try!(fields.word());
+                self.effects.push(Effect { kind: kind, left: left });
+            },
+            "weapon" => try!(self.weapon.load(fields)),
+            "shot" => try!(self.weapon.projectiles.load(fields)),
+            _ => return Err(fields.error("unknown entry")),
+        }
+
+        Ok(())
+    }
+
+    fn respawn(&mut self) {
+        self.rect.x = self.spawn_point.0;
+        self.rect.y = self.spawn_point.1;
+        self.current = ShipFrame::MidNorm;
+        self.health = MAX_HEALTH;
+        self.invulnerable = RESPAWN_INVULNERABILITY;
+        self.respawn_in = None;
+    }
+
+    /// Moves the ship according to `input`, keeping it inside of
+    /// `movable_region`, and moves its projectiles, which are removed when
+    /// they leave `screen`.
+    pub fn update(&mut self, input: ShipInput, dt: f64,
+                  movable_region: Rectangle, screen: Rectangle) {
+        self.weapon.projectiles.update(dt, screen);
+        self.invulnerable = (self.invulnerable - dt).max(0.0);
+
+        for effect in &mut self.effects {
+            effect.left -= dt;
+        }
+        self.effects.retain(|effect| effect.left > 0.0);
+
+        self.weapon.spread = if self.has(PowerUpKind::Spread) { 3 } else { 1 };
+        self.weapon.rate_multiplier = if self.has(PowerUpKind::Rapid) { 2.0 } else { 1.0 };
+
+        if let Some(time) = self.respawn_in {
+            let time = time - dt;
+            self.respawn_in = Some(time);
+
+            if time <= 0.0 && self.lives > 0 {
+                self.respawn();
+            }
+            return;
+        }
+
+        // Move player ship
+        let speed =
+            if self.has(PowerUpKind::Speed) { self.speed * SPEED_BOOST }
+            else { self.speed };
+
+        let diagonal =
+            (input.up ^ input.down) &&
+            (input.left ^ input.right);
+
+        let moved =
+            if diagonal { 1.0 / 2.0_f64.sqrt() }
+            else { 1.0 } * speed * dt;
+
+        let dx = match (input.left, input.right) {
+            (true, true) | (false, false) => 0.0,
+            (true, false) => -moved,
+            (false, true) => moved,
+        };
+
+        let dy = match (input.up, input.down) {
+            (true, true) | (false, false) => 0.0,
+            (true, false) => -moved,
+            (false, true) => moved,
+        };
+
+        self.rect.x += dx;
+        self.rect.y += dy;
+
+        // Bound player inside movable_region. If the window is too small for
+        // the ship to fit, it is kept at its top left corner instead.
+        self.rect = match self.rect.move_inside(movable_region) {
+            Some(rect) => rect,
+            None => Rectangle { x: movable_region.x, y: movable_region.y, ..self.rect },
+        };
+
+        // Select appropriate sprite of ship to show.
+        self.current =
+            if dx == 0.0 && dy < 0.0        { ShipFrame::UpNorm }
+            else if dx > 0.0 && dy < 0.0    { ShipFrame::UpFast }
+            else if dx < 0.0 && dy < 0.0    { ShipFrame::UpSlow }
+            else if dx == 0.0 && dy == 0.0  { ShipFrame::MidNorm }
+            else if dx > 0.0 && dy == 0.0   { ShipFrame::MidFast }
+            else if dx < 0.0 && dy == 0.0   { ShipFrame::MidSlow }
+            else if dx == 0.0 && dy > 0.0   { ShipFrame::DownNorm }
+            else if dx > 0.0 && dy > 0.0    { ShipFrame::DownFast }
+            else if dx < 0.0 && dy > 0.0    { ShipFrame::DownSlow }
+            else { unreachable!() };
+
+        // Fire from the nose of the ship
+        self.weapon.update(dt, input.fire, (
+            self.rect.x + self.rect.w,
+            self.rect.y + self.rect.h / 2.0));
+    }
+
+    pub fn render(&self, phi: &mut Phi) {
+        self.weapon.projectiles.render(phi);
+
+        if !self.is_alive() {
+            return;
+        }
+
+        // Blink while invulnerable, by skipping every other period.
+        if self.invulnerable > 0.0 && (self.invulnerable * BLINK_RATE) as u32 % 2 == 1 {
+            return;
+        }
+
+        phi.renderer.copy_sprite(&self.sprites[self.current as usize], self.rect);
+
+        // Outline the ship while shielded, blinking as the shield runs out.
+        let shield = self.effects.iter().find(|effect| effect.kind == PowerUpKind::Shield);
+        if let Some(effect) = shield {
+            if effect.left > 2.0 || (effect.left * 8.0) as u32 % 2 == 0 {
+                let outline = Rectangle {
+                    x: self.rect.x - 4.0,
+                    y: self.rect.y - 4.0,
+                    w: self.rect.w + 8.0,
+                    h: self.rect.h + 8.0,
+                };
+
+                phi.renderer.set_draw_color(Color::RGB(80, 200, 255));
+                phi.renderer.outline_rectangle(outline);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads `line` as the `ship` entry of a save file.
+    fn read(line: &str) -> Result<ShipLine, ParseError> {
+        let config = Config::parse(&format!("[player 1]\nship = {}\n", line)).unwrap();
+        ShipLine::read(&mut Fields::new(config.entry("player 1", "ship").unwrap()))
+    }
+
+    #[test]
+    fn reads_a_saved_ship() {
+        let line = read("10 20 43 39 3 2 1 0.5 0 0").unwrap();
+        assert_eq!((line.health, line.lives), (2, 1));
+        assert_eq!(line.respawn_in, None);
+
+        let line = read("10 20 43 39 3 0 0 0 1 1.5").unwrap();
+        assert_eq!((line.health, line.lives), (0, 0));
+        assert_eq!(line.respawn_in, Some(1.5));
+    }
+
+    #[test]
+    fn rejects_ships_without_health_or_lives() {
+        assert_eq!(read("10 20 43 39 3 0 2 0 0 0").err().map(|error| error.line), Some(2));
+        assert_eq!(read("10 20 43 39 3 2 0 0 0 0").err().map(|error| error.line), Some(2));
+        assert!(read("10 20 43 39 3 4 2 0 0 0").is_err());
+        assert!(read("10 20 43 39 3 2 10 0 1 1").is_err());
+        assert!(read("10 20 43 39 9 2 2 0 0 0").is_err());
+    }
+}
@@ -0,0 +1,227 @@
+use ::phi::Phi;
+use ::phi::collision::{self, CollisionMask};
+use ::phi::config::{Config, Fields, Line, ParseError};
+use ::phi::data::Rectangle;
+use ::phi::error::PhiResult;
+use ::phi::debug::{DebugOverlay, HITBOX_COLOR};
+use ::phi::gfx::{CopySprite, Sprite};
+
+const BULLET_PATH: &'static str = "assets/bullet.png";
+
+/// Number of projectiles allocated up front. The pool only grows if more
+/// than this many are ever on screen at once.
+const POOL_CAPACITY: usize = 64;
+
+/// A single shot travelling in a straight line.
+#[derive(Clone)]
+pub struct Projectile {
+    pub rect: Rectangle,
+    /// Velocity in pixels per second, along x and y.
+    vel: (f64, f64),
+    /// Dead projectiles are kept in the pool, waiting to be fired again.
+    alive: bool,
+}
+
+impl Projectile {
+    /// Removes the projectile from the screen, e.g. after it hit something.
+    pub fn kill(&mut self) {
+        self.alive = false;
+    }
+}
+
+/// Every projectile fired with the same sprite, recycled rather than
+/// allocated for each shot.
+#[derive(Clone)]
+pub struct ProjectilePool {
+    projectiles: Vec<Projectile>,
+    sprite: Sprite,
+}
+
+impl ProjectilePool {
+    pub fn new(sprite: Sprite) -> ProjectilePool {
+        let (w, h) = sprite.size();
+        let mut projectiles = Vec::with_capacity(POOL_CAPACITY);
+
+        for _ in 0..POOL_CAPACITY {
+            projectiles.push(Projectile {
+                rect: Rectangle { x: 0.0, y: 0.0, w: w, h: h },
+                vel: (0.0, 0.0),
+                alive: false,
+            });
+        }
+
+        ProjectilePool {
+            projectiles: projectiles,
+            sprite: sprite,
+        }
+    }
+
+    /// Fires a projectile centered on `(x, y)`, reusing a dead one if possible.
+    pub fn spawn(&mut self, x: f64, y: f64, vel: (f64, f64)) {
+        let (w, h) = self.sprite.size();
+        let rect = Rectangle { x: x - w / 2.0, y: y - h / 2.0, w: w, h: h };
+
+        if let Some(projectile) = self.projectiles.iter_mut().find(|p| !p.alive) {
+            projectile.rect = rect;
+            projectile.vel = vel;
+            projectile.alive = true;
+            return;
+        }
+
+        self.projectiles.push(Projectile {
+            rect: rect,
+            vel: vel,
+            alive: true,
+        });
+    }
+
+    /// Moves every projectile, and kills those which left `bounds`.
+    pub fn update(&mut self, dt: f64, bounds: Rectangle) {
+        for projectile in self.projectiles.iter_mut().filter(|p| p.alive) {
+            projectile.rect.x += projectile.vel.0 * dt;
+            projectile.rect.y += projectile.vel.1 * dt;
+
+            if !bounds.overlaps(projectile.rect) {
+                projectile.alive = false;
+            }
+        }
+    }
+
+    pub fn render(&self, phi: &mut Phi) {
+        for projectile in self.projectiles.iter().filter(|p| p.alive) {
+            phi.renderer.copy_sprite(&self.sprite, projectile.rect);
+        }
+    }
+
+    /// Kills the first projectile touching an object occupying `rect`, whose
+    /// solid pixels are `mask` if known. Returns whether there was one.
+    pub fn hit(&mut self, rect: Rectangle, mask: Option<&CollisionMask>) -> bool {
+        let sprite_mask = self.sprite.mask();
+
+        match self.projectiles.iter_mut()
+            .find(|p| p.alive && collision::collides(p.rect, sprite_mask, rect, mask)) {
+            Some(projectile) => {
+                projectile.alive = false;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Removes every projectile from the screen.
+    pub fn clear(&mut self) {
+        for projectile in &mut self.projectiles {
+            projectile.alive = false;
+        }
+    }
+
+    /// Shows the hitbox and velocity of every projectile on screen, counted
+    /// as `name`.
+    pub fn debug(&self, overlay: &mut DebugOverlay, name: &'static str) {
+        let mut count = 0;
+
+        for projectile in self.projectiles.iter().filter(|p| p.alive) {
+            overlay.rect(projectile.rect, HITBOX_COLOR);
+            overlay.velocity(projectile.rect, projectile.vel);
+            count += 1;
+        }
+
+        overlay.count(name, count);
+    }
+
+    /// Writes every projectile on screen to `section` of a save file, under
+    /// `key`.
+    pub fn save(&self, config: &mut Config, section: &str, key: &str) {
+        for projectile in self.projectiles.iter().filter(|p| p.alive) {
+            config.add(section, key, Line::new().rect(projectile.rect).pair(projectile.vel));
+        }
+    }
+
+    /// Fires again a projectile written by `save`, where it was.
+    pub fn load(&mut self, fields: &mut Fields) -> Result<(), ParseError> {
+        let rect = try!(fields.rect());
+        let vel = try!(fields.pair());
+        let (x, y) = (rect.x + rect.w / 2.0, rect.y + rect.h / 2.0);
+
+        self.spawn(x, y, vel);
+        Ok(())
+    }
+
+    /// The projectiles currently on screen.
+    pub fn alive_mut<'a>(&'a mut self) -> Box<Iterator<Item = &'a mut Projectile> + 'a> {
+        Box::new(self.projectiles.iter_mut().filter(|p| p.alive))
+    }
+}
+
+
+/// Fires projectiles at a limited rate.
+#[derive(Clone)]
+pub struct Weapon {
+    /// Shots per second
+    fire_rate: f64,
+    /// Pixels travelled by projectiles every second
+    projectile_speed: f64,
+    /// Seconds until the next shot is allowed
+    cooldown: f64,
+    /// Damage dealt by each projectile
+    pub damage: u32,
+    /// Projectiles fired at once, in a fan
+    pub spread: u32,
+    /// Multiplies `fire_rate`, e.g. while a power-up is active
+    pub rate_multiplier: f64,
+    pub projectiles: ProjectilePool,
+}
+
+impl Weapon {
+    pub fn new(sprite: Sprite, fire_rate: f64, projectile_speed: f64, damage: u32) -> Weapon {
+        Weapon {
+            fire_rate: fire_rate,
+            projectile_speed: projectile_speed,
+            cooldown: 0.0,
+            damage: damage,
+            spread: 1,
+            rate_multiplier: 1.0,
+            projectiles: ProjectilePool::new(sprite),
+        }
+    }
+
+    /// The weapon of the player's ship: a plasma gun shooting to the right.
+    pub fn blaster(phi: &mut Phi) -> PhiResult<Weapon> {
+        let sprite = try!(Sprite::load(&mut phi.renderer, BULLET_PATH));
+        Ok(Weapon::new(sprite, 8.0, 600.0, 1))
+    }
+
+    /// The weapon's state, as written to a save file. Its projectiles are
+    /// saved on their own lines.
+    pub fn save(&self) -> Line {
+        Line::new().word(self.cooldown)
+    }
+
+    pub fn load(&mut self, fields: &mut Fields) -> Result<(), ParseError> {
+        self.cooldown = try!(fields.word());
+        Ok(())
+    }
+
+    /// Advances the weapon's cooldown and, if `firing` and allowed to, fires
+    /// rightwards from `origin`.
+    pub fn update(&mut self, dt: f64, firing: bool, origin: (f64, f64)) {
+        self.cooldown = (self.cooldown - dt).max(0.0);
+
+        if !firing || self.cooldown > 0.0 {
+            return;
+        }
+
+        // Shots of a spread are 10 degrees apart.
+        let step = 10.0_f64.to_radians();
+        let first = -step * (self.spread.max(1) - 1) as f64 / 2.0;
+
+        for i in 0..self.spread.max(1) {
+            let angle = first + step * i as f64;
+            self.projectiles.spawn(origin.0, origin.1, (
+                self.projectile_speed * angle.cos(),
+                self.projectile_speed * angle.sin()));
+        }
+
+        self.cooldown = 1.0 / (self.fire_rate * self.rate_multiplier);
+    }
+}
@@ -0,0 +1,106 @@
+use ::phi::{Phi, View, ViewAction};
+use ::phi::ui::{Button, Label, Panel, Stack, Ui};
+use ::sdl2::pixels::Color;
+use ::views::error::or_error;
+use ::views::game::level::Level;
+use ::views::shared::{BgSet, menu_theme};
+
+/// The entries of the game over screen.
+#[derive(Clone, Copy)]
+enum GameOverAction {
+    Retry,
+    MainMenu,
+}
+
+/// Shown once the player lost every life or completed a level, with the
+/// final score.
+pub struct GameOverView {
+    ui: Ui<GameOverAction>,
+    /// The level to play again, if any, rather than endless waves
+    level: Option<Level>,
+
+    backgrounds: BgSet,
+}
+
+impl GameOverView {
+    /// The players lost every life while playing `level`, or endless waves,
+    /// with the given scores, one per player.
+    pub fn new(_phi: &mut Phi, backgrounds: BgSet, scores: &[u64], level: Option<Level>) -> GameOverView {
+        GameOverView::with_title("Game Over", Color::RGB(255, 90, 90), backgrounds, scores, level)
+    }
+
+    /// The players reached the end of `level`.
+    pub fn level_complete(_phi: &mut Phi, backgrounds: BgSet, scores: &[u64], level: Level) -> GameOverView {
+        GameOverView::with_title("Level Complete", Color::RGB(120, 255, 140), backgrounds, scores,
+                                 Some(level))
+    }
+
+    fn with_title(title: &str, color: Color, backgrounds: BgSet, scores: &[u64],
+                  level: Option<Level>) -> GameOverView {
+        let scores = if scores.len() == 1 {
+            format!("Score: {}", scores[0])
+        } else {
+            scores.iter().enumerate()
+                .map(|(i, score)| format!("P{}: {}", i + 1, score))
+                .collect::<Vec<_>>()
+                .join("   ")
+        };
+
+        GameOverView {
+            ui: Ui::new(
+                Panel::new(Stack::vertical()
+                    .with(Label::new(title).size(56).color(color))
+                    .with(Label::new(&scores))
+                    .with(Button::new("Retry", GameOverAction::Retry))
+                    .with(Button::new("Main Menu", GameOverAction::MainMenu)))
+                .min_width(360.0),
+                menu_theme()),
+            level: level,
+            backgrounds: backgrounds,
+        }
+    }
+}
+
+impl View for GameOverView {
+    fn render(&mut self, phi: &mut Phi, elapsed: f64) -> ViewAction {
+        if phi.events.now.quit {
+            return ViewAction::Quit;
+        }
+
+        if phi.events.now.key_escape == Some(true) {
+            return ViewAction::ChangeView(Box::new(
+                ::views::main_menu::MainMenuView::with_backgrounds(phi, self.backgrounds.clone())));
+        }
+
+        for action in self.ui.handle(&phi.events) {
+            match action {
+                GameOverAction::Retry => {
+                    let backgrounds = self.backgrounds.clone();
+                    return ViewAction::ChangeView(or_error(match self.level {
+                        Some(ref level) =>
+                            ::views::game::ShipView::with_level(phi, backgrounds, level.clone()),
+                        None =>
+                            ::views::game::ShipView::with_backgrounds(phi, backgrounds),
+                    }));
+                },
+
+                GameOverAction::MainMenu =>
+                    return ViewAction::ChangeView(Box::new(
+                        ::views::main_menu::MainMenuView::with_backgrounds(phi, self.backgrounds.clone()))),
+            }
+        }
+
+        // Clear the screen
+        phi.renderer.set_draw_color(Color::RGB(0, 0, 0));
+        phi.renderer.clear();
+
+        // Render backgrounds
+        self.backgrounds.back.render(&mut phi.renderer, elapsed);
+        self.backgrounds.middle.render(&mut phi.renderer, elapsed);
+        self.backgrounds.front.render(&mut phi.renderer, elapsed);
+
+        self.ui.render(phi, elapsed);
+
+        ViewAction::None
+    }
+}
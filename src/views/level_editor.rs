@@ -0,0 +1,506 @@
+use ::phi::{Phi, View, ViewAction};
+use ::phi::data::Rectangle;
+use ::phi::error::PhiResult;
+use ::phi::gfx::{AnimatedSprite, CopySprite, DrawRectangle, Sprite};
+use ::sdl2::keyboard::Keycode;
+use ::sdl2::pixels::Color;
+use ::std::collections::HashMap;
+use ::std::path::PathBuf;
+use ::views::error::or_error;
+use ::views::game::enemy::EnemyKind;
+use ::views::game::level::{Event, Level};
+use ::views::game::pattern::Pattern;
+use ::views::shared::{BgSet, FONT_PATH};
+
+const EDITOR_FONT_SIZE: i32 = 18;
+
+/// Horizontal pixels per second of the level on the playfield, so that events
+/// are laid out the way they come into view.
+const PIXELS_PER_SECOND: f64 = 80.0;
+/// Where the time cursor stands, as a proportion of the screen's width.
+const CURSOR_X: f64 = 0.15;
+/// Events are placed on tenths of seconds.
+const SNAPS_PER_SECOND: f64 = 10.0;
+/// Seconds moved by the arrow keys and by every notch of the mouse wheel.
+const SCRUB_STEP: f64 = 0.5;
+/// Asteroids appearing with every burst placed.
+const BURST_SIZE: u32 = 5;
+/// Pixels from an event within which right clicks remove it.
+const PICK_RADIUS: f64 = 24.0;
+
+const PALETTE_BOX_W: f64 = 104.0;
+const PALETTE_BOX_H: f64 = 28.0;
+const MARGIN: f64 = 8.0;
+const TIMELINE_H: f64 = 16.0;
+
+/// What left clicks on the playfield place.
+#[derive(Clone, Copy, PartialEq)]
+enum Tool {
+    /// An enemy of the given kind, following the selected pattern
+    Enemy(usize),
+    Asteroids,
+    Scroll(f64),
+    Boss,
+    End,
+}
+
+impl Tool {
+    fn all(kinds: &[EnemyKind]) -> Vec<Tool> {
+        let mut tools: Vec<Tool> = (0..kinds.len()).map(Tool::Enemy).collect();
+        tools.extend_from_slice(&[Tool::Asteroids, Tool::Scroll(2.0), Tool::Scroll(1.0),
+                                  Tool::Boss, Tool::End]);
+        tools
+    }
+
+    fn name(self, kinds: &[EnemyKind]) -> String {
+        match self {
+            Tool::Enemy(kind) => kinds[kind].name.to_string(),
+            Tool::Asteroids => "asteroids".to_string(),
+            Tool::Scroll(factor) => format!("scroll x{}", factor),
+            Tool::Boss => "boss".to_string(),
+            Tool::End => "end".to_string(),
+        }
+    }
+}
+
+/// The patterns enemies may be given instead of their own, with their name.
+fn pattern_presets() -> Vec<(&'static str, Option<Pattern>)> {
+    vec![
+        ("own", None),
+        ("straight", Some(Pattern::Straight(-160.0, 0.0))),
+        ("sine", Some(Pattern::Straight(-120.0, 0.0)
+            .with(Pattern::Sine { amplitude: 80.0, period: 2.0 }))),
+        ("dive", Some(Pattern::Straight(-100.0, 0.0)
+            .then(1.5, Pattern::Dive { speed: 320.0, turn: 2.0 }))),
+        ("strafe", Some(Pattern::Straight(-140.0, 0.0)
+            .then(1.2, Pattern::Strafe { speed: 90.0 })
+            .then(5.0, Pattern::Straight(-220.0, 0.0)))),
+        ("halt", Some(Pattern::Straight(-140.0, 0.0)
+            .then(1.0, Pattern::Stop)
+            .then(3.0, Pattern::Straight(-260.0, 0.0)))),
+    ]
+}
+
+fn inside(rect: Rectangle, (x, y): (f64, f64)) -> bool {
+    x >= rect.x && x < rect.x + rect.w && y >= rect.y && y < rect.y + rect.h
+}
+
+/// Lines of text rendered once, then reused every frame.
+struct Labels {
+    sprites: HashMap<String, Sprite>,
+}
+
+impl Labels {
+    fn draw(&mut self, phi: &mut Phi, text: &str, x: f64, y: f64) {
+        if !self.sprites.contains_key(text) {
+            match phi.ttf_str_sprite(text, FONT_PATH, EDITOR_FONT_SIZE, Color::RGB(255, 255, 255)) {
+                Some(sprite) => { self.sprites.insert(text.to_string(), sprite); },
+                None => return,
+            }
+        }
+
+        let sprite = &self.sprites[text];
+        let (w, h) = sprite.size();
+        phi.renderer.copy_sprite(sprite, Rectangle { x: x, y: y, w: w, h: h });
+    }
+}
+
+/// Edits the timeline of a level: left clicks on the playfield place the
+/// selected enemy or event where it would come into view, right clicks remove
+/// the nearest one, and the timeline at the bottom, the arrow keys or the
+/// mouse wheel scrub through time. `S` saves the level back to its file, `P`
+/// previews it from the cursor and `W` turns the usual waves on and off.
+pub struct LevelEditorView {
+    level: Level,
+    path: PathBuf,
+    /// Seconds into the level shown at the cursor
+    cursor: f64,
+    /// Whether the level changed since it was last saved
+    modified: bool,
+    /// Whether Escape was pressed once with unsaved changes
+    leaving: bool,
+    /// Feedback shown at the bottom of the screen, e.g. once saved
+    status: String,
+
+    kinds: Vec<EnemyKind>,
+    sprites: Vec<AnimatedSprite>,
+    tools: Vec<Tool>,
+    tool: usize,
+    patterns: Vec<(&'static str, Option<Pattern>)>,
+    pattern: usize,
+    /// Whether the timeline is being dragged
+    scrubbing: bool,
+    labels: Labels,
+
+    backgrounds: BgSet,
+}
+
+impl LevelEditorView {
+    /// Edits `level`, read from or to be saved at `path`, starting `cursor`
+    /// seconds in.
+    pub fn with_level(phi: &mut Phi, mut backgrounds: BgSet, level: Level, path: PathBuf,
+                      cursor: f64) -> PhiResult<LevelEditorView> {
+        backgrounds.set_scroll(1.0);
+
+        let kinds = EnemyKind::all();
+        let sprites = try!(EnemyKind::get_sprites(phi, &kinds));
+        let tools = Tool::all(&kinds);
+
+        Ok(LevelEditorView {
+            level: level,
+            path: path,
+            cursor: cursor,
+            modified: false,
+            leaving: false,
+            status: String::new(),
+
+            kinds: kinds,
+            sprites: sprites,
+            tools: tools,
+            tool: 0,
+            patterns: pattern_presets(),
+            pattern: 0,
+            scrubbing: false,
+            labels: Labels { sprites: HashMap::new() },
+
+            backgrounds: backgrounds,
+        })
+    }
+
+    /// The box of the `index`th entry of the palette's given `row`.
+    fn palette_box(row: usize, index: usize) -> Rectangle {
+        Rectangle {
+            x: MARGIN + index as f64 * (PALETTE_BOX_W + MARGIN),
+            y: MARGIN + row as f64 * (PALETTE_BOX_H + MARGIN),
+            w: PALETTE_BOX_W,
+            h: PALETTE_BOX_H,
+        }
+    }
+
+    fn timeline(screen: Rectangle) -> Rectangle {
+        Rectangle {
+            x: MARGIN * 2.0,
+            y: screen.h - TIMELINE_H - MARGIN * 2.0,
+            w: screen.w - MARGIN * 4.0,
+            h: TIMELINE_H,
+        }
+    }
+
+    /// Seconds covered by the timeline, leaving room to add events after the
+    /// last one.
+    fn timeline_length(&self) -> f64 {
+        (self.level.duration() + 20.0).max(60.0)
+    }
+
+    /// Where the events without a position, e.g. bursts, are laid out.
+    fn lane_y(screen: Rectangle) -> f64 {
+        screen.h - TIMELINE_H - MARGIN * 4.0 - PALETTE_BOX_H
+    }
+
+    fn time_to_x(&self, time: f64, screen: Rectangle) -> f64 {
+        screen.w * CURSOR_X + (time - self.cursor) * PIXELS_PER_SECOND
+    }
+
+    /// Where an event is drawn on the playfield.
+    fn event_position(&self, time: f64, event: &Event, screen: Rectangle) -> (f64, f64) {
+        let y = match *event {
+            Event::Enemy { y, .. } => y * screen.h,
+            _ => LevelEditorView::lane_y(screen),
+        };
+        (self.time_to_x(time, screen), y)
+    }
+
+    fn set_cursor(&mut self, time: f64) {
+        self.cursor = time.max(0.0).min(self.timeline_length());
+    }
+
+    fn changed(&mut self) {
+        self.modified = true;
+        self.leaving = false;
+        self.status.clear();
+    }
+
+    /// Places the selected tool at `(x, y)` on the playfield.
+    fn place(&mut self, (x, y): (f64, f64), screen: Rectangle) {
+        let time = self.cursor + (x - screen.w * CURSOR_X) / PIXELS_PER_SECOND;
+        // Dividing rather than multiplying by the snap, so that times are
+        // written as `0.3` and not `0.30000000000000004`
+        let time = (time * SNAPS_PER_SECOND).round() / SNAPS_PER_SECOND;
+        if time < 0.0 {
+            return;
+        }
+
+        let event = match self.tools[self.tool] {
+            Tool::Enemy(kind) => Event::Enemy {
+                kind: kind,
+                y: ((y / screen.h) * 100.0).round().max(0.0).min(100.0) / 100.0,
+                pattern: self.patterns[self.pattern].1.clone(),
+            },
+            Tool::Asteroids => Event::Asteroids(BURST_SIZE),
+            Tool::Scroll(factor) => Event::Scroll(factor),
+            Tool::Boss => Event::Boss,
+            Tool::End => Event::End,
+        };
+
+        self.level.add(time, event);
+        self.changed();
+    }
+
+    /// Removes the event drawn nearest to `(x, y)`, if close enough.
+    fn remove_near(&mut self, (x, y): (f64, f64), screen: Rectangle) {
+        let nearest = self.level.events().iter().enumerate()
+            .map(|(i, &(time, ref event))| {
+                let (ex, ey) = self.event_position(time, event, screen);
+                (i, ((ex - x).powi(2) + (ey - y).powi(2)).sqrt())
+            })
+            .filter(|&(_, distance)| distance < PICK_RADIUS)
+            .fold(None, |nearest: Option<(usize, f64)>, (i, distance)| match nearest {
+                Some((_, best)) if best <= distance => nearest,
+                _ => Some((i, distance)),
+            });
+
+        if let Some((i, _)) = nearest {
+            self.level.remove(i);
+            self.changed();
+        }
+    }
+
+    fn save(&mut self) {
+        self.status = match self.level.save(&self.path) {
+            Ok(()) => {
+                self.modified = false;
+                format!("Saved to {}", self.path.display())
+            },
+            Err(err) => format!("Could not save {}: {}", self.path.display(), err),
+        };
+    }
+
+    /// Picks from the palette, scrubs the timeline, or places and removes
+    /// events on the playfield, depending on where the mouse is clicked.
+    fn handle_mouse(&mut self, phi: &mut Phi, screen: Rectangle) {
+        let mouse = phi.events.mouse;
+        let timeline = LevelEditorView::timeline(screen);
+
+        if phi.events.now.mouse_left == Some(true) {
+            let tool = (0..self.tools.len())
+                .position(|i| inside(LevelEditorView::palette_box(0, i), mouse));
+            let pattern = (0..self.patterns.len())
+                .position(|i| inside(LevelEditorView::palette_box(1, i), mouse));
+
+            if let Some(tool) = tool {
+                self.tool = tool;
+            } else if let Some(pattern) = pattern {
+                self.pattern = pattern;
+            } else if inside(timeline, mouse) {
+                self.scrubbing = true;
+            } else {
+                self.place(mouse, screen);
+            }
+        }
+
+        if !phi.events.mouse_left {
+            self.scrubbing = false;
+        }
+
+        if self.scrubbing {
+            let length = self.timeline_length();
+            self.set_cursor((mouse.0 - timeline.x) / timeline.w * length);
+        }
+
+        if phi.events.now.mouse_right == Some(true) {
+            self.remove_near(mouse, screen);
+        }
+    }
+
+    fn render_box(phi: &mut Phi, rect: Rectangle, color: Color) {
+        phi.renderer.set_draw_color(color);
+        phi.renderer.fill_rectangle(rect);
+    }
+
+    fn render_palette(&mut self, phi: &mut Phi) {
+        for (i, &tool) in self.tools.iter().enumerate() {
+            let rect = LevelEditorView::palette_box(0, i);
+            let color = if i == self.tool { Color::RGB(200, 120, 40) } else { Color::RGB(40, 40, 70) };
+            LevelEditorView::render_box(phi, rect, color);
+            self.labels.draw(phi, &tool.name(&self.kinds), rect.x + 6.0, rect.y + 4.0);
+        }
+
+        for (i, &(name, _)) in self.patterns.iter().enumerate() {
+            let rect = LevelEditorView::palette_box(1, i);
+            let color = if i == self.pattern { Color::RGB(40, 140, 200) } else { Color::RGB(40, 40, 70) };
+            LevelEditorView::render_box(phi, rect, color);
+            self.labels.draw(phi, name, rect.x + 6.0, rect.y + 4.0);
+        }
+    }
+
+    fn render_events(&mut self, phi: &mut Phi, screen: Rectangle) {
+        for &(time, ref event) in self.level.events() {
+            let (x, y) = self.event_position(time, event, screen);
+            if x < -PICK_RADIUS || x > screen.w + PICK_RADIUS {
+                continue;
+            }
+
+            match *event {
+                Event::Enemy { kind, .. } => {
+                    let sprite = self.sprites[kind].current_sprite();
+                    let (w, h) = sprite.size();
+                    phi.renderer.copy_sprite(sprite, Rectangle {
+                        x: x - w / 2.0,
+                        y: y - h / 2.0,
+                        w: w,
+                        h: h,
+                    });
+                },
+
+                _ => {
+                    let label = match *event {
+                        Event::Asteroids(count) => format!("{} asteroids", count),
+                        Event::Scroll(factor) => format!("scroll x{}", factor),
+                        Event::Boss => "boss".to_string(),
+                        _ => "end".to_string(),
+                    };
+
+                    LevelEditorView::render_box(phi, Rectangle {
+                        x: x - 1.0,
+                        y: 2.0 * (PALETTE_BOX_H + MARGIN) + MARGIN,
+                        w: 2.0,
+                        h: (y - 2.0 * (PALETTE_BOX_H + MARGIN) - MARGIN).max(0.0),
+                    }, Color::RGB(120, 120, 160));
+                    LevelEditorView::render_box(phi, Rectangle {
+                        x: x - 6.0,
+                        y: y - 6.0,
+                        w: 12.0,
+                        h: 12.0,
+                    }, Color::RGB(220, 220, 90));
+                    self.labels.draw(phi, &label, x + 10.0, y - 10.0);
+                },
+            }
+        }
+    }
+
+    fn render_timeline(&mut self, phi: &mut Phi, screen: Rectangle) {
+        let timeline = LevelEditorView::timeline(screen);
+        let length = self.timeline_length();
+
+        LevelEditorView::render_box(phi, timeline, Color::RGB(40, 40, 70));
+
+        for &(time, _) in self.level.events() {
+            LevelEditorView::render_box(phi, Rectangle {
+                x: timeline.x + time / length * timeline.w - 1.0,
+                y: timeline.y,
+                w: 2.0,
+                h: timeline.h,
+            }, Color::RGB(220, 220, 90));
+        }
+
+        // The cursor on the timeline
+        LevelEditorView::render_box(phi, Rectangle {
+            x: timeline.x + self.cursor / length * timeline.w - 2.0,
+            y: timeline.y - 3.0,
+            w: 4.0,
+            h: timeline.h + 6.0,
+        }, Color::RGB(200, 120, 40));
+
+        // The cursor on the playfield
+        LevelEditorView::render_box(phi, Rectangle {
+            x: screen.w * CURSOR_X - 1.0,
+            y: 2.0 * (PALETTE_BOX_H + MARGIN) + MARGIN,
+            w: 2.0,
+            h: (timeline.y - 2.0 * (PALETTE_BOX_H + MARGIN) - MARGIN * 2.0).max(0.0),
+        }, Color::RGB(200, 120, 40));
+
+        let status = if !self.status.is_empty() {
+            self.status.clone()
+        } else {
+            format!("{}{}  {:.1}s  waves {}  [S] save  [P] preview  [W] waves",
+                    self.level.name, if self.modified { "*" } else { "" }, self.cursor,
+                    if self.level.waves { "on" } else { "off" })
+        };
+        self.labels.draw(phi, &status, timeline.x, timeline.y + timeline.h + 2.0);
+    }
+}
+
+impl View for LevelEditorView {
+    fn render(&mut self, phi: &mut Phi, elapsed: f64) -> ViewAction {
+        if phi.events.now.quit {
+            return ViewAction::Quit;
+        }
+
+        if phi.events.now.key_escape == Some(true) {
+            if !self.modified || self.leaving {
+                return ViewAction::ChangeView(Box::new(
+                    ::views::level_select::LevelSelectView::for_editing(phi, self.backgrounds.clone())));
+            }
+
+            self.leaving = true;
+            self.status = "Unsaved changes: press Escape again to leave anyway".to_string();
+        }
+
+        let (win_w, win_h) = phi.output_size();
+        let screen = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            w: win_w,
+            h: win_h,
+        };
+
+        let keycode = phi.events.now.keycode;
+        match keycode {
+            Some(Keycode::S) => self.save(),
+
+            Some(Keycode::P) => {
+                let mut backgrounds = self.backgrounds.clone();
+                backgrounds.set_scroll(1.0);
+                return ViewAction::ChangeView(or_error(::views::game::ShipView::preview(
+                    phi, backgrounds, self.level.clone(), self.path.clone(), self.cursor)));
+            },
+
+            Some(Keycode::W) => {
+                self.level.waves = !self.level.waves;
+                self.changed();
+            },
+
+            _ => {},
+        }
+
+        let cursor = self.cursor;
+        if phi.events.now.key_left == Some(true) {
+            self.set_cursor(cursor - SCRUB_STEP);
+        }
+        if phi.events.now.key_right == Some(true) {
+            self.set_cursor(cursor + SCRUB_STEP);
+        }
+        if phi.events.now.wheel != 0 {
+            let cursor = self.cursor;
+            self.set_cursor(cursor - phi.events.now.wheel as f64 * SCRUB_STEP);
+        }
+
+        self.handle_mouse(phi, screen);
+
+        for sprite in &mut self.sprites {
+            sprite.add_time(elapsed);
+        }
+
+        // Clear the screen
+        phi.renderer.set_draw_color(Color::RGB(0, 0, 0));
+        phi.renderer.clear();
+
+        // The backdrop follows the cursor, as fast as the level scrolls there.
+        let scroll = self.level.scroll_at(self.cursor);
+        self.backgrounds.set_scroll(scroll);
+        {
+            let cursor = self.cursor;
+            let backgrounds = &mut self.backgrounds;
+            for background in vec![&mut backgrounds.back, &mut backgrounds.middle, &mut backgrounds.front] {
+                background.pos = (background.vel * cursor) % background.sprite.size().0;
+                background.render(&mut phi.renderer, 0.0);
+            }
+        }
+
+        self.render_events(phi, screen);
+        self.render_palette(phi);
+        self.render_timeline(phi, screen);
+
+        ViewAction::None
+    }
+}
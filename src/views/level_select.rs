@@ -0,0 +1,153 @@
+use ::phi::{Phi, View, ViewAction};
+use ::phi::ui::{Button, Label, List, Panel, Stack, Ui};
+use ::sdl2::pixels::Color;
+use ::std::path::PathBuf;
+use ::views::error::or_error;
+use ::views::game::level::{LEVELS_DIR, Level};
+use ::views::shared::{BgSet, menu_theme};
+
+/// Levels shown at once in the list.
+const VISIBLE_LEVELS: usize = 6;
+
+#[derive(Clone, Copy)]
+enum LevelSelectAction {
+    /// Play, or edit, the given entry of `levels`.
+    Play(usize),
+    /// Edit a new level file.
+    New,
+    Back,
+}
+
+/// Lists the level files, and starts the one picked, or opens it in the level
+/// editor. Invalid files are listed too, so that their error can be shown when
+/// picked.
+pub struct LevelSelectView {
+    ui: Ui<LevelSelectAction>,
+    paths: Vec<PathBuf>,
+    /// Every level file, loaded, or the reason why it could not be
+    levels: Vec<Result<Level, String>>,
+    /// Whether picked levels are edited rather than played
+    editing: bool,
+
+    backgrounds: BgSet,
+}
+
+impl LevelSelectView {
+    pub fn with_backgrounds(phi: &mut Phi, backgrounds: BgSet) -> LevelSelectView {
+        LevelSelectView::with_mode(phi, backgrounds, false)
+    }
+
+    /// Lists the levels to open in the level editor, along with a new one.
+    pub fn for_editing(phi: &mut Phi, backgrounds: BgSet) -> LevelSelectView {
+        LevelSelectView::with_mode(phi, backgrounds, true)
+    }
+
+    fn with_mode(_phi: &mut Phi, backgrounds: BgSet, editing: bool) -> LevelSelectView {
+        let paths = Level::list();
+        let levels: Vec<Result<Level, String>> = paths.iter()
+            .map(|path| Level::load(path)
+                .map_err(|err| format!("{}: {}", path.display(), err)))
+            .collect();
+
+        let ui = LevelSelectView::build_ui(&levels, editing, None);
+
+        LevelSelectView {
+            ui: ui,
+            paths: paths,
+            levels: levels,
+            editing: editing,
+            backgrounds: backgrounds,
+        }
+    }
+
+    /// Creates the menu, with an `error` below the list if any.
+    fn build_ui(levels: &[Result<Level, String>], editing: bool, error: Option<&str>)
+                -> Ui<LevelSelectAction> {
+        let names: Vec<String> = levels.iter()
+            .map(|level| match *level {
+                Ok(ref level) => level.name.clone(),
+                Err(_) => "(invalid level)".to_string(),
+            })
+            .collect();
+
+        let mut menu = Stack::vertical()
+            .with(Label::new(if editing { "Edit level" } else { "Levels" }));
+
+        if names.is_empty() {
+            menu = menu.with(Label::new(&format!("No levels in {}", LEVELS_DIR)).size(24));
+        } else {
+            menu = menu.with(List::new(&names, VISIBLE_LEVELS, Box::new(LevelSelectAction::Play)));
+        }
+
+        if let Some(error) = error {
+            menu = menu.with(Label::new(error).size(20).color(Color::RGB(255, 90, 90)));
+        }
+
+        if editing {
+            menu = menu.with(Button::new("New level", LevelSelectAction::New));
+        }
+
+        Ui::new(Panel::new(menu.with(Button::new("Back", LevelSelectAction::Back)))
+                .min_width(480.0),
+                menu_theme())
+    }
+
+    fn leave(&self, phi: &mut Phi) -> ViewAction {
+        ViewAction::ChangeView(Box::new(
+            ::views::main_menu::MainMenuView::with_backgrounds(phi, self.backgrounds.clone())))
+    }
+}
+
+impl View for LevelSelectView {
+    fn render(&mut self, phi: &mut Phi, elapsed: f64) -> ViewAction {
+        if phi.events.now.quit {
+            return ViewAction::Quit;
+        }
+
+        if phi.events.now.key_escape == Some(true) {
+            return self.leave(phi);
+        }
+
+        for action in self.ui.handle(&phi.events) {
+            match action {
+                LevelSelectAction::Play(i) => {
+                    let error = match self.levels[i] {
+                        Ok(ref level) if self.editing => return ViewAction::ChangeView(or_error(
+                            ::views::level_editor::LevelEditorView::with_level(
+                                phi, self.backgrounds.clone(), level.clone(), self.paths[i].clone(), 0.0))),
+                        Ok(ref level) => return ViewAction::ChangeView(or_error(
+                            ::views::game::ShipView::with_level(
+                                phi, self.backgrounds.clone(), level.clone()))),
+                        Err(ref error) => error.clone(),
+                    };
+
+                    // Show what is wrong with the file, keeping it selected.
+                    let focus = self.ui.focus();
+                    self.ui = LevelSelectView::build_ui(&self.levels, self.editing, Some(&error));
+                    self.ui.set_focus(focus);
+                },
+
+                LevelSelectAction::New =>
+                    return ViewAction::ChangeView(or_error(
+                        ::views::level_editor::LevelEditorView::with_level(
+                            phi, self.backgrounds.clone(), Level::new("New level"), Level::unused_path(), 0.0))),
+
+                LevelSelectAction::Back =>
+                    return self.leave(phi),
+            }
+        }
+
+        // Clear the screen
+        phi.renderer.set_draw_color(Color::RGB(0, 0, 0));
+        phi.renderer.clear();
+
+        // Render backgrounds
+        self.backgrounds.back.render(&mut phi.renderer, elapsed);
+        self.backgrounds.middle.render(&mut phi.renderer, elapsed);
+        self.backgrounds.front.render(&mut phi.renderer, elapsed);
+
+        self.ui.render(phi, elapsed);
+
+        ViewAction::None
+    }
+}
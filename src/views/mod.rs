@@ -1,3 +1,8 @@
+pub mod error;
 pub mod game;
+pub mod game_over;
+pub mod level_editor;
+pub mod level_select;
 pub mod main_menu;
+pub mod options;
 pub mod shared;
@@ -0,0 +1,208 @@
+use ::phi::{Phi, View, ViewAction};
+use ::phi::settings::{RESOLUTIONS, WindowMode};
+use ::phi::ui::{Align, Button, Choice, Insets, Panel, Slider, Stack, Toggle, Ui};
+use ::sdl2::pixels::Color;
+use ::views::shared::{BgSet, menu_theme};
+
+/// Hold the fire key to shoot continuously, rather than once per press.
+pub const AUTO_FIRE: &'static str = "auto_fire";
+/// Draw the bounding box of the player's ship.
+pub const SHOW_HITBOXES: &'static str = "show_hitboxes";
+
+/// The gameplay switches offered in the menu: their name in the settings,
+/// their label and their default value.
+const GAMEPLAY_OPTIONS: [(&'static str, &'static str, bool); 2] = [
+    (AUTO_FIRE, "Auto fire", true),
+    (SHOW_HITBOXES, "Show hitboxes", false),
+];
+
+/// The key bindings which may be changed: their name in `Bindings` and their
+/// label.
+const REBINDABLE: [(&'static str, &'static str); 5] = [
+    ("key_up", "Up"),
+    ("key_down", "Down"),
+    ("key_left", "Left"),
+    ("key_right", "Right"),
+    ("key_fire", "Fire"),
+];
+
+#[derive(Clone, Copy)]
+enum OptionsAction {
+    Volume(f64),
+    WindowMode(usize),
+    Resolution(usize),
+    /// Wait for a key to bind to the given entry of `REBINDABLE`.
+    Rebind(usize),
+    /// Switch the given entry of `GAMEPLAY_OPTIONS`.
+    Gameplay(usize, bool),
+    Back,
+}
+
+pub struct OptionsView {
+    ui: Ui<OptionsAction>,
+    /// The entry of `REBINDABLE` waiting for a key to be pressed, if any
+    rebinding: Option<usize>,
+    /// Whether it was pushed over another view, e.g. a paused game, to which
+    /// it goes back rather than to the main menu
+    pushed: bool,
+
+    backgrounds: BgSet,
+}
+
+impl OptionsView {
+    pub fn with_backgrounds(phi: &mut Phi, backgrounds: BgSet) -> OptionsView {
+        OptionsView {
+            ui: OptionsView::build_ui(phi, None),
+            rebinding: None,
+            pushed: false,
+            backgrounds: backgrounds,
+        }
+    }
+
+    /// The options menu, shown over another view which is resumed when
+    /// leaving it.
+    pub fn pushed(phi: &mut Phi, backgrounds: BgSet) -> OptionsView {
+        OptionsView {
+            pushed: true,
+            ..OptionsView::with_backgrounds(phi, backgrounds)
+        }
+    }
+
+    /// Creates the menu reflecting the current settings.
+    fn build_ui(phi: &Phi, rebinding: Option<usize>) -> Ui<OptionsAction> {
+        let settings = &phi.settings;
+
+        let modes: Vec<String> = WindowMode::all().iter()
+            .map(|mode| match *mode {
+                WindowMode::Windowed => "Windowed",
+                WindowMode::Fullscreen => "Fullscreen",
+                WindowMode::Borderless => "Borderless",
+            }.to_string())
+            .collect();
+        let mode = WindowMode::all().iter()
+            .position(|&mode| mode == settings.window_mode).unwrap_or(0);
+
+        let resolutions: Vec<String> = RESOLUTIONS.iter()
+            .map(|&(w, h)| format!("{}x{}", w, h))
+            .collect();
+        let resolution = RESOLUTIONS.iter()
+            .position(|&resolution| resolution == settings.resolution).unwrap_or(0);
+
+        let mut options = Stack::vertical()
+            .align(Align::Stretch)
+            .padding(Insets::symmetric(10.0, 20.0))
+            .with(Slider::new("Volume", (settings.volume * 100.0).round(), 0.0, 100.0, 10.0,
+                              Box::new(|volume| OptionsAction::Volume(volume / 100.0))))
+            .with(Choice::new("Window", &modes, mode,
+                              Box::new(OptionsAction::WindowMode)))
+            .with(Choice::new("Resolution", &resolutions, resolution,
+                              Box::new(OptionsAction::Resolution)));
+
+        for (i, &(name, label)) in REBINDABLE.iter().enumerate() {
+            let key = if rebinding == Some(i) {
+                "...".to_string()
+            } else {
+                settings.bindings.get(name).map_or(String::new(), |keycode| keycode.name())
+            };
+
+            options = options.with(Button::new(&format!("{}: {}", label, key),
+                                               OptionsAction::Rebind(i)));
+        }
+
+        for (i, &(name, label, default)) in GAMEPLAY_OPTIONS.iter().enumerate() {
+            options = options.with(Toggle::new(label, settings.gameplay(name, default),
+                                               Box::new(move |on| OptionsAction::Gameplay(i, on))));
+        }
+
+        options = options.with(Button::new("Back", OptionsAction::Back));
+
+        Ui::new(Panel::new(options).min_width(480.0), menu_theme())
+    }
+
+    /// Rebuilds the menu after the settings changed, keeping the focus where
+    /// it was.
+    fn refresh(&mut self, phi: &Phi) {
+        let focus = self.ui.focus();
+        self.ui = OptionsView::build_ui(phi, self.rebinding);
+        self.ui.set_focus(focus);
+    }
+
+    /// Saves the settings and goes back to the main menu, or to the view
+    /// below.
+    fn leave(&self, phi: &mut Phi) -> ViewAction {
+        phi.save_settings();
+        if self.pushed {
+            return ViewAction::PopView;
+        }
+        ViewAction::ChangeView(Box::new(
+            ::views::main_menu::MainMenuView::with_backgrounds(phi, self.backgrounds.clone())))
+    }
+}
+
+impl View for OptionsView {
+    fn render(&mut self, phi: &mut Phi, elapsed: f64) -> ViewAction {
+        if phi.events.now.quit {
+            phi.save_settings();
+            return ViewAction::Quit;
+        }
+
+        if let Some(i) = self.rebinding {
+            // Escape cancels, any other key becomes the new binding.
+            if phi.events.now.key_escape == Some(true) {
+                self.rebinding = None;
+                self.refresh(phi);
+            } else if let Some(keycode) = phi.events.now.keycode {
+                phi.settings.bindings.set(REBINDABLE[i].0, keycode);
+                phi.events.bindings = phi.settings.bindings;
+                self.rebinding = None;
+                self.refresh(phi);
+            }
+        } else {
+            if phi.events.now.key_escape == Some(true) {
+                return self.leave(phi);
+            }
+
+            for action in self.ui.handle(&phi.events) {
+                match action {
+                    OptionsAction::Volume(volume) =>
+                        phi.settings.volume = volume.max(0.0).min(1.0),
+
+                    OptionsAction::WindowMode(i) => {
+                        phi.settings.window_mode = WindowMode::all()[i];
+                        phi.apply_settings();
+                    },
+
+                    OptionsAction::Resolution(i) => {
+                        phi.settings.resolution = RESOLUTIONS[i];
+                        phi.apply_settings();
+                    },
+
+                    OptionsAction::Rebind(i) => {
+                        self.rebinding = Some(i);
+                        self.refresh(phi);
+                    },
+
+                    OptionsAction::Gameplay(i, on) => {
+                        phi.settings.gameplay.insert(GAMEPLAY_OPTIONS[i].0.to_string(), on);
+                    },
+
+                    OptionsAction::Back =>
+                        return self.leave(phi),
+                }
+            }
+        }
+
+        // Clear the screen
+        phi.renderer.set_draw_color(Color::RGB(0, 0, 0));
+        phi.renderer.clear();
+
+        // Render backgrounds
+        self.backgrounds.back.render(&mut phi.renderer, elapsed);
+        self.backgrounds.middle.render(&mut phi.renderer, elapsed);
+        self.backgrounds.front.render(&mut phi.renderer, elapsed);
+
+        self.ui.render(phi, elapsed);
+
+        ViewAction::None
+    }
+}
@@ -1,5 +1,8 @@
+use ::phi::config::{Config, Fields, Line, LoadError};
 use ::phi::data::Rectangle;
-use ::phi::gfx::{CopySprite, Sprite};
+use ::phi::error::PhiResult;
+use ::phi::gfx::{CopySprite, Sprite, screen_size};
+use ::phi::ui::Theme;
 use ::sdl2::render::Renderer;
 
 #[derive(Clone)]
@@ -21,18 +24,18 @@ impl Background {
         }
 
         // Determine the scale ratio of the window to the sprite
-        let (win_w, win_h) = renderer.output_size().unwrap();
-        let scale = win_h as f64 / sprite_h;
+        let (win_w, win_h) = screen_size(renderer);
+        let scale = win_h / sprite_h;
 
         // Render as many copies of background as needed to fill the screen.
         let mut physical_left = -self.pos * scale;
 
-        while physical_left < win_w as f64 {
+        while physical_left < win_w {
             renderer.copy_sprite(&self.sprite, Rectangle {
                 x: physical_left,
                 y: 0.0,
                 w: sprite_w * scale,
-                h: win_h as f64,
+                h: win_h,
             });
 
             physical_left += sprite_w * scale;
@@ -40,6 +43,22 @@ impl Background {
     }
 }
 
+/// The font used for all of the game's text.
+pub const FONT_PATH: &'static str = "assets/belligerent.ttf";
+
+/// The look shared by all of the game's menus.
+pub fn menu_theme() -> Theme {
+    Theme::with_font(FONT_PATH)
+}
+
+/// Pixels per second of each layer of the background, from back to front,
+/// when scrolling at the usual speed.
+const BG_SPEEDS: [f64; 3] = [20.0, 40.0, 80.0];
+/// The image of each layer of the background, from back to front.
+const BG_PATHS: [&'static str; 3] = ["assets/starBG.png", "assets/starMG.png", "assets/starFG.png"];
+/// The keys under which each layer is saved, from back to front.
+const BG_KEYS: [&'static str; 3] = ["back", "middle", "front"];
+
 #[derive(Clone)]
 pub struct BgSet {
     pub back: Background,
@@ -48,23 +67,66 @@ pub struct BgSet {
 }
 
 impl BgSet {
-    pub fn new(renderer: &Renderer) -> BgSet {
-        BgSet {
+    pub fn new(renderer: &Renderer) -> PhiResult<BgSet> {
+        Ok(BgSet {
             back: Background {
                 pos: 0.0,
-                vel: 20.0,
-                sprite: Sprite::load(&renderer, "assets/starBG.png").unwrap(),
+                vel: BG_SPEEDS[0],
+                sprite: try!(Sprite::load(&renderer, BG_PATHS[0])),
             },
             middle: Background {
                 pos: 0.0,
-                vel: 40.0,
-                sprite: Sprite::load(&renderer, "assets/starMG.png").unwrap(),
+                vel: BG_SPEEDS[1],
+                sprite: try!(Sprite::load(&renderer, BG_PATHS[1])),
             },
             front: Background {
                 pos: 0.0,
-                vel: 80.0,
-                sprite: Sprite::load(&renderer, "assets/starFG.png").unwrap(),
+                vel: BG_SPEEDS[2],
+                sprite: try!(Sprite::load(&renderer, BG_PATHS[2])),
             },
+        })
+    }
+
+    /// Scrolls every layer `factor` times as fast as usual.
+    pub fn set_scroll(&mut self, factor: f64) {
+        self.back.vel = BG_SPEEDS[0] * factor;
+        self.middle.vel = BG_SPEEDS[1] * factor;
+        self.front.vel = BG_SPEEDS[2] * factor;
+    }
+
+    /// Writes the image, position and speed of every layer to the
+    /// `backgrounds` section of a save file.
+    pub fn save(&self, config: &mut Config) {
+        let layers = [&self.back, &self.middle, &self.front];
+
+        for i in 0..layers.len() {
+            config.set("backgrounds", BG_KEYS[i],
+                       Line::new().word(BG_PATHS[i]).word(layers[i].pos).word(layers[i].vel));
+        }
+    }
+
+    /// Reads back what `save` wrote, loading the images it names. Missing
+    /// layers are as usual.
+    pub fn load(renderer: &Renderer, config: &Config) -> Result<BgSet, LoadError> {
+        let mut backgrounds = try!(BgSet::new(renderer));
+
+        for entry in config.entries().iter().filter(|entry| entry.section == "backgrounds") {
+            let mut fields = Fields::new(entry);
+
+            let layer = match &entry.key[..] {
+                "back" => &mut backgrounds.back,
+                "middle" => &mut backgrounds.middle,
+                "front" => &mut backgrounds.front,
+                _ => return Err(LoadError::from(fields.error("unknown layer"))),
+            };
+
+            let path: String = try!(fields.word());
+            layer.sprite = try!(Sprite::load(renderer, &path));
+            layer.pos = try!(fields.word());
+            layer.vel = try!(fields.word());
+            try!(fields.end());
         }
+
+        Ok(backgrounds)
     }
 }